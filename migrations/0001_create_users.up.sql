@@ -0,0 +1,14 @@
+-- Accounts that own wallets; rows are inserted by Database::create_user
+-- gen_random_uuid() is built in from PostgreSQL 13; older servers need pgcrypto
+CREATE TABLE users (
+    id                    UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email                 TEXT NOT NULL UNIQUE,
+    full_name             TEXT NOT NULL,
+    cnic                  TEXT NOT NULL UNIQUE,
+    wallet_id             TEXT NOT NULL UNIQUE,
+    public_key            TEXT NOT NULL,
+    encrypted_private_key TEXT NOT NULL,
+    is_verified           BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at            TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at            TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+-- One wallet per user, keyed like users.wallet_id
+CREATE TABLE wallets (
+    wallet_id  TEXT PRIMARY KEY REFERENCES users (wallet_id) ON DELETE CASCADE,
+    balance    DOUBLE PRECISION NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+DROP VIEW IF EXISTS wallet_balances, chain_transfers;
+DROP TABLE IF EXISTS chain_outputs, chain_inputs, chain_transactions, chain_blocks;
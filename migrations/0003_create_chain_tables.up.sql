@@ -0,0 +1,54 @@
+-- Tables and views for the confirmed chain, written by PgChainStore
+CREATE TABLE IF NOT EXISTS chain_blocks (
+    hash          TEXT PRIMARY KEY,
+    height        BIGINT NOT NULL UNIQUE,
+    previous_hash TEXT NOT NULL,
+    merkle_root   TEXT NOT NULL,
+    timestamp     BIGINT NOT NULL,
+    target        TEXT NOT NULL,
+    nonce         NUMERIC(20, 0) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS chain_transactions (
+    txid             TEXT PRIMARY KEY,
+    block_hash       TEXT NOT NULL REFERENCES chain_blocks (hash) ON DELETE CASCADE,
+    block_index      BIGINT NOT NULL,
+    position         INTEGER NOT NULL,
+    sender           TEXT,
+    transaction_type TEXT NOT NULL,
+    timestamp        BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS chain_transactions_sender ON chain_transactions (sender);
+
+CREATE TABLE IF NOT EXISTS chain_inputs (
+    txid      TEXT NOT NULL REFERENCES chain_transactions (txid) ON DELETE CASCADE,
+    vin       INTEGER NOT NULL,
+    prev_txid TEXT NOT NULL,
+    prev_vout INTEGER NOT NULL,
+    address   TEXT NOT NULL,
+    PRIMARY KEY (txid, vin)
+);
+CREATE INDEX IF NOT EXISTS chain_inputs_spends ON chain_inputs (prev_txid, prev_vout);
+
+CREATE TABLE IF NOT EXISTS chain_outputs (
+    txid     TEXT NOT NULL REFERENCES chain_transactions (txid) ON DELETE CASCADE,
+    vout     INTEGER NOT NULL,
+    receiver TEXT NOT NULL,
+    amount   INTEGER NOT NULL,
+    PRIMARY KEY (txid, vout)
+);
+CREATE INDEX IF NOT EXISTS chain_outputs_receiver ON chain_outputs (receiver);
+
+CREATE OR REPLACE VIEW chain_transfers AS
+SELECT t.txid, t.sender, o.receiver, o.amount, t.block_index, t.transaction_type, t.timestamp
+FROM chain_transactions t
+JOIN chain_outputs o ON o.txid = t.txid
+WHERE t.sender IS DISTINCT FROM o.receiver;
+
+CREATE OR REPLACE VIEW wallet_balances AS
+SELECT o.receiver AS address, SUM(o.amount) AS balance, COUNT(*) AS unspent_outputs
+FROM chain_outputs o
+WHERE NOT EXISTS (
+    SELECT 1 FROM chain_inputs i WHERE i.prev_txid = o.txid AND i.prev_vout = o.vout
+)
+GROUP BY o.receiver;
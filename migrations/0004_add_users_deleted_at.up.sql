@@ -0,0 +1,2 @@
+-- Soft delete: deleted users keep their row, and with it their email, CNIC and wallet_id
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
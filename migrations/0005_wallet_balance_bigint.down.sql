@@ -0,0 +1 @@
+ALTER TABLE wallets ALTER COLUMN balance TYPE DOUBLE PRECISION;
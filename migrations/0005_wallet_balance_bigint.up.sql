@@ -0,0 +1,6 @@
+-- Balances are whole coins like the chain's amounts; a float cannot hold money exactly
+-- Fractions left over in the old DOUBLE PRECISION column are rounded away
+ALTER TABLE wallets
+    ALTER COLUMN balance DROP DEFAULT,
+    ALTER COLUMN balance TYPE BIGINT USING round(balance)::BIGINT,
+    ALTER COLUMN balance SET DEFAULT 0;
@@ -4,7 +4,7 @@ use tokio::io::AsyncReadExt;
 async fn main() {
     let mut file = tokio::fs::File::open("./data/hello.txt").await.unwrap();
     let mut buf = vec![0; 1024];
-    file.read(&mut buf).await.unwrap();
-    let content = String::from_utf8_lossy(&buf);
+    let n = file.read(&mut buf).await.unwrap();
+    let content = String::from_utf8_lossy(&buf[..n]);
     println!("File content: {}", content);
 }
\ No newline at end of file
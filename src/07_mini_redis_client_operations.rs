@@ -36,7 +36,7 @@ async fn main() -> Result<()> {
 
     // Example 3: Store and retrieve messages
     println!("Example 3: Message queue simulation");
-    let messages = vec![
+    let messages = [
         "First message",
         "Second message",
         "Third message",
@@ -4,5 +4,5 @@ use rust101::run;
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
     let address = TcpListener::bind("127.0.0.1:8000")?;
-    run(address)?.await
+    run(address, None)?.await
 }
\ No newline at end of file
@@ -50,7 +50,7 @@ impl Block {
         let target = "0".repeat(difficulty);
         println!("Mining block {}...", self.id);
 
-        while &self.hash[..difficulty] != target {
+        while self.hash[..difficulty] != target {
             self.nonce += 1;
             self.hash = self.calculate_hash();
         }
@@ -114,7 +114,7 @@ impl Blockchain {
 
         // Check proof-of-work
         let target = "0".repeat(self.difficulty);
-        if &block.hash[..self.difficulty] != target {
+        if block.hash[..self.difficulty] != target {
             println!("❌ Block {} doesn't meet difficulty requirement", block.id);
             return false;
         }
@@ -19,6 +19,51 @@ pub struct Transaction {
     pub timestamp: i64,
     pub created_at: DateTime<Utc>,
 }
+
+impl Transaction {
+    // Calculate hash over every field except the hash itself. Each text field is
+    // length-prefixed so text cannot shift between neighbouring fields unnoticed,
+    // and optional fields start with a byte saying whether they are present.
+    pub fn calculate_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hash_text(&mut hasher, &self.id.to_string());
+        hash_text(&mut hasher, &self.sender_wallet_id);
+        hash_text(&mut hasher, &self.receiver_wallet_id);
+        hash_text(&mut hasher, &self.amount.to_string());
+        match &self.note {
+            Some(note) => {
+                hasher.update([1]);
+                hash_text(&mut hasher, note);
+            }
+            None => hasher.update([0]),
+        }
+        hash_text(&mut hasher, &self.signature);
+        match self.block_index {
+            Some(index) => {
+                hasher.update([1]);
+                hasher.update(index.to_le_bytes());
+            }
+            None => hasher.update([0]),
+        }
+        hash_text(&mut hasher, &self.transaction_type);
+        hash_text(&mut hasher, &self.timestamp.to_string());
+        hash_text(&mut hasher, &self.created_at.to_rfc3339());
+        let result = hasher.finalize();
+
+        let mut hash_string = String::new();
+        for byte in result.iter() {
+            write!(&mut hash_string, "{:02x}", byte).unwrap();
+        }
+        hash_string
+    }
+}
+
+// Feed a length-prefixed text field into the hash
+fn hash_text(hasher: &mut Sha256, field: &str) {
+    hasher.update((field.len() as u64).to_le_bytes());
+    hasher.update(field.as_bytes());
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub id: u64,
@@ -129,7 +174,7 @@ impl Block {
         let target = "0".repeat(difficulty);
         println!("Mining block {}...", self.id);
 
-        while &self.hash[..difficulty] != target {
+        while self.hash[..difficulty] != target {
             self.nonce += 1;
             self.hash = self.calculate_hash();
         }
@@ -193,11 +238,25 @@ impl Blockchain {
 
         // Check proof-of-work
         let target = "0".repeat(self.difficulty);
-        if &block.hash[..self.difficulty] != target {
+        if block.hash[..self.difficulty] != target {
             println!("❌ Block {} doesn't meet difficulty requirement", block.id);
             return false;
         }
 
+        // Check every transaction still matches its hash
+        for tx in &block.transactions {
+            if tx.transaction_hash != tx.calculate_hash() {
+                println!("❌ Invalid hash for transaction {} in block {}", tx.id, block.id);
+                return false;
+            }
+        }
+
+        // Check the merkle root still commits to these transactions
+        if block.merkle_root.as_deref() != Some(block.calculate_merkle_root().as_str()) {
+            println!("❌ Invalid merkle root for block {}", block.id);
+            return false;
+        }
+
         true
     }
 
@@ -248,19 +307,9 @@ fn create_transaction(
     let timestamp = Utc::now().timestamp();
     let created_at = Utc::now();
 
-    // Create a simple transaction hash
-    let data = format!("{}{}{}{}", sender, receiver, amount, timestamp);
-    let mut hasher = Sha256::new();
-    hasher.update(data.as_bytes());
-    let result = hasher.finalize();
-    let mut transaction_hash = String::new();
-    for byte in result.iter() {
-        write!(&mut transaction_hash, "{:02x}", byte).unwrap();
-    }
-
-    Transaction {
+    let mut transaction = Transaction {
         id,
-        transaction_hash,
+        transaction_hash: String::new(),
         sender_wallet_id: sender.to_string(),
         receiver_wallet_id: receiver.to_string(),
         amount,
@@ -270,7 +319,9 @@ fn create_transaction(
         transaction_type: transaction_type.to_string(),
         timestamp,
         created_at,
-    }
+    };
+    transaction.transaction_hash = transaction.calculate_hash();
+    transaction
 }
 
 fn main() {
@@ -334,5 +385,15 @@ fn main() {
         println!("❌ Tampering detected! Blockchain is invalid.");
     }
 
+    println!("\nRecomputing the tampered transaction's hash to cover it up...");
+    if let Some(tx) = blockchain.blocks[1].transactions.first_mut() {
+        tx.transaction_hash = tx.calculate_hash();
+    }
+
+    println!("Validating blockchain after the cover-up...\n");
+    if !blockchain.is_chain_valid() {
+        println!("❌ Tampering detected! The merkle root no longer matches.");
+    }
+
     println!("\n✨ Blockchain demonstration complete!");
 }
\ No newline at end of file
@@ -0,0 +1,226 @@
+// 🌐 Blockchain node: the `rust101::chain` blockchain shared between processes
+// Each node keeps its own chain and mempool and talks to its peers over TCP with the
+// `rust101::p2p` protocol. Start a few on localhost and watch blocks and
+// transactions spread:
+//
+//   cargo run --bin node -- --listen 127.0.0.1:8333 --mine
+//   cargo run --bin node -- --listen 127.0.0.1:8334 --connect 127.0.0.1:8333
+//   cargo run --bin node -- --connect 127.0.0.1:8334 --mine
+//
+// Seeds, connection limits, the ban length and where the address book is kept come
+// from P2P_* variables (see `NodeConfig::from_env`); `--http 127.0.0.1:8000` serves
+// the peers, bans and address book as JSON under /p2p/.
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use rust101::chain::{Block, Blockchain, ChainEvent, ChainParams, Fee, Transaction, Wallet};
+use rust101::p2p::{Node, NodeConfig, NodeEvent, P2pError};
+
+/// Every node must start from the same genesis block to talk to each other
+const GENESIS_ADDRESS: &str = "rust101-network-genesis";
+const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+const DIFFICULTY: usize = 4;
+
+const USAGE: &str = "usage: node [--listen ADDR] [--connect ADDR]... [--mine] [--http ADDR]";
+
+struct Options {
+    listen: Option<String>,
+    connect: Vec<String>,
+    mine: bool,
+    http: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        listen: None,
+        connect: Vec::new(),
+        mine: false,
+        http: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => options.listen = Some(args.next().ok_or("--listen needs an address")?),
+            "--connect" => options.connect.push(args.next().ok_or("--connect needs an address")?),
+            "--mine" => options.mine = true,
+            "--http" => options.http = Some(args.next().ok_or("--http needs an address")?),
+            other => return Err(format!("unknown argument {}", other)),
+        }
+    }
+    Ok(options)
+}
+
+/// The chain every node on this network shares
+fn network_chain() -> Blockchain {
+    let params = ChainParams::with_difficulty(DIFFICULTY);
+    let genesis = Block::genesis(&params, GENESIS_ADDRESS, GENESIS_TIMESTAMP);
+    Blockchain::with_genesis(params, genesis).expect("genesis block is valid")
+}
+
+/// Print what happens on the network
+fn report_events(node: &Node, mut chain_events: tokio::sync::broadcast::Receiver<ChainEvent>) {
+    let mut events = node.subscribe();
+    tokio::spawn(async move {
+        let mut synced = true;
+        while let Ok(event) = events.recv().await {
+            match event {
+                NodeEvent::PeerConnected(peer) => println!(
+                    "🤝 Peer {} connected: {} ({}, height {}, {})",
+                    peer.id,
+                    peer.addr,
+                    peer.version.user_agent,
+                    peer.version.best_height,
+                    if peer.inbound { "inbound" } else { "outbound" }
+                ),
+                NodeEvent::PeerDisconnected { id, addr, reason } => {
+                    println!("👋 Peer {} ({}) disconnected: {}", id, addr, reason)
+                }
+                NodeEvent::PeerBanned(ban) => {
+                    println!("🚫 Banned {} until {}: {}", ban.ip, ban.until.format("%Y-%m-%d %H:%M"), ban.reason)
+                }
+                NodeEvent::BlockRejected { peer, hash, error } => {
+                    println!("❌ Block {} from peer {} rejected: {}", &hash[..16], peer, error)
+                }
+                NodeEvent::TransactionRejected { peer, txid, error } => {
+                    println!("❌ Transaction {} from peer {} rejected: {}", &txid[..16], peer, error)
+                }
+                NodeEvent::SyncProgress(progress) if !progress.is_synced() => {
+                    synced = false;
+                    println!(
+                        "⏳ Syncing: block {} of {} ({:.1}%), {} in flight, {} buffered",
+                        progress.block_height,
+                        progress.header_height,
+                        progress.percent(),
+                        progress.blocks_in_flight,
+                        progress.blocks_buffered
+                    );
+                }
+                NodeEvent::SyncProgress(progress) => {
+                    if !synced {
+                        println!("✅ Synced at height {}", progress.block_height);
+                    }
+                    synced = true;
+                }
+            }
+        }
+    });
+    tokio::spawn(async move {
+        while let Ok(event) = chain_events.recv().await {
+            match event {
+                ChainEvent::BlockConnected(block) => println!(
+                    "📦 Block {} connected: {} ({} transactions)",
+                    block.id,
+                    &block.hash[..16],
+                    block.transactions.len()
+                ),
+                ChainEvent::BlockDisconnected(block) => println!("↩️  Block {} disconnected", block.id),
+                ChainEvent::Reorg { fork_height, disconnected, connected, .. } => println!(
+                    "🔀 Reorg at height {}: -{} +{} blocks",
+                    fork_height, disconnected, connected
+                ),
+            }
+        }
+    });
+}
+
+/// Mine forever, paying a coin to a fresh address whenever the last payment confirmed
+async fn mine(node: Node, wallet: Wallet) -> Result<(), P2pError> {
+    let address = wallet.get_address();
+    let mut pending: Option<String> = None;
+    loop {
+        match node.mine_block(&address).await {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                println!("⏭️  Another block arrived first, mining on the new tip");
+                continue;
+            }
+            Err(P2pError::NodeStopped) => return Err(P2pError::NodeStopped),
+            Err(error) => {
+                println!("⚠️  Could not mine a block: {}", error);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        }
+
+        if let Some(txid) = &pending {
+            let txid = txid.clone();
+            if node.mempool(move |mempool| mempool.contains(&txid)).await? {
+                continue;
+            }
+        }
+        let payer = wallet.clone();
+        let recipient = Wallet::new().get_address();
+        let payment = node
+            .chain(move |chain| {
+                Transaction::new_utxo_transaction(&payer, &recipient, 1, Fee::Fixed(1), &chain.utxo_set)
+            })
+            .await?;
+        let sent = match payment {
+            Ok(payment) => {
+                pending = Some(payment.id.clone());
+                node.submit_transaction(payment).await.map(|_| ())
+            }
+            Err(error) => Err(error.into()),
+        };
+        match sent {
+            Ok(()) => println!("💸 Sent a payment to the network"),
+            Err(P2pError::NodeStopped) => return Err(P2pError::NodeStopped),
+            Err(error) => println!("⚠️  No payment this round: {}", error),
+        }
+
+        // Leave peers a moment to mine too
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    dotenv::dotenv().ok();
+    let config = NodeConfig::from_env()?;
+    let chain = network_chain();
+    println!("🔗 Genesis block {}", chain.blocks[0].hash);
+    let chain_events = chain.subscribe();
+    let node = Node::start(chain, config)?;
+    report_events(&node, chain_events);
+
+    if let Some(addr) = &options.listen {
+        let bound = node.listen(addr).await?;
+        println!("👂 Listening on {}", bound);
+    }
+    for addr in &options.connect {
+        match node.connect(addr).await {
+            Ok(_) => println!("📞 Connecting to {}", addr),
+            Err(error) => println!("⚠️  Could not connect to {}: {}", addr, error),
+        }
+    }
+
+    if let Some(addr) = &options.http {
+        let server = rust101::run(TcpListener::bind(addr)?, Some(node.clone()))?;
+        tokio::spawn(server);
+        println!("📊 Peer state at http://{}/p2p/peers", addr);
+    }
+
+    if options.mine {
+        let wallet = Wallet::new();
+        println!("⛏️  Mining to {}", wallet.get_address());
+        let miner = node.clone();
+        tokio::spawn(async move {
+            if let Err(error) = mine(miner, wallet).await {
+                println!("❌ Mining stopped: {}", error);
+            }
+        });
+    }
+
+    tokio::signal::ctrl_c().await?;
+    let height = node.chain(|chain| chain.get_latest_block().id).await?;
+    println!("\n🛑 Shutting down at height {}", height);
+    Ok(())
+}
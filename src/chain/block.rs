@@ -0,0 +1,168 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::encoding::{write_compact_size, Decode, Encode, Reader};
+use super::error::DecodeError;
+use super::hash::hash_bytes;
+use super::header::{BlockHeader, BLOCK_VERSION, HEADER_SIZE};
+use super::merkle::{self, MerkleProof};
+use super::miner::{Miner, MiningHandle};
+use super::params::ChainParams;
+use super::pow::Target;
+use super::transaction::Transaction;
+
+/// Block structure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    pub id: u64,
+    pub hash: String,
+    pub previous_hash: String,
+    pub timestamp: i64,
+    pub target: Target,            // Proof-of-work target the hash must meet
+    pub nonce: u64,
+    pub transactions: Vec<Transaction>,
+    pub merkle_root: String,
+}
+
+impl Block {
+    /// Create new block with transactions, to be mined against `target`
+    pub fn new(id: u64, previous_hash: String, transactions: Vec<Transaction>, target: Target) -> Self {
+        let timestamp = Utc::now().timestamp();
+        let mut block = Block {
+            id,
+            hash: String::new(),
+            previous_hash,
+            timestamp,
+            target,
+            nonce: 0,
+            transactions,
+            merkle_root: String::new(),
+        };
+        block.merkle_root = block.calculate_merkle_root();
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    /// Genesis block paying the initial subsidy to `address`, stamped `timestamp`
+    ///
+    /// Mining starts at nonce 0 on one thread, so nodes that agree on the parameters,
+    /// address and timestamp build the same genesis block and can share a chain.
+    pub fn genesis(params: &ChainParams, address: &str, timestamp: i64) -> Self {
+        let subsidy = params.allowed_subsidy(0, 0);
+        let mut coinbase = Transaction::new_coinbase(
+            address,
+            i32::try_from(subsidy).expect("initial subsidy fits in an output"),
+            Some("Genesis Block".to_string()),
+        );
+        coinbase.timestamp = timestamp;
+        coinbase.id = coinbase.calculate_hash();
+
+        let mut genesis = Block::new(0, "0".repeat(64), vec![coinbase], params.pow_limit);
+        genesis.timestamp = timestamp;
+        genesis.mine_block();
+        genesis
+    }
+
+    /// Calculate merkle root from transaction hashes
+    ///
+    /// Each parent is the SHA-256 of its two children's raw 32-byte hashes.
+    pub fn calculate_merkle_root(&self) -> String {
+        if self.transactions.is_empty() {
+            return "0".repeat(64);
+        }
+        let levels = self.merkle_levels();
+        hex::encode(levels[levels.len() - 1][0])
+    }
+
+    /// Whether repeated transactions make the merkle tree ambiguous (CVE-2012-2459)
+    ///
+    /// Such a block has the same merkle root as one without the repeats, so it is
+    /// rejected outright rather than judged by its transactions.
+    pub fn has_mutated_merkle_tree(&self) -> bool {
+        merkle::mutated(&self.merkle_levels())
+    }
+
+    /// Proof that `txid` is in this block, for clients that only keep headers
+    pub fn merkle_proof(&self, txid: &str) -> Option<MerkleProof> {
+        let index = self.transactions.iter().position(|tx| tx.id == txid)?;
+        Some(MerkleProof::from_levels(txid, index, &self.merkle_levels()))
+    }
+
+    fn merkle_levels(&self) -> Vec<Vec<[u8; 32]>> {
+        merkle::levels(self.transactions.iter().map(|tx| hash_bytes(&tx.id)).collect())
+    }
+
+    /// Assemble a block from a header and its body
+    pub fn from_header(id: u64, header: BlockHeader, transactions: Vec<Transaction>) -> Self {
+        Block {
+            id,
+            hash: header.hash(),
+            previous_hash: header.previous_hash,
+            timestamp: header.timestamp,
+            target: header.target,
+            nonce: header.nonce,
+            transactions,
+            merkle_root: header.merkle_root,
+        }
+    }
+
+    /// The block's header, all that proof-of-work covers
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            version: BLOCK_VERSION,
+            previous_hash: self.previous_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            timestamp: self.timestamp,
+            target: self.target,
+            nonce: self.nonce,
+        }
+    }
+
+    /// Binary form of the header
+    pub fn header_bytes(&self) -> [u8; HEADER_SIZE] {
+        self.header().to_bytes()
+    }
+
+    /// Calculate block hash: SHA-256 of the binary header
+    pub fn calculate_hash(&self) -> String {
+        self.header().hash()
+    }
+
+    /// Check whether the stored hash satisfies the block's target
+    pub fn meets_target(&self) -> bool {
+        self.target.is_met_by(&self.hash)
+    }
+
+    /// Mine block with proof-of-work on the current thread
+    ///
+    /// Use a [`Miner`] directly for more threads, cancellation or hashrate.
+    pub fn mine_block(&mut self) {
+        Miner::new(1).mine(self, &MiningHandle::new());
+    }
+}
+
+/// header | height u64 | transaction count | transactions
+///
+/// The hash is not encoded; it is recomputed from the header. Hashes come back as
+/// lowercase hex.
+impl Encode for Block {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header().encode_to(out);
+        out.extend(self.id.to_le_bytes());
+        write_compact_size(out, self.transactions.len() as u64);
+        for tx in &self.transactions {
+            tx.encode_to(out);
+        }
+    }
+}
+
+impl Decode for Block {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let header = BlockHeader::decode_from(reader)?;
+        let id = reader.u64()?;
+        let transactions = (0..reader.count()?)
+            .map(|_| Transaction::decode_from(reader))
+            .collect::<Result<_, _>>()?;
+        Ok(Block::from_header(id, header, transactions))
+    }
+}
@@ -0,0 +1,669 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+use super::block::Block;
+use super::error::ChainError;
+use super::event::{BlockStatus, ChainEvent};
+use super::header::{median_time_past, validate_header, BlockHeader};
+use super::mempool::Mempool;
+use super::params::ChainParams;
+use super::pow::Target;
+use super::store::ChainStore;
+use super::transaction::{verify_transactions, Transaction};
+use super::utxo::{BlockUndo, UtxoSet};
+
+/// Events buffered per subscriber before it starts missing them
+const EVENT_CAPACITY: usize = 1024;
+
+/// Position of a known block in the block tree
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    height: u64,
+    chain_work: u128,              // Work of this block and all its ancestors
+}
+
+/// Blockchain with UTXO set
+///
+/// `blocks` is the active chain. Blocks on competing branches are kept aside, and
+/// whenever a branch carries more cumulative work than the active chain the UTXO set
+/// is unwound to the fork point and replayed along the heavier branch.
+pub struct Blockchain {
+    pub blocks: Vec<Block>,
+    pub params: ChainParams,
+    pub utxo_set: UtxoSet,        // UTXO set for fast balance queries
+    undo: Vec<BlockUndo>,         // Spent outputs per block, parallel to `blocks`
+    issued: i64,                  // Coins minted by all coinbases so far
+    side_blocks: HashMap<String, Block>, // Known blocks off the active chain, by hash
+    nodes: HashMap<String, Node>, // Every known block, active or not, by hash
+    events: broadcast::Sender<ChainEvent>,
+    store: Option<ChainStore>,    // Where the active chain is persisted, if anywhere
+}
+
+impl Blockchain {
+    /// Create new blockchain with genesis block, starting at `difficulty` leading hex
+    /// zeros under the default consensus parameters
+    pub fn new(difficulty: usize, genesis_address: &str) -> Self {
+        Self::with_params(ChainParams::with_difficulty(difficulty), genesis_address)
+    }
+
+    /// Create new blockchain with genesis block under custom consensus parameters
+    pub fn with_params(params: ChainParams, genesis_address: &str) -> Self {
+        let genesis = Block::genesis(&params, genesis_address, Utc::now().timestamp());
+        Self::with_genesis(params, genesis).expect("a freshly mined genesis block is valid")
+    }
+
+    /// Start a chain from a given genesis block, such as one all nodes of a network
+    /// share (see [`Block::genesis`])
+    pub fn with_genesis(params: ChainParams, genesis: Block) -> Result<Self, ChainError> {
+        if genesis.id != 0 {
+            return Err(ChainError::InvalidHeight(genesis.id));
+        }
+        if genesis.previous_hash != "0".repeat(64) {
+            return Err(ChainError::InvalidPreviousHash(0));
+        }
+        if genesis.hash != genesis.calculate_hash() {
+            return Err(ChainError::InvalidHash(0));
+        }
+        if genesis.merkle_root != genesis.calculate_merkle_root() {
+            return Err(ChainError::InvalidMerkleRoot(0));
+        }
+        if genesis.target != params.pow_limit {
+            return Err(ChainError::UnexpectedTarget(0));
+        }
+        if !genesis.meets_target() {
+            return Err(ChainError::InvalidProofOfWork(0));
+        }
+
+        // Initialize UTXO set with genesis outputs
+        let mut blockchain = Self::empty(params);
+        let node = Node {
+            height: 0,
+            chain_work: genesis.target.work(),
+        };
+        blockchain.nodes.insert(genesis.hash.clone(), node);
+        blockchain.connect_block(genesis)?;
+        Ok(blockchain)
+    }
+
+    /// Resume the chain persisted in `store`, or start a new one there
+    ///
+    /// A store that already holds a chain is loaded as is, from its stored tip and
+    /// UTXO set, without mining or replaying anything; `genesis_address` is only used
+    /// when the store is empty. A store whose UTXO set is not up to date with its tip
+    /// is refused (see [`ChainStore::check_consistency`]). From then on every change of
+    /// the active chain is written to the store.
+    pub fn open(store: ChainStore, params: ChainParams, genesis_address: &str) -> Result<Self, ChainError> {
+        store.check_consistency()?;
+        if store.tip()?.is_none() {
+            let mut blockchain = Self::with_params(params, genesis_address);
+            blockchain.store = Some(store);
+            blockchain.persist()?;
+            return Ok(blockchain);
+        }
+
+        let mut blockchain = Self::empty(params);
+        blockchain.utxo_set = store.utxo_set()?;
+        let mut chain_work = 0u128;
+        for (block, undo) in store.active_chain()? {
+            chain_work = chain_work.saturating_add(block.target.work());
+            let node = Node {
+                height: block.id,
+                chain_work,
+            };
+            blockchain.nodes.insert(block.hash.clone(), node);
+            blockchain.issued += undo.minted(&block);
+            blockchain.blocks.push(block);
+            blockchain.undo.push(undo);
+        }
+        blockchain.store = Some(store);
+        Ok(blockchain)
+    }
+
+    // No blocks at all; every constructor fills in at least the genesis block
+    fn empty(params: ChainParams) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Blockchain {
+            blocks: Vec::new(),
+            params,
+            utxo_set: UtxoSet::new(),
+            undo: Vec::new(),
+            issued: 0,
+            side_blocks: HashMap::new(),
+            nodes: HashMap::new(),
+            events,
+            store: None,
+        }
+    }
+
+    /// The store the active chain is persisted in, if any
+    pub fn store(&self) -> Option<&ChainStore> {
+        self.store.as_ref()
+    }
+
+    /// Drop the in-memory chain and hand back its store, still open, to resume from
+    pub fn into_store(self) -> Option<ChainStore> {
+        self.store
+    }
+
+    // Write the active chain to the store, if there is one
+    fn persist(&self) -> Result<(), ChainError> {
+        self.persist_chain(&self.blocks, &self.undo)
+    }
+
+    fn persist_chain(&self, blocks: &[Block], undo: &[BlockUndo]) -> Result<(), ChainError> {
+        match &self.store {
+            Some(store) => Ok(store.sync(blocks, undo)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Receive every future change to the active chain
+    pub fn subscribe(&self) -> broadcast::Receiver<ChainEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: ChainEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+
+    /// Verify, mine and append a block paying the mining reward to `miner_address`
+    pub fn add_block(
+        &mut self,
+        transactions: Vec<Transaction>,
+        miner_address: &str,
+    ) -> Result<&Block, ChainError> {
+        let mut block = self.prepare_block(transactions, miner_address)?;
+        block.mine_block();
+        self.submit_block(block)?;
+        Ok(self.get_latest_block())
+    }
+
+    /// Build the unmined block that would extend the tip with `transactions`
+    ///
+    /// The transactions are verified up front so no effort is spent mining a block
+    /// that would be rejected. Mine it (for example with a [`Miner`](super::Miner))
+    /// and hand it to [`submit_block`](Self::submit_block).
+    pub fn prepare_block(
+        &self,
+        transactions: Vec<Transaction>,
+        miner_address: &str,
+    ) -> Result<Block, ChainError> {
+        let size: usize = transactions.iter().map(Transaction::size).sum();
+        if size > self.params.max_block_size {
+            return Err(ChainError::BlockTooLarge {
+                block: self.blocks.len() as u64,
+                size,
+                max: self.params.max_block_size,
+            });
+        }
+
+        // Verify all transactions, including spends between them
+        let fees: i64 = verify_transactions(&self.utxo_set, &transactions)?.iter().sum();
+
+        let previous_hash = self.get_latest_block().hash.clone();
+        let id = self.blocks.len() as u64;
+
+        // Create coinbase transaction (mining reward). The height keeps coinbase ids
+        // unique when the same miner wins several blocks within one second.
+        let reward = self.params.allowed_subsidy(id, self.issued) + fees;
+        let coinbase = Transaction::new_coinbase(
+            miner_address,
+            i32::try_from(reward).map_err(|_| ChainError::RewardOverflow(reward))?,
+            Some(format!("Block {} reward to {}", id, miner_address)),
+        );
+
+        // Combine coinbase with other transactions
+        let mut all_transactions = vec![coinbase];
+        all_transactions.extend(transactions);
+
+        // Timestamps must move past the median of recent blocks even when blocks are
+        // mined faster than the clock ticks
+        let mut block = Block::new(id, previous_hash, all_transactions, self.next_target());
+        block.timestamp = block.timestamp.max(self.median_time_past() + 1);
+        block.hash = block.calculate_hash();
+        Ok(block)
+    }
+
+    /// Accept a block from elsewhere (a peer, another miner) into the block tree
+    ///
+    /// The block must build on a known block, carry the right height, and pass
+    /// [`validate_header`] (target, proof-of-work and timestamps). Blocks extending
+    /// the tip are connected at once; blocks on other branches are stored, and if
+    /// their branch now has more cumulative work than the active chain the chain
+    /// reorganizes onto it. A branch that turns out to contain an invalid block is
+    /// discarded and the previous chain restored.
+    ///
+    /// With a store, the new active chain is written before anything is announced. If
+    /// that write fails the chain stays exactly as it was, without the block, and the
+    /// error is [`ChainError::Storage`]: the block was not rejected and may be
+    /// submitted again.
+    pub fn submit_block(&mut self, block: Block) -> Result<BlockStatus, ChainError> {
+        if self.nodes.contains_key(&block.hash) {
+            return Ok(BlockStatus::AlreadyKnown);
+        }
+        let parent = *self
+            .nodes
+            .get(&block.previous_hash)
+            .ok_or_else(|| ChainError::UnknownParent(block.hash.clone()))?;
+
+        let height = parent.height + 1;
+        if block.id != height {
+            return Err(ChainError::InvalidHeight(block.id));
+        }
+        if block.hash != block.calculate_hash() {
+            return Err(ChainError::InvalidHash(block.id));
+        }
+        if block.merkle_root != block.calculate_merkle_root() {
+            return Err(ChainError::InvalidMerkleRoot(block.id));
+        }
+        if block.has_mutated_merkle_tree() {
+            return Err(ChainError::MutatedMerkleTree(block.id));
+        }
+        validate_header(
+            &self.params,
+            height,
+            &block.header(),
+            |h| self.ancestor(&block.previous_hash, h).header(),
+            Utc::now().timestamp(),
+        )?;
+
+        let node = Node {
+            height,
+            chain_work: parent.chain_work.saturating_add(block.target.work()),
+        };
+        let hash = block.hash.clone();
+        self.nodes.insert(hash.clone(), node);
+
+        if block.previous_hash == self.get_latest_block().hash {
+            if let Err(error) = self.connect_block(block) {
+                self.nodes.remove(&hash);
+                return Err(error);
+            }
+            if let Err(error) = self.persist() {
+                self.pop_block();
+                self.nodes.remove(&hash);
+                return Err(error);
+            }
+            self.emit(ChainEvent::BlockConnected(self.get_latest_block().clone()));
+            return Ok(BlockStatus::Connected);
+        }
+
+        self.side_blocks.insert(hash.clone(), block);
+        if node.chain_work > self.tip_work() {
+            return self.reorganize(&hash);
+        }
+        Ok(BlockStatus::SideBranch)
+    }
+
+    /// Block at `height` on the branch ending in `tip`
+    fn ancestor(&self, tip: &str, height: u64) -> &Block {
+        let mut hash = tip;
+        while let Some(block) = self.side_blocks.get(hash) {
+            if block.id == height {
+                return block;
+            }
+            hash = &block.previous_hash;
+        }
+        // Reached the active chain, which the branch shares from here down
+        &self.blocks[height as usize]
+    }
+
+    // Unwind the active chain to where the branch ending in `new_tip` forks off, then
+    // replay the branch block by block
+    fn reorganize(&mut self, new_tip: &str) -> Result<BlockStatus, ChainError> {
+        let mut branch = Vec::new();
+        let mut hash = new_tip.to_string();
+        while let Some(block) = self.side_blocks.get(&hash) {
+            branch.push(hash.clone());
+            hash = block.previous_hash.clone();
+        }
+        branch.reverse();
+        let fork_height = self.nodes[&hash].height;
+        let old_tip = self.get_latest_block().hash.clone();
+
+        let mut disconnected = Vec::new();
+        while self.blocks.len() as u64 > fork_height + 1 {
+            disconnected.push(self.pop_block().expect("fork point is at or above genesis"));
+        }
+
+        for hash in &branch {
+            let block = self.side_blocks.remove(hash).expect("branch blocks are stored");
+            if let Err(error) = self.connect_block(block) {
+                // Forget the bad block and everything built on it
+                self.discard_branch(hash);
+                self.restore(fork_height, disconnected);
+                return Err(error);
+            }
+        }
+        if let Err(error) = self.persist() {
+            // Forget the block that tipped the balance, so submitting it again retries
+            self.restore(fork_height, disconnected);
+            self.side_blocks.remove(new_tip);
+            self.nodes.remove(new_tip);
+            return Err(error);
+        }
+
+        for block in &disconnected {
+            self.emit(ChainEvent::BlockDisconnected(block.clone()));
+        }
+        for block in &self.blocks[fork_height as usize + 1..] {
+            self.emit(ChainEvent::BlockConnected(block.clone()));
+        }
+        self.emit(ChainEvent::Reorg {
+            fork_height,
+            old_tip,
+            new_tip: new_tip.to_string(),
+            disconnected: disconnected.len(),
+            connected: branch.len(),
+        });
+
+        let status = BlockStatus::Reorganized {
+            disconnected: disconnected.len(),
+            connected: branch.len(),
+        };
+        for block in disconnected {
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        Ok(status)
+    }
+
+    // Undo a reorg that could not finish: set aside what was connected of the new
+    // branch and put the previous chain back exactly as it was
+    fn restore(&mut self, fork_height: u64, disconnected: Vec<Block>) {
+        while self.blocks.len() as u64 > fork_height + 1 {
+            let block = self.pop_block().expect("fork point is at or above genesis");
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        for block in disconnected.into_iter().rev() {
+            self.connect_block(block)
+                .expect("blocks of the previous chain were valid");
+        }
+    }
+
+    // Drop a block and every stored descendant from the tree
+    fn discard_branch(&mut self, hash: &str) {
+        let mut stale = vec![hash.to_string()];
+        while let Some(hash) = stale.pop() {
+            self.side_blocks.remove(&hash);
+            self.nodes.remove(&hash);
+            stale.extend(
+                self.side_blocks
+                    .values()
+                    .filter(|block| block.previous_hash == hash)
+                    .map(|block| block.hash.clone()),
+            );
+        }
+    }
+
+    // Fully validate `block` against the tip and append it
+    fn connect_block(&mut self, block: Block) -> Result<(), ChainError> {
+        let subsidy = self.params.allowed_subsidy(block.id, self.issued);
+        validate_block_body(&self.utxo_set, &block, subsidy, self.params.max_block_size)?;
+        let undo = self.utxo_set.apply_block(&block)?;
+        self.push_block(block, undo);
+        Ok(())
+    }
+
+    fn push_block(&mut self, block: Block, undo: BlockUndo) {
+        self.issued += undo.minted(&block);
+        self.blocks.push(block);
+        self.undo.push(undo);
+    }
+
+    // Remove the tip and restore the outputs it spent; the genesis block stays
+    fn pop_block(&mut self) -> Option<Block> {
+        if self.blocks.len() <= 1 {
+            return None;
+        }
+        let block = self.blocks.pop()?;
+        let undo = self.undo.pop()?;
+        self.utxo_set.undo_block(&block, &undo);
+        self.issued -= undo.minted(&block);
+        Some(block)
+    }
+
+    /// Mine the best-paying transactions from `mempool` into a new block
+    ///
+    /// Confirmed transactions, and anything that conflicted with them, leave the pool.
+    pub fn mine_from_mempool(
+        &mut self,
+        mempool: &mut Mempool,
+        miner_address: &str,
+    ) -> Result<&Block, ChainError> {
+        let template = mempool.block_template(self.params.max_block_size);
+        self.add_block(template, miner_address)?;
+        mempool.remove_for_block(self.get_latest_block());
+        Ok(self.get_latest_block())
+    }
+
+    /// Remove the tip block and restore the outputs it spent
+    ///
+    /// The block is forgotten along with any side branch built on it. The genesis
+    /// block cannot be disconnected. Hand the returned block to
+    /// [`Mempool::readmit_block`] so its transactions are not lost. With a store, the
+    /// block is removed from the store first and stays connected if that fails.
+    pub fn disconnect_tip(&mut self) -> Result<Option<Block>, ChainError> {
+        let keep = self.blocks.len() - 1;
+        if keep == 0 {
+            return Ok(None);
+        }
+        self.persist_chain(&self.blocks[..keep], &self.undo[..keep])?;
+        let block = self.pop_block().expect("more than the genesis block is connected");
+        self.discard_branch(&block.hash);
+        self.emit(ChainEvent::BlockDisconnected(block.clone()));
+        Ok(Some(block))
+    }
+
+    /// Look up any known block, on the active chain or a side branch
+    pub fn get_block(&self, hash: &str) -> Option<&Block> {
+        if let Some(block) = self.side_blocks.get(hash) {
+            return Some(block);
+        }
+        let node = self.nodes.get(hash)?;
+        self.blocks.get(node.height as usize)
+    }
+
+    /// Cumulative work of the known block `hash`
+    pub fn chain_work(&self, hash: &str) -> Option<u128> {
+        self.nodes.get(hash).map(|node| node.chain_work)
+    }
+
+    /// Cumulative work of the active chain
+    pub fn tip_work(&self) -> u128 {
+        self.nodes[&self.get_latest_block().hash].chain_work
+    }
+
+    /// Coins issued so far, cross-checked against the UTXO set
+    ///
+    /// Fees only move coins between outputs, so the value held by the UTXO set must
+    /// always equal the sum of the subsidies the coinbases actually claimed.
+    pub fn total_supply(&self) -> Result<i64, ChainError> {
+        let utxo_total = self.utxo_set.total_value();
+        if utxo_total != self.issued {
+            return Err(ChainError::SupplyMismatch {
+                issued: self.issued,
+                utxo_total,
+            });
+        }
+        Ok(self.issued)
+    }
+
+    /// Target the next block must be mined against
+    pub fn next_target(&self) -> Target {
+        self.params.next_target(&self.blocks)
+    }
+
+    /// Median timestamp of the latest blocks; the next block must be later than this
+    pub fn median_time_past(&self) -> i64 {
+        median_time_past(self.blocks.len() as u64, |h| self.blocks[h as usize].header())
+    }
+
+    /// Height of `hash` if it is on the active chain
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        let node = self.nodes.get(hash)?;
+        let block = self.blocks.get(node.height as usize)?;
+        (block.hash == hash).then_some(node.height)
+    }
+
+    /// Active-chain hashes from the tip down to genesis, one per block for the
+    /// latest ten and then twice as far apart each step, so a peer can find where
+    /// its chain and ours fork in a few dozen hashes
+    pub fn block_locator(&self) -> Vec<String> {
+        locator_heights(self.blocks.len() as u64 - 1)
+            .into_iter()
+            .map(|height| self.blocks[height as usize].hash.clone())
+            .collect()
+    }
+
+    /// Up to `max` active-chain headers following the first `locator` hash on the
+    /// active chain (genesis if none is), ending early at `stop`
+    pub fn headers_after(&self, locator: &[String], stop: Option<&str>, max: usize) -> Vec<BlockHeader> {
+        let start = locator.iter().find_map(|hash| self.height_of(hash)).unwrap_or(0) + 1;
+        let mut headers = Vec::new();
+        for block in self.blocks.iter().skip(start as usize).take(max) {
+            headers.push(block.header());
+            if stop == Some(block.hash.as_str()) {
+                break;
+            }
+        }
+        headers
+    }
+
+    /// Headers of the active chain, from genesis
+    pub fn headers(&self) -> Vec<BlockHeader> {
+        self.blocks.iter().map(Block::header).collect()
+    }
+
+    /// Get latest block
+    pub fn get_latest_block(&self) -> &Block {
+        self.blocks.last().expect("chain always contains the genesis block")
+    }
+
+    /// Get balance for an address
+    pub fn get_balance(&self, address: &str) -> i64 {
+        self.utxo_set.balance(address)
+    }
+
+    /// Validate every block from genesis, replaying its transactions
+    ///
+    /// Besides the header rules of [`validate_header`] this re-verifies every
+    /// transaction against the UTXO set as it stood at that height and checks that no
+    /// coinbase claims more than the scheduled subsidy plus the fees of its block, so
+    /// the supply never exceeds `params.max_supply`. Finally the UTXO set must be
+    /// exactly the one the blocks produce.
+    pub fn validate(&self) -> Result<(), ChainError> {
+        self.validate_with_progress(|_, _| {})
+    }
+
+    /// [`validate`](Self::validate), calling `progress(height, tip_height)` after each
+    /// block
+    pub fn validate_with_progress(&self, mut progress: impl FnMut(u64, u64)) -> Result<(), ChainError> {
+        let tip_height = self.blocks.len() as u64 - 1;
+        let now = Utc::now().timestamp();
+        let mut utxo_set = UtxoSet::new();
+        let mut issued = 0;
+        for (height, block) in self.blocks.iter().enumerate() {
+            // Check height and hash; the id is not part of the hashed header
+            if block.id != height as u64 {
+                return Err(ChainError::InvalidHeight(block.id));
+            }
+            if block.hash != block.calculate_hash() {
+                return Err(ChainError::InvalidHash(block.id));
+            }
+
+            // Link, target, proof-of-work and timestamps
+            validate_header(
+                &self.params,
+                height as u64,
+                &block.header(),
+                |h| self.blocks[h as usize].header(),
+                now,
+            )?;
+
+            let subsidy = self.params.allowed_subsidy(height as u64, issued);
+            validate_block_body(&utxo_set, block, subsidy, self.params.max_block_size)?;
+            issued += utxo_set.apply_block(block)?.minted(block);
+            progress(height as u64, tip_height);
+        }
+        if issued > self.params.max_supply {
+            return Err(ChainError::SupplyExceeded {
+                issued,
+                max_supply: self.params.max_supply,
+            });
+        }
+        if utxo_set != self.utxo_set {
+            return Err(ChainError::UtxoSetMismatch);
+        }
+        Ok(())
+    }
+
+    /// Validate blockchain
+    pub fn is_chain_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+}
+
+/// Heights a block locator samples below `tip`: the latest ten, then twice as far
+/// apart each step, always ending at genesis
+pub(crate) fn locator_heights(tip: u64) -> Vec<u64> {
+    let mut heights = Vec::new();
+    let mut height = tip;
+    let mut step = 1;
+    loop {
+        heights.push(height);
+        if height == 0 {
+            return heights;
+        }
+        if heights.len() >= 10 {
+            step *= 2;
+        }
+        height = height.saturating_sub(step);
+    }
+}
+
+/// Check a block's transactions against the UTXO set it builds on
+fn validate_block_body(
+    utxo_set: &UtxoSet,
+    block: &Block,
+    subsidy: i64,
+    max_block_size: usize,
+) -> Result<(), ChainError> {
+    if block.merkle_root != block.calculate_merkle_root() {
+        return Err(ChainError::InvalidMerkleRoot(block.id));
+    }
+    if block.has_mutated_merkle_tree() {
+        return Err(ChainError::MutatedMerkleTree(block.id));
+    }
+
+    let (coinbase, transactions) = match block.transactions.split_first() {
+        Some((coinbase, rest)) if coinbase.is_coinbase() => (coinbase, rest),
+        _ => return Err(ChainError::MissingCoinbase(block.id)),
+    };
+    coinbase.verify(utxo_set).map_err(|error| ChainError::InvalidTransaction {
+        txid: coinbase.id.clone(),
+        error,
+    })?;
+
+    let size: usize = transactions.iter().map(Transaction::size).sum();
+    if size > max_block_size {
+        return Err(ChainError::BlockTooLarge {
+            block: block.id,
+            size,
+            max: max_block_size,
+        });
+    }
+
+    let fees: i64 = verify_transactions(utxo_set, transactions)?.iter().sum();
+    let allowed = subsidy + fees;
+    let claimed = coinbase.output_total();
+    if claimed > allowed {
+        return Err(ChainError::ExcessiveCoinbase {
+            block: block.id,
+            claimed,
+            allowed,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Canonical binary encoding
+//!
+//! Every value has exactly one encoding, so hashes over these bytes never depend on
+//! how Rust happens to format a struct. Integers are little-endian, counts and string
+//! lengths are Bitcoin-style CompactSize integers that must use their shortest form,
+//! and strings are UTF-8. Transactions and block headers start with a version so the
+//! format can evolve.
+
+use super::error::DecodeError;
+
+/// Types with a canonical byte encoding
+pub trait Encode {
+    /// Append the encoding of `self` to `out`
+    fn encode_to(&self, out: &mut Vec<u8>);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_to(&mut out);
+        out
+    }
+}
+
+/// Types that can be read back from their canonical encoding
+pub trait Decode: Sized {
+    /// Read one value from the front of `reader`
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError>;
+
+    /// Decode a value that must span all of `bytes`
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader::new(bytes);
+        let value = Self::decode_from(&mut reader)?;
+        reader.finish()?;
+        Ok(value)
+    }
+}
+
+/// Append a CompactSize integer: one byte below 0xfd, otherwise a marker byte
+/// followed by 2, 4 or 8 bytes
+pub fn write_compact_size(out: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => out.push(value as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend((value as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend((value as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend(value.to_le_bytes());
+        }
+    }
+}
+
+/// Append a length-prefixed UTF-8 string
+pub fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_compact_size(out, s.len() as u64);
+    out.extend(s.as_bytes());
+}
+
+/// Cursor over encoded bytes
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, position: 0 }
+    }
+
+    /// Bytes not yet read
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// Fail unless every byte has been read
+    pub fn finish(&self) -> Result<(), DecodeError> {
+        match self.remaining() {
+            0 => Ok(()),
+            extra => Err(DecodeError::TrailingBytes(extra)),
+        }
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.remaining() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let bytes = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        Ok(self.take(N)?.try_into().expect("took exactly N bytes"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, DecodeError> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    /// Read a CompactSize integer, rejecting non-minimal encodings
+    pub fn compact_size(&mut self) -> Result<u64, DecodeError> {
+        let (value, minimum) = match self.u8()? {
+            0xfd => (u64::from(u16::from_le_bytes(self.array()?)), 0xfd),
+            0xfe => (u64::from(u32::from_le_bytes(self.array()?)), 0x1_0000),
+            0xff => (self.u64()?, 0x1_0000_0000),
+            small => return Ok(u64::from(small)),
+        };
+        if value < minimum {
+            return Err(DecodeError::NonCanonicalSize(value));
+        }
+        Ok(value)
+    }
+
+    /// Read a count of items that are each at least one byte long, so a corrupt count
+    /// cannot trigger a huge allocation
+    pub fn count(&mut self) -> Result<usize, DecodeError> {
+        let count = self.compact_size()?;
+        if count > self.remaining() as u64 {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        Ok(count as usize)
+    }
+
+    pub fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.count()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
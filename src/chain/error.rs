@@ -0,0 +1,136 @@
+use thiserror::Error;
+
+use super::utxo::OutPoint;
+
+/// Everything that can go wrong while building, validating or extending the chain
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ChainError {
+    #[error("not enough funds: need {needed}, have {available}")]
+    InsufficientFunds { needed: i32, available: i32 },
+
+    #[error("amount must be positive, got {0}")]
+    InvalidAmount(i32),
+
+    #[error("output {vout} of transaction {txid} not found in UTXO set")]
+    UnknownOutput { txid: String, vout: usize },
+
+    #[error("outpoint {0} already holds an unspent output")]
+    DuplicateOutput(OutPoint),
+
+    #[error("invalid transaction {txid}: {error}")]
+    InvalidTransaction {
+        txid: String,
+        #[source]
+        error: TxValidationError,
+    },
+
+    #[error("block {0} does not build on any known block")]
+    UnknownParent(String),
+
+    #[error("block {0} is not one above its parent")]
+    InvalidHeight(u64),
+
+    #[error("invalid hash for block {0}")]
+    InvalidHash(u64),
+
+    #[error("invalid previous hash for block {0}")]
+    InvalidPreviousHash(u64),
+
+    #[error("block {0} does not carry the target the retargeting rules require")]
+    UnexpectedTarget(u64),
+
+    #[error("invalid proof-of-work for block {0}")]
+    InvalidProofOfWork(u64),
+
+    #[error("block {0} has an unsupported header version")]
+    UnsupportedVersion(u64),
+
+    #[error("timestamp of block {0} is not after the median of the blocks before it")]
+    TimestampTooEarly(u64),
+
+    #[error("timestamp of block {0} is too far in the future")]
+    TimestampTooFarAhead(u64),
+
+    #[error("merkle root of block {0} does not match its transactions")]
+    InvalidMerkleRoot(u64),
+
+    #[error("block {0} repeats transactions in its merkle tree")]
+    MutatedMerkleTree(u64),
+
+    #[error("block {0} does not start with a coinbase transaction")]
+    MissingCoinbase(u64),
+
+    #[error("coinbase of block {block} claims {claimed}, only {allowed} allowed")]
+    ExcessiveCoinbase { block: u64, claimed: i64, allowed: i64 },
+
+    #[error("block {block} carries {size} bytes of transactions, limit is {max}")]
+    BlockTooLarge { block: u64, size: usize, max: usize },
+
+    #[error("block reward {0} does not fit in an output")]
+    RewardOverflow(i64),
+
+    #[error("{issued} coins issued, exceeding the maximum supply of {max_supply}")]
+    SupplyExceeded { issued: i64, max_supply: i64 },
+
+    #[error("issued supply {issued} does not match UTXO set total {utxo_total}")]
+    SupplyMismatch { issued: i64, utxo_total: i64 },
+
+    #[error("UTXO set does not match the one the blocks produce")]
+    UtxoSetMismatch,
+
+    #[error("chain store failed: {0}")]
+    Storage(String),
+}
+
+/// Why a single transaction was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TxValidationError {
+    #[error("transaction has no inputs")]
+    NoInputs,
+
+    #[error("transaction has no outputs")]
+    NoOutputs,
+
+    #[error("coinbase transaction outside the first slot of a block")]
+    UnexpectedCoinbase,
+
+    #[error("output {index} has non-positive value {value}")]
+    NonPositiveOutput { index: usize, value: i32 },
+
+    #[error("outpoint {0} is spent more than once")]
+    DuplicateInput(OutPoint),
+
+    #[error("outpoint {0} is not in the UTXO set")]
+    UnknownOutpoint(OutPoint),
+
+    #[error("public key of input {index} does not own the output it spends")]
+    PubKeyMismatch { index: usize },
+
+    #[error("bad signature on input {index}")]
+    BadSignature { index: usize },
+
+    #[error("outputs ({outputs}) exceed inputs ({inputs})")]
+    Overspend { inputs: i64, outputs: i64 },
+}
+
+/// Why bytes could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    #[error("input ended early")]
+    UnexpectedEnd,
+
+    #[error("{0} bytes left over after decoding")]
+    TrailingBytes(usize),
+
+    #[error("size {0} is not in its shortest encoding")]
+    NonCanonicalSize(u64),
+
+    #[error("string is not valid UTF-8")]
+    InvalidUtf8,
+
+    #[error("unsupported encoding version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("invalid flag byte {0}")]
+    InvalidFlag(u8),
+}
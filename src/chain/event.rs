@@ -0,0 +1,35 @@
+use super::block::Block;
+
+/// Changes to the active chain, broadcast to everyone who called
+/// [`Blockchain::subscribe`](super::Blockchain::subscribe)
+///
+/// A reorg is reported as the disconnected blocks (tip first), then the connected
+/// blocks (oldest first), then a single `Reorg` summary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    /// A block became part of the active chain
+    BlockConnected(Block),
+    /// A block left the active chain; its transactions may belong back in the mempool
+    BlockDisconnected(Block),
+    /// The active chain switched to a heavier branch
+    Reorg {
+        fork_height: u64,      // Last block both branches share
+        old_tip: String,
+        new_tip: String,
+        disconnected: usize,
+        connected: usize,
+    },
+}
+
+/// What happened to a block handed to [`Blockchain::submit_block`](super::Blockchain::submit_block)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStatus {
+    /// The block was already known
+    AlreadyKnown,
+    /// The block extended the active chain
+    Connected,
+    /// The block was stored on a side branch with less work than the active chain
+    SideBranch,
+    /// The block's branch outweighed the active chain, which switched to it
+    Reorganized { disconnected: usize, connected: usize },
+}
@@ -0,0 +1,33 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// SHA-256 of `data` as a lowercase hex string
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Raw bytes of a hex hash string
+///
+/// Anything that is not 64 hex characters (such as an old-style `"0"` previous hash)
+/// is hashed down to 32 bytes instead, so the result still commits to the string.
+pub(crate) fn hash_bytes(hash: &str) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    match hex::decode_to_slice(hash, &mut bytes) {
+        Ok(()) => bytes,
+        Err(_) => Sha256::digest(hash.as_bytes()).into(),
+    }
+}
+
+/// Hash public key to create address
+pub fn hash_pub_key(pub_key: &str) -> String {
+    let result = Sha256::digest(pub_key.as_bytes());
+    to_hex(&result[..20]) // Take first 20 bytes like Bitcoin
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hash_string = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(&mut hash_string, "{:02x}", byte).unwrap();
+    }
+    hash_string
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use super::encoding::{Decode, Encode, Reader};
+use super::error::{ChainError, DecodeError};
+use super::hash::{hash_bytes, sha256_hex};
+use super::params::ChainParams;
+use super::pow::Target;
+
+/// Version written into every block header
+pub const BLOCK_VERSION: u32 = 1;
+
+/// Size of the binary header proof-of-work hashes:
+/// version u32 | previous hash 32 | merkle root 32 | timestamp i64 | target 32 | nonce u64
+pub const HEADER_SIZE: usize = 116;
+
+/// Offset of the nonce, the last field of the header
+pub(crate) const NONCE_OFFSET: usize = HEADER_SIZE - 8;
+
+/// Number of previous blocks whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// How far ahead of the local clock a block timestamp may be, in seconds
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 2 * 60 * 60;
+
+/// Everything proof-of-work commits to, without the transactions
+///
+/// The merkle root ties a header to its transactions, so a chain of headers can be
+/// downloaded and checked first and the bodies fetched afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub previous_hash: String,
+    pub merkle_root: String,
+    pub timestamp: i64,
+    pub target: Target,
+    pub nonce: u64,
+}
+
+impl BlockHeader {
+    /// Fixed-size binary form; integers are little-endian, hashes and the target
+    /// big-endian as they are written in hex
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&self.version.to_le_bytes());
+        header[4..36].copy_from_slice(&hash_bytes(&self.previous_hash));
+        header[36..68].copy_from_slice(&hash_bytes(&self.merkle_root));
+        header[68..76].copy_from_slice(&self.timestamp.to_le_bytes());
+        header[76..108].copy_from_slice(&self.target.to_be_bytes());
+        header[NONCE_OFFSET..].copy_from_slice(&self.nonce.to_le_bytes());
+        header
+    }
+
+    /// Block hash: SHA-256 of the binary header
+    pub fn hash(&self) -> String {
+        sha256_hex(&self.to_bytes())
+    }
+
+    pub fn meets_target(&self) -> bool {
+        self.target.is_met_by(&self.hash())
+    }
+}
+
+impl Encode for BlockHeader {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend(self.to_bytes());
+    }
+}
+
+impl Decode for BlockHeader {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let version = reader.u32()?;
+        if version != BLOCK_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        Ok(BlockHeader {
+            version,
+            previous_hash: hex::encode(reader.array::<32>()?),
+            merkle_root: hex::encode(reader.array::<32>()?),
+            timestamp: reader.i64()?,
+            target: Target::from_be_bytes(reader.array()?),
+            nonce: reader.u64()?,
+        })
+    }
+}
+
+/// Median timestamp of the (up to) `MEDIAN_TIME_SPAN` blocks below `height`
+pub fn median_time_past(height: u64, ancestor: impl Fn(u64) -> BlockHeader) -> i64 {
+    let start = height.saturating_sub(MEDIAN_TIME_SPAN as u64);
+    let mut times: Vec<i64> = (start..height).map(|h| ancestor(h).timestamp).collect();
+    times.sort_unstable();
+    times.get(times.len() / 2).copied().unwrap_or(i64::MIN)
+}
+
+/// Check `header` as the block at `height`, where `ancestor(h)` returns the header at
+/// height `h` on the branch it extends
+///
+/// Covers linkage to the parent, the retargeting rules, proof-of-work, and the
+/// timestamp rules: later than the median of the previous `MEDIAN_TIME_SPAN` blocks
+/// and no more than `MAX_FUTURE_BLOCK_TIME` ahead of `now`.
+pub fn validate_header(
+    params: &ChainParams,
+    height: u64,
+    header: &BlockHeader,
+    ancestor: impl Fn(u64) -> BlockHeader,
+    now: i64,
+) -> Result<(), ChainError> {
+    if header.version != BLOCK_VERSION {
+        return Err(ChainError::UnsupportedVersion(height));
+    }
+    if height > 0 && header.previous_hash != ancestor(height - 1).hash() {
+        return Err(ChainError::InvalidPreviousHash(height));
+    }
+    if header.target != params.target_at(height, &ancestor) {
+        return Err(ChainError::UnexpectedTarget(height));
+    }
+    if !header.meets_target() {
+        return Err(ChainError::InvalidProofOfWork(height));
+    }
+    if height > 0 && header.timestamp <= median_time_past(height, &ancestor) {
+        return Err(ChainError::TimestampTooEarly(height));
+    }
+    if header.timestamp > now.saturating_add(MAX_FUTURE_BLOCK_TIME) {
+        return Err(ChainError::TimestampTooFarAhead(height));
+    }
+    Ok(())
+}
+
+/// Validate a chain of headers starting from genesis, without any transactions
+pub fn validate_header_chain(params: &ChainParams, headers: &[BlockHeader], now: i64) -> Result<(), ChainError> {
+    for (height, header) in headers.iter().enumerate() {
+        validate_header(params, height as u64, header, |h| headers[h as usize].clone(), now)?;
+    }
+    Ok(())
+}
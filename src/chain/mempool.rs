@@ -0,0 +1,530 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use thiserror::Error;
+
+use super::block::Block;
+use super::error::TxValidationError;
+use super::transaction::{TXOutput, Transaction};
+use super::utxo::{OutPoint, UtxoSet, UtxoView};
+
+/// Default mempool capacity in bytes of transaction data
+pub const DEFAULT_MEMPOOL_SIZE: usize = 5_000_000;
+
+/// Why the mempool refused a transaction
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum MempoolError {
+    #[error("transaction {0} is already in the mempool")]
+    AlreadyKnown(String),
+
+    #[error("coinbase transactions are only valid inside blocks")]
+    Coinbase,
+
+    #[error(transparent)]
+    Invalid(#[from] TxValidationError),
+
+    #[error("replacement pays {fee} at {fee_rate:.4}/byte, needs more than {required_fee} and {required_rate:.4}/byte")]
+    ReplacementTooCheap {
+        fee: i64,
+        fee_rate: f64,
+        required_fee: i64,
+        required_rate: f64,
+    },
+
+    #[error("mempool is full and fee rate {0:.4}/byte is too low to evict anything")]
+    PoolFull(f64),
+}
+
+/// A validated transaction waiting to be mined
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub fee: i64,
+    pub size: usize,
+    sequence: u64, // Admission order, breaks fee-rate ties
+}
+
+impl MempoolEntry {
+    /// Fee paid per byte
+    pub fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.size.max(1) as f64
+    }
+}
+
+/// What else changed when a transaction was admitted
+#[derive(Debug, Clone, Default)]
+pub struct AddOutcome {
+    pub fee: i64,
+    pub replaced: Vec<Transaction>, // Conflicting transactions it outbid
+    pub evicted: Vec<Transaction>,  // Low fee-rate transactions dropped to make room
+}
+
+/// Pool of pending transactions
+///
+/// Every entry is valid against the confirmed UTXO set plus the other entries it
+/// spends from, and no two entries spend the same outpoint. A conflicting newcomer
+/// replaces the entries it conflicts with only if it pays a higher fee rate than each
+/// of them and a higher absolute fee than all of them together (with descendants).
+pub struct Mempool {
+    entries: HashMap<String, MempoolEntry>,
+    spends: HashMap<OutPoint, String>, // Outpoint -> txid of the entry spending it
+    max_size: usize,
+    total_size: usize,
+    next_sequence: u64,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMPOOL_SIZE)
+    }
+}
+
+impl Mempool {
+    /// Create a mempool holding at most `max_size` bytes of transactions
+    pub fn new(max_size: usize) -> Self {
+        Mempool {
+            entries: HashMap::new(),
+            spends: HashMap::new(),
+            max_size,
+            total_size: 0,
+            next_sequence: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of transaction data currently held
+    pub fn size(&self) -> usize {
+        self.total_size
+    }
+
+    pub fn contains(&self, txid: &str) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &str) -> Option<&MempoolEntry> {
+        self.entries.get(txid)
+    }
+
+    /// Entries ordered from highest to lowest fee rate
+    pub fn entries_by_fee_rate(&self) -> Vec<&MempoolEntry> {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| compare_priority(b, a));
+        entries
+    }
+
+    /// Validate `tx` against the UTXO set plus pending transactions and admit it
+    pub fn add(&mut self, tx: Transaction, utxo_set: &UtxoSet) -> Result<AddOutcome, MempoolError> {
+        if tx.is_coinbase() {
+            return Err(MempoolError::Coinbase);
+        }
+        if self.contains(&tx.id) {
+            return Err(MempoolError::AlreadyKnown(tx.id.clone()));
+        }
+
+        // Entries spending the same outpoints, and everything built on top of them
+        let conflicts: HashSet<String> = tx
+            .vin
+            .iter()
+            .filter_map(|input| self.spends.get(&input.outpoint()).cloned())
+            .collect();
+        let mut replaced = HashSet::new();
+        for txid in &conflicts {
+            self.collect_with_descendants(txid, &mut replaced);
+        }
+
+        let view = PoolView {
+            utxo_set,
+            pool: self,
+            excluded: &replaced,
+        };
+        let fee = tx.verify(&view)?;
+        let size = tx.size();
+        let fee_rate = fee as f64 / size.max(1) as f64;
+
+        if !conflicts.is_empty() {
+            self.check_replacement(fee, fee_rate, &conflicts, &replaced)?;
+        }
+
+        let replaced_size: usize = replaced.iter().map(|txid| self.entries[txid].size).sum();
+        let mut ancestors = HashSet::new();
+        self.collect_ancestors(&tx, &mut ancestors);
+        let evict = self.plan_eviction(
+            (self.total_size - replaced_size + size).saturating_sub(self.max_size),
+            fee_rate,
+            &replaced,
+            &ancestors,
+        )?;
+
+        let mut outcome = AddOutcome {
+            fee,
+            ..AddOutcome::default()
+        };
+        for txid in &replaced {
+            outcome.replaced.extend(self.remove_entry(txid));
+        }
+        for txid in &evict {
+            outcome.evicted.extend(self.remove_entry(txid));
+        }
+        self.insert(tx, fee, size);
+        Ok(outcome)
+    }
+
+    fn check_replacement(
+        &self,
+        fee: i64,
+        fee_rate: f64,
+        conflicts: &HashSet<String>,
+        replaced: &HashSet<String>,
+    ) -> Result<(), MempoolError> {
+        let required_fee: i64 = replaced.iter().map(|txid| self.entries[txid].fee).sum();
+        let required_rate = conflicts
+            .iter()
+            .map(|txid| self.entries[txid].fee_rate())
+            .fold(0.0, f64::max);
+        if fee <= required_fee || fee_rate <= required_rate {
+            return Err(MempoolError::ReplacementTooCheap {
+                fee,
+                fee_rate,
+                required_fee,
+                required_rate,
+            });
+        }
+        Ok(())
+    }
+
+    // Pick the cheapest entries (with their descendants) until `needed` bytes are free.
+    // Nothing paying at least `fee_rate` is ever evicted for the newcomer, and neither
+    // are its `ancestors`, which it needs in the pool to stay valid.
+    fn plan_eviction(
+        &self,
+        mut needed: usize,
+        fee_rate: f64,
+        already_removed: &HashSet<String>,
+        ancestors: &HashSet<String>,
+    ) -> Result<HashSet<String>, MempoolError> {
+        let mut evict = HashSet::new();
+        if needed == 0 {
+            return Ok(evict);
+        }
+        let mut candidates: Vec<_> = self
+            .entries
+            .values()
+            .filter(|entry| !already_removed.contains(&entry.tx.id))
+            .collect();
+        candidates.sort_by(|a, b| compare_priority(a, b));
+
+        for entry in candidates {
+            if evict.contains(&entry.tx.id) || ancestors.contains(&entry.tx.id) {
+                continue;
+            }
+            if entry.fee_rate() >= fee_rate {
+                break;
+            }
+            let mut package = HashSet::new();
+            self.collect_with_descendants(&entry.tx.id, &mut package);
+            for txid in package {
+                if !already_removed.contains(&txid) && evict.insert(txid.clone()) {
+                    needed = needed.saturating_sub(self.entries[&txid].size);
+                }
+            }
+            if needed == 0 {
+                return Ok(evict);
+            }
+        }
+        Err(MempoolError::PoolFull(fee_rate))
+    }
+
+    fn insert(&mut self, tx: Transaction, fee: i64, size: usize) {
+        for input in &tx.vin {
+            self.spends.insert(input.outpoint(), tx.id.clone());
+        }
+        self.total_size += size;
+        let entry = MempoolEntry {
+            tx,
+            fee,
+            size,
+            sequence: self.next_sequence,
+        };
+        self.next_sequence += 1;
+        self.entries.insert(entry.tx.id.clone(), entry);
+    }
+
+    fn remove_entry(&mut self, txid: &str) -> Option<Transaction> {
+        let entry = self.entries.remove(txid)?;
+        for input in &entry.tx.vin {
+            let outpoint = input.outpoint();
+            if self.spends.get(&outpoint).map(String::as_str) == Some(txid) {
+                self.spends.remove(&outpoint);
+            }
+        }
+        self.total_size -= entry.size;
+        Some(entry.tx)
+    }
+
+    /// Remove a transaction and everything that spends its outputs
+    pub fn remove(&mut self, txid: &str) -> Vec<Transaction> {
+        let mut package = HashSet::new();
+        self.collect_with_descendants(txid, &mut package);
+        package.iter().filter_map(|txid| self.remove_entry(txid)).collect()
+    }
+
+    /// The in-pool entries `tx` spends from, directly or through other entries
+    fn collect_ancestors(&self, tx: &Transaction, out: &mut HashSet<String>) {
+        let mut stack: Vec<&Transaction> = vec![tx];
+        while let Some(tx) = stack.pop() {
+            for input in &tx.vin {
+                if let Some(parent) = self.entries.get(&input.txid)
+                    && out.insert(input.txid.clone())
+                {
+                    stack.push(&parent.tx);
+                }
+            }
+        }
+    }
+
+    fn collect_with_descendants(&self, txid: &str, out: &mut HashSet<String>) {
+        let mut stack = vec![txid.to_string()];
+        while let Some(txid) = stack.pop() {
+            let Some(entry) = self.entries.get(&txid) else {
+                continue;
+            };
+            for vout in 0..entry.tx.vout.len() {
+                if let Some(child) = self.spends.get(&OutPoint::new(txid.clone(), vout)) {
+                    stack.push(child.clone());
+                }
+            }
+            out.insert(txid);
+        }
+    }
+
+    /// Drop transactions a newly connected block confirmed or conflicted with
+    ///
+    /// Returns the conflicting transactions (and their descendants) that were dropped.
+    pub fn remove_for_block(&mut self, block: &Block) -> Vec<Transaction> {
+        let mut conflicted = Vec::new();
+        for tx in &block.transactions {
+            if self.remove_entry(&tx.id).is_some() || tx.is_coinbase() {
+                continue;
+            }
+            for input in &tx.vin {
+                if let Some(spender) = self.spends.get(&input.outpoint()).cloned() {
+                    conflicted.extend(self.remove(&spender));
+                }
+            }
+        }
+        conflicted
+    }
+
+    /// Put the transactions of a disconnected block back into the pool
+    ///
+    /// `utxo_set` must already reflect the disconnection. Transactions that are no
+    /// longer valid (for example because the new chain spent their inputs) are
+    /// silently dropped. Returns how many were admitted.
+    pub fn readmit_block(&mut self, block: &Block, utxo_set: &UtxoSet) -> usize {
+        block
+            .transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .filter(|tx| self.add((*tx).clone(), utxo_set).is_ok())
+            .count()
+    }
+
+    /// Drop entries spending outputs that exist neither in `utxo_set` nor in the pool
+    ///
+    /// After a reorg this clears out transactions built on the disconnected blocks,
+    /// for example spends of their coinbases. Returns the dropped transactions
+    /// (with their descendants).
+    pub fn remove_unspendable(&mut self, utxo_set: &UtxoSet) -> Vec<Transaction> {
+        let unspendable: Vec<String> = self
+            .entries
+            .values()
+            .filter(|entry| {
+                entry.tx.vin.iter().any(|input| {
+                    let outpoint = input.outpoint();
+                    utxo_set.get(&outpoint).is_none()
+                        && self
+                            .entries
+                            .get(&outpoint.txid)
+                            .is_none_or(|parent| outpoint.vout >= parent.tx.vout.len())
+                })
+            })
+            .map(|entry| entry.tx.id.clone())
+            .collect();
+        unspendable.iter().flat_map(|txid| self.remove(txid)).collect()
+    }
+
+    /// Check every entry again from scratch against `utxo_set`
+    ///
+    /// For when the pool may have missed blocks being connected or disconnected.
+    /// Entries are re-added parents first and keep their place in admission order;
+    /// whatever no longer fits is dropped and returned.
+    pub fn revalidate(&mut self, utxo_set: &UtxoSet) -> Vec<Transaction> {
+        let pending: Vec<(Transaction, u64)> = self
+            .block_template(usize::MAX)
+            .into_iter()
+            .map(|tx| {
+                let sequence = self.entries[&tx.id].sequence;
+                (tx, sequence)
+            })
+            .collect();
+        let next_sequence = self.next_sequence;
+        *self = Mempool::new(self.max_size);
+        let mut dropped = Vec::new();
+        for (tx, sequence) in pending {
+            let txid = tx.id.clone();
+            match self.add(tx.clone(), utxo_set) {
+                Ok(_) => self.entries.get_mut(&txid).expect("just admitted").sequence = sequence,
+                Err(_) => dropped.push(tx),
+            }
+        }
+        self.next_sequence = next_sequence;
+        dropped
+    }
+
+    /// Pick transactions for the next block, best fee rate first
+    ///
+    /// Entries are ranked by the fee rate of the package they form with their in-pool
+    /// ancestors not taken yet, so a well-paying child pulls in a cheap parent. A
+    /// package is taken parents first, so the template is always valid in order.
+    pub fn block_template(&self, max_bytes: usize) -> Vec<Transaction> {
+        let mut included = HashSet::new();
+        let mut heap: BinaryHeap<Candidate> = self
+            .entries
+            .values()
+            .map(|entry| Candidate::new(entry, &self.package(&entry.tx.id, &included)))
+            .collect();
+        let mut template = Vec::new();
+        let mut bytes = 0;
+
+        while let Some(candidate) = heap.pop() {
+            if included.contains(candidate.txid) {
+                continue;
+            }
+            // Taking other packages may have shrunk this one since it was ranked
+            let package = self.package(candidate.txid, &included);
+            let current = Candidate::new(&self.entries[candidate.txid], &package);
+            if (current.fee, current.size) != (candidate.fee, candidate.size) {
+                heap.push(current);
+                continue;
+            }
+            if bytes + current.size > max_bytes {
+                continue;
+            }
+            bytes += current.size;
+            for entry in package {
+                included.insert(entry.tx.id.as_str());
+                template.push(entry.tx.clone());
+            }
+        }
+        template
+    }
+
+    // `txid` and its in-pool ancestors that are not in `included`, parents first
+    fn package<'a>(&'a self, txid: &'a str, included: &HashSet<&str>) -> Vec<&'a MempoolEntry> {
+        let mut package = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![(txid, false)];
+        while let Some((txid, parents_done)) = stack.pop() {
+            if parents_done {
+                package.push(&self.entries[txid]);
+                continue;
+            }
+            if !seen.insert(txid) {
+                continue;
+            }
+            stack.push((txid, true));
+            for input in &self.entries[txid].tx.vin {
+                let parent = input.txid.as_str();
+                if self.entries.contains_key(parent) && !included.contains(parent) && !seen.contains(parent) {
+                    stack.push((parent, false));
+                }
+            }
+        }
+        package
+    }
+}
+
+/// An entry ranked by the fee rate of its package, for [`Mempool::block_template`]
+struct Candidate<'a> {
+    txid: &'a str,
+    fee: i64,
+    size: usize,
+    sequence: u64,
+}
+
+impl<'a> Candidate<'a> {
+    fn new(entry: &'a MempoolEntry, package: &[&MempoolEntry]) -> Self {
+        Candidate {
+            txid: &entry.tx.id,
+            fee: package.iter().map(|entry| entry.fee).sum(),
+            size: package.iter().map(|entry| entry.size).sum(),
+            sequence: entry.sequence,
+        }
+    }
+
+    fn fee_rate(&self) -> f64 {
+        self.fee as f64 / self.size.max(1) as f64
+    }
+}
+
+// Higher package fee rate first; among equal rates the older entry goes first
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.fee_rate()
+            .total_cmp(&other.fee_rate())
+            .then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+// Lower fee rate first; among equal rates the newer entry is worth less
+fn compare_priority(a: &MempoolEntry, b: &MempoolEntry) -> std::cmp::Ordering {
+    a.fee_rate()
+        .total_cmp(&b.fee_rate())
+        .then(b.sequence.cmp(&a.sequence))
+}
+
+/// Confirmed outputs plus outputs of pending entries, minus anything an entry spends
+struct PoolView<'a> {
+    utxo_set: &'a UtxoSet,
+    pool: &'a Mempool,
+    excluded: &'a HashSet<String>, // Entries being replaced, treated as absent
+}
+
+impl UtxoView for PoolView<'_> {
+    fn output(&self, outpoint: &OutPoint) -> Option<&TXOutput> {
+        if let Some(spender) = self.pool.spends.get(outpoint)
+            && !self.excluded.contains(spender)
+        {
+            return None;
+        }
+        if let Some(output) = self.utxo_set.get(outpoint) {
+            return Some(output);
+        }
+        if self.excluded.contains(&outpoint.txid) {
+            return None;
+        }
+        self.pool
+            .entries
+            .get(&outpoint.txid)
+            .and_then(|entry| entry.tx.vout.get(outpoint.vout))
+    }
+}
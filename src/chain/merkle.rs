@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::encoding::{write_compact_size, Decode, Encode, Reader};
+use super::error::DecodeError;
+
+/// Parent of two merkle nodes: SHA-256 of their raw bytes side by side
+fn parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    Sha256::new().chain_update(left).chain_update(right).finalize().into()
+}
+
+/// Every level of the tree, leaves first and root last
+///
+/// A level with an odd number of nodes pairs its last node with itself, as Bitcoin
+/// does. That makes `[a, b, c]` and `[a, b, c, c]` share a root (CVE-2012-2459), which
+/// is why blocks whose tree is [`mutated`] are rejected and proofs commit to the
+/// number of leaves.
+pub(crate) fn levels(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves];
+    while levels.last().is_some_and(|level| level.len() > 1) {
+        let level = levels.last().expect("checked above");
+        let next = level
+            .chunks(2)
+            .map(|pair| parent(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Whether two real siblings anywhere in the tree are equal
+///
+/// That only happens with repeated transactions, and is exactly what lets a block's
+/// transaction list be padded without changing its merkle root.
+pub(crate) fn mutated(levels: &[Vec<[u8; 32]>]) -> bool {
+    levels
+        .iter()
+        .any(|level| level.chunks_exact(2).any(|pair| pair[0] == pair[1]))
+}
+
+/// Proof that a transaction is part of a block, checkable against the block header's
+/// merkle root alone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub txid: String,
+    pub index: u64,                    // Position of the transaction in the block
+    pub leaf_count: u64,               // Transactions in the block
+    pub siblings: Vec<Option<String>>, // Bottom-up; None where a node was paired with itself
+}
+
+impl MerkleProof {
+    /// Build the proof for leaf `index` of a tree given by its `levels`
+    pub(crate) fn from_levels(txid: &str, index: usize, levels: &[Vec<[u8; 32]>]) -> Self {
+        let mut siblings = Vec::new();
+        let mut position = index;
+        for level in &levels[..levels.len() - 1] {
+            let sibling = position ^ 1;
+            siblings.push(level.get(sibling).map(hex::encode));
+            position /= 2;
+        }
+        MerkleProof {
+            txid: txid.to_string(),
+            index: index as u64,
+            leaf_count: levels[0].len() as u64,
+            siblings,
+        }
+    }
+
+    /// Recompute the merkle root this proof leads to
+    ///
+    /// Returns `None` for malformed proofs: an index outside the tree, the wrong
+    /// number of steps, a self-pairing anywhere but at the end of an odd-sized level,
+    /// or a sibling equal to the node it is paired with. Because the shape of the
+    /// tree follows from `leaf_count`, a duplicated last leaf cannot pose as a real
+    /// one.
+    pub fn root(&self) -> Option<String> {
+        if self.index >= self.leaf_count {
+            return None;
+        }
+        let mut current = decode_hash(&self.txid)?;
+        let (mut position, mut width) = (self.index, self.leaf_count);
+        let mut steps = self.siblings.iter();
+
+        while width > 1 {
+            let paired_with_self = position == width - 1 && width % 2 == 1;
+            current = match (steps.next()?, paired_with_self) {
+                (None, true) => parent(&current, &current),
+                (Some(sibling), false) => {
+                    let sibling = decode_hash(sibling)?;
+                    if sibling == current {
+                        return None;
+                    }
+                    if position % 2 == 0 {
+                        parent(&current, &sibling)
+                    } else {
+                        parent(&sibling, &current)
+                    }
+                }
+                _ => return None,
+            };
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        if steps.next().is_some() {
+            return None;
+        }
+        Some(hex::encode(current))
+    }
+
+    /// Whether the proof is well formed and leads to `merkle_root`, typically taken
+    /// from a [`BlockHeader`](super::BlockHeader)
+    pub fn verify(&self, merkle_root: &str) -> bool {
+        self.root().is_some_and(|root| root == merkle_root)
+    }
+}
+
+// Proofs only ever carry real 32-byte hashes
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// txid 32 | index | leaf count | sibling count | per sibling: 0, or 1 and 32 bytes
+///
+/// Hashes that are not 64 hex characters encode as zeros and will not verify.
+impl Encode for MerkleProof {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend(decode_hash(&self.txid).unwrap_or_default());
+        write_compact_size(out, self.index);
+        write_compact_size(out, self.leaf_count);
+        write_compact_size(out, self.siblings.len() as u64);
+        for sibling in &self.siblings {
+            match sibling {
+                None => out.push(0),
+                Some(hash) => {
+                    out.push(1);
+                    out.extend(decode_hash(hash).unwrap_or_default());
+                }
+            }
+        }
+    }
+}
+
+impl Decode for MerkleProof {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let txid = hex::encode(reader.array::<32>()?);
+        let index = reader.compact_size()?;
+        let leaf_count = reader.compact_size()?;
+        let siblings = (0..reader.count()?)
+            .map(|_| match reader.u8()? {
+                0 => Ok(None),
+                1 => Ok(Some(hex::encode(reader.array::<32>()?))),
+                flag => Err(DecodeError::InvalidFlag(flag)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(MerkleProof {
+            txid,
+            index,
+            leaf_count,
+            siblings,
+        })
+    }
+}
@@ -0,0 +1,230 @@
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+use super::block::Block;
+use super::header::{HEADER_SIZE, NONCE_OFFSET};
+use super::event::ChainEvent;
+
+/// Hashes a worker does between checks for cancellation (and hashrate updates)
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Proof-of-work miner spreading the nonce space over several threads
+///
+/// Worker `i` of `n` tries nonces `start + i`, `start + i + n`, ... against a copy of
+/// the block's binary header, only rewriting the last eight bytes per attempt. The
+/// first 64 header bytes never change, so their SHA-256 state is computed once and
+/// cloned for every hash.
+#[derive(Debug, Clone, Copy)]
+pub struct Miner {
+    threads: usize,
+}
+
+impl Default for Miner {
+    /// One worker per available CPU
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+impl Miner {
+    pub fn new(threads: usize) -> Self {
+        Miner {
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Search for a nonce that makes `block` meet its target, starting at its current
+    /// nonce, until one is found or `handle` is cancelled
+    ///
+    /// On success the block's nonce and hash are updated.
+    pub fn mine(&self, block: &mut Block, handle: &MiningHandle) -> MiningReport {
+        let header = block.header_bytes();
+        let target = block.target.to_be_bytes();
+        let found = AtomicBool::new(false);
+        let winner = Mutex::new(None);
+        let started = Instant::now();
+        let hashes_before = handle.hashes();
+
+        thread::scope(|scope| {
+            for worker in 0..self.threads {
+                let first = block.nonce.wrapping_add(worker as u64);
+                let (target, found, winner) = (&target, &found, &winner);
+                scope.spawn(move || {
+                    if let Some(nonce) = search(header, target, first, self.threads as u64, found, handle) {
+                        winner.lock().expect("winner lock").get_or_insert(nonce);
+                    }
+                });
+            }
+        });
+
+        let nonce = winner.into_inner().expect("winner lock");
+        if let Some(nonce) = nonce {
+            block.nonce = nonce;
+            block.hash = block.calculate_hash();
+        }
+        MiningReport {
+            nonce,
+            hashes: handle.hashes() - hashes_before,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// Mine on tokio's blocking thread pool so async tasks can await the result
+    pub async fn mine_async(self, mut block: Block, handle: MiningHandle) -> (Block, MiningReport) {
+        tokio::task::spawn_blocking(move || {
+            let report = self.mine(&mut block, &handle);
+            (block, report)
+        })
+        .await
+        .expect("mining thread panicked")
+    }
+}
+
+// One worker's share of the nonce space
+fn search(
+    mut header: [u8; HEADER_SIZE],
+    target: &[u8; 32],
+    first: u64,
+    stride: u64,
+    found: &AtomicBool,
+    handle: &MiningHandle,
+) -> Option<u64> {
+    let midstate = Sha256::new_with_prefix(&header[..64]);
+    let mut nonce = first;
+    let mut pending = 0;
+    let result = loop {
+        header[NONCE_OFFSET..].copy_from_slice(&nonce.to_le_bytes());
+        let digest = midstate.clone().chain_update(&header[64..]).finalize();
+        pending += 1;
+
+        // Big-endian comparison: the digest read as a number must not exceed the target
+        if digest.as_slice() <= target.as_slice() {
+            found.store(true, Ordering::Relaxed);
+            break Some(nonce);
+        }
+        if pending == CHECK_INTERVAL {
+            handle.add_hashes(pending);
+            pending = 0;
+            if found.load(Ordering::Relaxed) || handle.is_cancelled() {
+                break None;
+            }
+        }
+        match nonce.checked_add(stride) {
+            Some(next) => nonce = next,
+            None => break None, // This worker's share is exhausted
+        }
+    };
+    handle.add_hashes(pending);
+    result
+}
+
+/// Shared control over a running mining job: cancel it, or watch its hashrate
+///
+/// Cloning gives another handle to the same job. Cancellation is permanent, so use
+/// a fresh handle per job.
+#[derive(Debug, Clone)]
+pub struct MiningHandle {
+    state: Arc<HandleState>,
+}
+
+#[derive(Debug)]
+struct HandleState {
+    cancelled: AtomicBool,
+    hashes: AtomicU64,
+    created: Instant,
+}
+
+impl Default for MiningHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MiningHandle {
+    pub fn new() -> Self {
+        MiningHandle {
+            state: Arc::new(HandleState {
+                cancelled: AtomicBool::new(false),
+                hashes: AtomicU64::new(0),
+                created: Instant::now(),
+            }),
+        }
+    }
+
+    /// Stop mining as soon as every worker notices (within a few microseconds)
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Hashes computed so far under this handle
+    pub fn hashes(&self) -> u64 {
+        self.state.hashes.load(Ordering::Relaxed)
+    }
+
+    /// Average hashes per second since the handle was created
+    pub fn hashrate(&self) -> f64 {
+        per_second(self.hashes(), self.state.created.elapsed())
+    }
+
+    fn add_hashes(&self, count: u64) {
+        self.state.hashes.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Cancel this job as soon as another block reaches the active chain
+    ///
+    /// Pass a receiver from [`Blockchain::subscribe`](super::Blockchain::subscribe)
+    /// taken before mining started. Must be called inside a tokio runtime.
+    pub fn cancel_on_new_tip(&self, mut events: broadcast::Receiver<ChainEvent>) -> JoinHandle<()> {
+        let handle = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    // Missed events may well include a new tip
+                    Ok(ChainEvent::BlockConnected(_)) | Err(RecvError::Lagged(_)) => {
+                        handle.cancel();
+                        return;
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+}
+
+/// Outcome of one call to [`Miner::mine`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiningReport {
+    pub nonce: Option<u64>,        // Winning nonce, or None if cancelled
+    pub hashes: u64,
+    pub elapsed: Duration,
+}
+
+impl MiningReport {
+    pub fn found(&self) -> bool {
+        self.nonce.is_some()
+    }
+
+    /// Hashes per second over the run
+    pub fn hashrate(&self) -> f64 {
+        per_second(self.hashes, self.elapsed)
+    }
+}
+
+fn per_second(hashes: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 { hashes as f64 / seconds } else { 0.0 }
+}
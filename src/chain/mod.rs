@@ -0,0 +1,57 @@
+//! UTXO blockchain library
+//!
+//! The types from the `blockchain104` demo (blocks, UTXO transactions, wallets and the
+//! chain itself) packaged as a reusable module. Everything that can fail returns a
+//! [`ChainError`] instead of printing or panicking, so services and integration tests
+//! can depend on it directly.
+//!
+//! ```no_run
+//! use rust101::chain::{Blockchain, Fee, Transaction, Wallet};
+//!
+//! let alice = Wallet::new();
+//! let bob = Wallet::new();
+//! let mut chain = Blockchain::new(2, &alice.get_address());
+//!
+//! let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(1), &chain.utxo_set)?;
+//! chain.add_block(vec![tx], &alice.get_address())?;
+//! assert_eq!(chain.get_balance(&bob.get_address()), 30);
+//! # Ok::<(), rust101::chain::ChainError>(())
+//! ```
+
+mod block;
+mod blockchain;
+mod encoding;
+mod error;
+mod event;
+mod hash;
+mod header;
+mod mempool;
+mod merkle;
+mod miner;
+mod params;
+mod pow;
+mod store;
+mod transaction;
+mod utxo;
+mod wallet;
+
+pub use block::Block;
+pub use blockchain::Blockchain;
+pub(crate) use blockchain::locator_heights;
+pub use encoding::{write_compact_size, write_str, Decode, Encode, Reader};
+pub use error::{ChainError, DecodeError, TxValidationError};
+pub use event::{BlockStatus, ChainEvent};
+pub use hash::hash_pub_key;
+pub use header::{
+    median_time_past, validate_header, validate_header_chain, BlockHeader, BLOCK_VERSION, HEADER_SIZE,
+    MAX_FUTURE_BLOCK_TIME, MEDIAN_TIME_SPAN,
+};
+pub use mempool::{AddOutcome, Mempool, MempoolEntry, MempoolError, DEFAULT_MEMPOOL_SIZE};
+pub use merkle::MerkleProof;
+pub use miner::{Miner, MiningHandle, MiningReport};
+pub use params::ChainParams;
+pub use pow::Target;
+pub use store::{ChainStore, StoreError};
+pub use transaction::{verify_transactions, Fee, TXInput, TXOutput, Transaction, TX_VERSION};
+pub use utxo::{BlockUndo, OutPoint, UtxoOverlay, UtxoSet, UtxoView};
+pub use wallet::{verify_signature, Wallet};
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use super::block::Block;
+use super::header::BlockHeader;
+use super::pow::Target;
+
+/// Consensus parameters a chain is created with
+///
+/// The emission schedule works like Bitcoin's: the block subsidy starts at
+/// `initial_subsidy`, halves every `halving_interval` blocks and stops for good once
+/// `max_supply` coins have been issued.
+///
+/// Difficulty retargets every `retarget_interval` blocks so blocks arrive roughly
+/// every `target_block_time` seconds, never getting easier than `pow_limit`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainParams {
+    pub initial_subsidy: i64,      // Coins minted by the genesis coinbase
+    pub halving_interval: u64,     // Blocks between subsidy halvings
+    pub max_supply: i64,           // Hard cap on coins ever issued
+    pub max_block_size: usize,     // Bytes of non-coinbase transactions a block may carry
+    pub pow_limit: Target,         // Genesis target and the easiest one ever allowed
+    pub retarget_interval: u64,    // Blocks per difficulty window
+    pub target_block_time: i64,    // Desired seconds between blocks
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams {
+            initial_subsidy: 50,
+            halving_interval: 210_000,
+            max_supply: 21_000_000,
+            max_block_size: 1_000_000,
+            pow_limit: Target::from_leading_zeros(4),
+            retarget_interval: 2016,
+            target_block_time: 600,
+        }
+    }
+}
+
+impl ChainParams {
+    /// Default parameters starting at `difficulty` leading hex zeros
+    pub fn with_difficulty(difficulty: usize) -> Self {
+        ChainParams {
+            pow_limit: Target::from_leading_zeros(difficulty),
+            ..Self::default()
+        }
+    }
+
+    /// Subsidy the schedule grants at `height`, ignoring the supply cap
+    pub fn block_subsidy(&self, height: u64) -> i64 {
+        let halvings = height / self.halving_interval.max(1);
+        if halvings >= 63 {
+            return 0;
+        }
+        self.initial_subsidy >> halvings
+    }
+
+    /// Subsidy a block at `height` may mint when `issued` coins already exist
+    pub fn allowed_subsidy(&self, height: u64, issued: i64) -> i64 {
+        self.block_subsidy(height)
+            .min(self.max_supply - issued)
+            .max(0)
+    }
+
+    /// Target the block following `chain` (blocks from genesis up) must carry
+    pub fn next_target(&self, chain: &[Block]) -> Target {
+        self.target_at(chain.len() as u64, |height| chain[height as usize].header())
+    }
+
+    /// Target a block at `height` must carry, where `ancestor(h)` returns the header at
+    /// height `h` on the branch it extends
+    ///
+    /// Within a window every block keeps its predecessor's target. At each window
+    /// boundary the target is scaled by how long the last window actually took versus
+    /// how long it should have taken, measured between its first and last block and
+    /// clamped to a factor of four either way as Bitcoin does.
+    pub fn target_at(&self, height: u64, ancestor: impl Fn(u64) -> BlockHeader) -> Target {
+        if height == 0 {
+            return self.pow_limit;
+        }
+        let last = ancestor(height - 1);
+        let interval = self.retarget_interval.max(2);
+        if !height.is_multiple_of(interval) {
+            return last.target;
+        }
+
+        let first = ancestor(height - interval);
+        // Saturating, so hostile timestamps or huge parameters just hit the clamp
+        let blocks = i64::try_from(interval - 1).unwrap_or(i64::MAX);
+        let expected = self.target_block_time.max(1).saturating_mul(blocks);
+        let actual = last
+            .timestamp
+            .saturating_sub(first.timestamp)
+            .clamp(expected / 4, expected.saturating_mul(4));
+        last.target
+            .scale(actual.max(1) as u64, expected as u64)
+            .min(self.pow_limit)
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 256-bit proof-of-work target
+///
+/// A block hash, read as a big-endian number, must be less than or equal to the
+/// target. Halving the target doubles the expected work, so unlike counting leading
+/// hex zeros the difficulty can move in steps of any size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Target([u8; 32]); // Big-endian, so byte order matches numeric order
+
+impl Target {
+    /// The easiest possible target: every hash meets it
+    pub const MAX: Target = Target([0xff; 32]);
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        Target(bytes)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Largest target whose hashes start with `zeros` hex zeros, matching the old
+    /// `difficulty` setting of the demos
+    pub fn from_leading_zeros(zeros: usize) -> Self {
+        let mut bytes = [0xff; 32];
+        let zero_bits = (zeros * 4).min(256);
+        for bit in 0..zero_bits {
+            bytes[bit / 8] &= !(0x80 >> (bit % 8));
+        }
+        Target(bytes)
+    }
+
+    /// Parse a 64-character hex block hash
+    pub fn from_hex(hash: &str) -> Option<Self> {
+        let bytes = hex::decode(hash).ok()?;
+        Some(Target(bytes.try_into().ok()?))
+    }
+
+    /// Whether the hex block hash `hash` satisfies this target
+    pub fn is_met_by(&self, hash: &str) -> bool {
+        Target::from_hex(hash).is_some_and(|value| value <= *self)
+    }
+
+    /// Expected number of hashes needed to meet this target, roughly `2^256 / target`
+    ///
+    /// Computed from the top 128 bits, which keeps it in a `u128` and is plenty of
+    /// precision for comparing branches. Saturates for absurdly hard targets.
+    pub fn work(&self) -> u128 {
+        let high = u128::from_be_bytes(self.0[..16].try_into().expect("16-byte half"));
+        u128::MAX / high.saturating_add(1)
+    }
+
+    /// `self * numerator / denominator`, saturating at [`Target::MAX`]
+    pub fn scale(self, numerator: u64, denominator: u64) -> Self {
+        let denominator = u128::from(denominator.max(1));
+
+        // Little-endian 64-bit limbs, one extra for the product's overflow
+        let mut limbs = [0u64; 5];
+        for (i, chunk) in self.0.rchunks(8).enumerate() {
+            limbs[i] = u64::from_be_bytes(chunk.try_into().expect("8-byte chunk"));
+        }
+
+        let mut carry = 0u128;
+        for limb in &mut limbs {
+            let product = u128::from(*limb) * u128::from(numerator) + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let current = (remainder << 64) | u128::from(*limb);
+            *limb = (current / denominator) as u64;
+            remainder = current % denominator;
+        }
+
+        if limbs[4] != 0 {
+            return Target::MAX;
+        }
+        let mut bytes = [0u8; 32];
+        for (i, chunk) in bytes.rchunks_mut(8).enumerate() {
+            chunk.copy_from_slice(&limbs[i].to_be_bytes());
+        }
+        Target(bytes)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
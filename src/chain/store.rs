@@ -0,0 +1,394 @@
+use sled::transaction::{
+    ConflictableTransactionError, TransactionError, Transactional, TransactionalTree, UnabortableTransactionError,
+};
+use sled::Tree;
+use std::convert::Infallible;
+use std::path::Path;
+use thiserror::Error;
+
+use super::block::Block;
+use super::encoding::{write_str, Decode, Encode};
+use super::error::{ChainError, DecodeError};
+use super::transaction::TXOutput;
+use super::utxo::{BlockUndo, OutPoint, UtxoSet};
+
+/// Key of the tip pointer in the `meta` tree
+const TIP_KEY: &[u8] = b"tip";
+
+/// Key of the hash of the block the UTXO set and undo data are up to date with
+const UTXO_BEST_KEY: &[u8] = b"utxo_best";
+
+/// Why the chain store could not be read or written
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("database error: {0}")]
+    Database(#[from] sled::Error),
+
+    #[error("corrupt record: {0}")]
+    Corrupt(#[from] DecodeError),
+
+    #[error("stored chain is missing {0}")]
+    Missing(String),
+
+    #[error("store is inconsistent: {0}; reindex it")]
+    Inconsistent(String),
+}
+
+impl From<StoreError> for ChainError {
+    fn from(error: StoreError) -> Self {
+        ChainError::Storage(error.to_string())
+    }
+}
+
+/// The active chain and its UTXO set, persisted in sled
+///
+/// Every change of the active chain - a single block or a whole reorganization - is
+/// written in one sled transaction across all trees, so the store always holds one
+/// consistent tip. Blocks are kept in their canonical [`Encode`] form. Clones are
+/// handles on the same open database.
+#[derive(Clone)]
+pub struct ChainStore {
+    db: sled::Db,
+    blocks: Tree,    // Encoded block by hash
+    undo: Tree,      // Encoded undo data by hash, for blocks on the active chain
+    heights: Tree,   // Block hash by big-endian height, for the active chain
+    meta: Tree,      // Tip pointer and UTXO best-block marker
+    utxos: Tree,     // Encoded output by encoded outpoint
+    addresses: Tree, // Empty values keyed by address | outpoint
+}
+
+impl ChainStore {
+    /// Open (or create) a store in the directory `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_db(sled::open(path)?)
+    }
+
+    /// Store that is deleted when dropped, for tests and demos
+    pub fn temporary() -> Result<Self, StoreError> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self, StoreError> {
+        Ok(ChainStore {
+            blocks: db.open_tree("blocks")?,
+            undo: db.open_tree("undo")?,
+            heights: db.open_tree("heights")?,
+            meta: db.open_tree("meta")?,
+            utxos: db.open_tree("utxos")?,
+            addresses: db.open_tree("addresses")?,
+            db,
+        })
+    }
+
+    /// Tip of the stored chain, or `None` if nothing was stored yet
+    pub fn tip(&self) -> Result<Option<Block>, StoreError> {
+        let Some(hash) = self.meta.get(TIP_KEY)? else {
+            return Ok(None);
+        };
+        let hash = String::from_utf8_lossy(&hash);
+        self.block(&hash)?
+            .map(Some)
+            .ok_or_else(|| StoreError::Missing(format!("tip block {hash}")))
+    }
+
+    /// Any block the store has seen, active or not
+    pub fn block(&self, hash: &str) -> Result<Option<Block>, StoreError> {
+        match self.blocks.get(hash)? {
+            Some(bytes) => Ok(Some(Block::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Hash of the block at `height` on the stored chain
+    pub fn hash_at(&self, height: u64) -> Result<Option<String>, StoreError> {
+        Ok(self
+            .heights
+            .get(height.to_be_bytes())?
+            .map(|hash| String::from_utf8_lossy(&hash).into_owned()))
+    }
+
+    /// Block at `height` on the stored chain
+    pub fn block_at(&self, height: u64) -> Result<Option<Block>, StoreError> {
+        match self.hash_at(height)? {
+            Some(hash) => self.block(&hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up an unspent output
+    pub fn utxo(&self, outpoint: &OutPoint) -> Result<Option<TXOutput>, StoreError> {
+        match self.utxos.get(outpoint.encode())? {
+            Some(bytes) => Ok(Some(TXOutput::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Unspent outputs locked to `address`, read through the address index
+    pub fn outputs_for(&self, address: &str) -> Result<Vec<(OutPoint, TXOutput)>, StoreError> {
+        let prefix = address_prefix(address);
+        let mut outputs = Vec::new();
+        for entry in self.addresses.scan_prefix(&prefix) {
+            let (key, _) = entry?;
+            let outpoint = OutPoint::decode(&key[prefix.len()..])?;
+            let output = self
+                .utxo(&outpoint)?
+                .ok_or_else(|| StoreError::Missing(format!("indexed output {outpoint}")))?;
+            outputs.push((outpoint, output));
+        }
+        Ok(outputs)
+    }
+
+    /// Sum of the unspent outputs locked to `address`
+    pub fn balance(&self, address: &str) -> Result<i64, StoreError> {
+        Ok(self.outputs_for(address)?.iter().map(|(_, output)| i64::from(output.value)).sum())
+    }
+
+    /// Load the whole stored UTXO set into memory
+    pub fn utxo_set(&self) -> Result<UtxoSet, StoreError> {
+        let mut utxo_set = UtxoSet::new();
+        for entry in self.utxos.iter() {
+            let (key, value) = entry?;
+            utxo_set.insert(OutPoint::decode(&key)?, TXOutput::decode(&value)?);
+        }
+        Ok(utxo_set)
+    }
+
+    /// Every block of the stored chain from genesis, with its undo data
+    pub(crate) fn active_chain(&self) -> Result<Vec<(Block, BlockUndo)>, StoreError> {
+        let mut chain = Vec::new();
+        for entry in self.heights.iter() {
+            let (_, hash) = entry?;
+            let hash = String::from_utf8_lossy(&hash);
+            chain.push(self.connected(&hash)?);
+        }
+        Ok(chain)
+    }
+
+    // A block on the stored chain and its undo data, both of which must be present
+    fn connected(&self, hash: &str) -> Result<(Block, BlockUndo), StoreError> {
+        let block = self
+            .block(hash)?
+            .ok_or_else(|| StoreError::Missing(format!("block {hash}")))?;
+        let undo = self
+            .undo
+            .get(hash)?
+            .ok_or_else(|| StoreError::Missing(format!("undo data for block {hash}")))?;
+        Ok((block, BlockUndo::decode(&undo)?))
+    }
+
+    /// Check that the UTXO set is up to date with the tip
+    ///
+    /// Both move together in every write, so they only disagree when the store was
+    /// damaged or a [`reindex`](Self::reindex) was interrupted; [`Blockchain::open`]
+    /// refuses such a store.
+    ///
+    /// [`Blockchain::open`]: super::Blockchain::open
+    pub fn check_consistency(&self) -> Result<(), StoreError> {
+        let tip = self.meta.get(TIP_KEY)?;
+        let utxo_best = self.meta.get(UTXO_BEST_KEY)?;
+        if tip == utxo_best {
+            return Ok(());
+        }
+        let describe = |hash: Option<sled::IVec>| match hash {
+            Some(hash) => format!("block {}", String::from_utf8_lossy(&hash)),
+            None => String::from("nothing"),
+        };
+        Err(StoreError::Inconsistent(format!(
+            "UTXO set reflects {} but the tip is {}",
+            describe(utxo_best),
+            describe(tip)
+        )))
+    }
+
+    /// Rebuild the height index, UTXO set, address index and undo data from the
+    /// stored blocks, following `previous_hash` links down from the tip
+    ///
+    /// `progress(height, tip_height)` is called after each block. The UTXO marker is
+    /// cleared first and advanced block by block, so an interrupted reindex fails
+    /// [`check_consistency`](Self::check_consistency) until it is run again.
+    pub fn reindex(&self, mut progress: impl FnMut(u64, u64)) -> Result<(), StoreError> {
+        let Some(tip) = self.tip()? else {
+            return Ok(());
+        };
+        self.meta.remove(UTXO_BEST_KEY)?;
+        self.db.flush()?;
+
+        let mut hashes = vec![tip.hash.clone()];
+        let mut block = tip;
+        while block.id > 0 {
+            block = self
+                .block(&block.previous_hash)?
+                .ok_or_else(|| StoreError::Missing(format!("parent of block {}", block.hash)))?;
+            hashes.push(block.hash.clone());
+        }
+        hashes.reverse();
+
+        for tree in [&self.heights, &self.undo, &self.utxos, &self.addresses] {
+            tree.clear()?;
+        }
+        let tip_height = hashes.len() as u64 - 1;
+        let mut utxo_set = UtxoSet::new();
+        for (height, hash) in hashes.iter().enumerate() {
+            let block = self
+                .block(hash)?
+                .ok_or_else(|| StoreError::Missing(format!("block {hash}")))?;
+            if block.id != height as u64 {
+                return Err(StoreError::Inconsistent(format!("block {hash} claims height {}", block.id)));
+            }
+            let block_undo = utxo_set
+                .apply_block(&block)
+                .map_err(|error| StoreError::Inconsistent(format!("block {height}: {error}")))?;
+
+            let trees = (&self.undo, &self.heights, &self.meta, &self.utxos, &self.addresses);
+            trees
+                .transaction(|(undo_tree, heights, meta, utxos, addresses)| {
+                    Coins { utxos, addresses }.connect(&block, &block_undo)?;
+                    undo_tree.insert(block.hash.as_bytes(), block_undo.encode())?;
+                    heights.insert(&block.id.to_be_bytes(), block.hash.as_bytes())?;
+                    meta.insert(UTXO_BEST_KEY, block.hash.as_bytes())?;
+                    Ok::<_, ConflictableTransactionError<Infallible>>(())
+                })
+                .map_err(storage_error)?;
+            progress(height as u64, tip_height);
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Make the stored chain match `blocks`, whose undo data is the parallel `undo`
+    ///
+    /// Only the difference is written: stored blocks above the fork point are
+    /// disconnected and the new ones connected, all in a single transaction, which is
+    /// flushed before returning.
+    pub(crate) fn sync(&self, blocks: &[Block], undo: &[BlockUndo]) -> Result<(), StoreError> {
+        let stored_tip = match self.heights.last()? {
+            Some((height, _)) => Some(u64::from_be_bytes(
+                height.as_ref().try_into().map_err(|_| DecodeError::UnexpectedEnd)?,
+            )),
+            None => None,
+        };
+
+        // Highest height at which the stored chain and `blocks` agree
+        let mut fork = stored_tip.map(|tip| tip.min(blocks.len() as u64 - 1));
+        while let Some(height) = fork {
+            if self.hash_at(height)?.as_deref() == Some(blocks[height as usize].hash.as_str()) {
+                break;
+            }
+            fork = height.checked_sub(1);
+        }
+        let first_new = fork.map_or(0, |height| height + 1);
+
+        let mut disconnect = Vec::new();
+        if let Some(tip) = stored_tip {
+            for height in (first_new..=tip).rev() {
+                let hash = self
+                    .hash_at(height)?
+                    .ok_or_else(|| StoreError::Missing(format!("block at height {height}")))?;
+                disconnect.push(self.connected(&hash)?);
+            }
+        }
+        let connect: Vec<_> = blocks.iter().zip(undo).skip(first_new as usize).collect();
+        if disconnect.is_empty() && connect.is_empty() {
+            return Ok(());
+        }
+
+        let trees = (&self.blocks, &self.undo, &self.heights, &self.meta, &self.utxos, &self.addresses);
+        trees
+            .transaction(|(blocks_tree, undo_tree, heights, meta, utxos, addresses)| {
+                let coins = Coins { utxos, addresses };
+                for (block, block_undo) in &disconnect {
+                    coins.disconnect(block, block_undo)?;
+                    heights.remove(&block.id.to_be_bytes())?;
+                    undo_tree.remove(block.hash.as_bytes())?;
+                }
+                for (block, block_undo) in &connect {
+                    coins.connect(block, block_undo)?;
+                    blocks_tree.insert(block.hash.as_bytes(), block.encode())?;
+                    undo_tree.insert(block.hash.as_bytes(), block_undo.encode())?;
+                    heights.insert(&block.id.to_be_bytes(), block.hash.as_bytes())?;
+                }
+                if let Some(tip) = blocks.last() {
+                    meta.insert(TIP_KEY, tip.hash.as_bytes())?;
+                    meta.insert(UTXO_BEST_KEY, tip.hash.as_bytes())?;
+                }
+                Ok::<_, ConflictableTransactionError<Infallible>>(())
+            })
+            .map_err(storage_error)?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+// Transactions here never abort, so any failure is the database's
+fn storage_error(error: TransactionError<Infallible>) -> StoreError {
+    match error {
+        TransactionError::Storage(error) => StoreError::Database(error),
+        TransactionError::Abort(never) => match never {},
+    }
+}
+
+/// Key prefix of every address index entry for `address`
+fn address_prefix(address: &str) -> Vec<u8> {
+    let mut prefix = Vec::new();
+    write_str(&mut prefix, address);
+    prefix
+}
+
+/// UTXO and address index trees inside a transaction
+struct Coins<'a> {
+    utxos: &'a TransactionalTree,
+    addresses: &'a TransactionalTree,
+}
+
+impl Coins<'_> {
+    fn insert(&self, outpoint: &OutPoint, output: &TXOutput) -> Result<(), UnabortableTransactionError> {
+        let key = outpoint.encode();
+        self.utxos.insert(key.as_slice(), output.encode())?;
+        let mut index_key = address_prefix(&output.pub_key_hash);
+        index_key.extend(key);
+        self.addresses.insert(index_key, &[])?;
+        Ok(())
+    }
+
+    fn remove(&self, outpoint: &OutPoint, output: &TXOutput) -> Result<(), UnabortableTransactionError> {
+        let key = outpoint.encode();
+        self.utxos.remove(key.as_slice())?;
+        let mut index_key = address_prefix(&output.pub_key_hash);
+        index_key.extend(key);
+        self.addresses.remove(index_key)?;
+        Ok(())
+    }
+
+    // Same order as `UtxoSet::apply_block`, so outputs created and spent inside the
+    // block end up spent
+    fn connect(&self, block: &Block, undo: &BlockUndo) -> Result<(), UnabortableTransactionError> {
+        let mut spent = undo.spent.iter();
+        for tx in &block.transactions {
+            if !tx.is_coinbase() {
+                for (outpoint, output) in spent.by_ref().take(tx.vin.len()) {
+                    self.remove(outpoint, output)?;
+                }
+            }
+            for (vout, output) in tx.vout.iter().enumerate() {
+                self.insert(&OutPoint::new(tx.id.clone(), vout), output)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Mirror of `connect`, walking the transactions backwards
+    fn disconnect(&self, block: &Block, undo: &BlockUndo) -> Result<(), UnabortableTransactionError> {
+        let mut spent = undo.spent.iter().rev();
+        for tx in block.transactions.iter().rev() {
+            for (vout, output) in tx.vout.iter().enumerate() {
+                self.remove(&OutPoint::new(tx.id.clone(), vout), output)?;
+            }
+            if !tx.is_coinbase() {
+                for (outpoint, output) in spent.by_ref().take(tx.vin.len()) {
+                    self.insert(outpoint, output)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
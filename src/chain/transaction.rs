@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::encoding::{write_compact_size, write_str, Decode, Encode, Reader};
+use super::error::{ChainError, DecodeError, TxValidationError};
+use super::hash::{hash_pub_key, sha256_hex};
+use super::utxo::{OutPoint, UtxoOverlay, UtxoSet, UtxoView};
+use super::wallet::{verify_signature, Wallet};
+
+/// Version written at the start of every encoded transaction
+pub const TX_VERSION: u32 = 1;
+
+/// Transaction Input - references a previous transaction output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TXInput {
+    pub txid: String,              // Transaction ID being spent
+    pub vout: usize,               // Output index in that transaction
+    pub signature: String,         // Signature proving ownership
+    pub pub_key: String,           // Public key of sender
+}
+
+impl TXInput {
+    pub fn new(txid: String, vout: usize, signature: String, pub_key: String) -> Self {
+        TXInput {
+            txid,
+            vout,
+            signature,
+            pub_key,
+        }
+    }
+
+    /// The output this input spends
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(self.txid.clone(), self.vout)
+    }
+
+    /// Check if this input can be unlocked by a public key
+    pub fn can_unlock_output_with(&self, pub_key_hash: &str) -> bool {
+        let input_pub_key_hash = hash_pub_key(&self.pub_key);
+        input_pub_key_hash == pub_key_hash
+    }
+}
+
+/// txid | vout | signature | pub_key
+impl Encode for TXInput {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        write_str(out, &self.txid);
+        write_compact_size(out, self.vout as u64);
+        write_str(out, &self.signature);
+        write_str(out, &self.pub_key);
+    }
+}
+
+impl Decode for TXInput {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let txid = reader.string()?;
+        let vout = reader.compact_size()?;
+        Ok(TXInput {
+            txid,
+            vout: usize::try_from(vout).map_err(|_| DecodeError::NonCanonicalSize(vout))?,
+            signature: reader.string()?,
+            pub_key: reader.string()?,
+        })
+    }
+}
+
+/// Transaction Output - represents coins that can be spent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TXOutput {
+    pub value: i32,                // Amount of coins
+    pub pub_key_hash: String,      // Hash of public key (address)
+}
+
+impl TXOutput {
+    pub fn new(value: i32, address: &str) -> Self {
+        let mut output = TXOutput {
+            value,
+            pub_key_hash: String::new(),
+        };
+        output.lock(address);
+        output
+    }
+
+    /// Lock output to an address
+    fn lock(&mut self, address: &str) {
+        // In real implementation, this would decode base58 address
+        // For educational purposes, we'll use the address directly
+        self.pub_key_hash = address.to_string();
+    }
+
+    /// Check if output can be unlocked by a public key
+    pub fn can_be_unlocked_with(&self, pub_key_hash: &str) -> bool {
+        self.pub_key_hash == pub_key_hash
+    }
+}
+
+/// value | pub_key_hash
+impl Encode for TXOutput {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend(self.value.to_le_bytes());
+        write_str(out, &self.pub_key_hash);
+    }
+}
+
+impl Decode for TXOutput {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(TXOutput {
+            value: reader.i32()?,
+            pub_key_hash: reader.string()?,
+        })
+    }
+}
+
+/// Fee a sender attaches to a transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fee {
+    /// Pay exactly this many coins
+    Fixed(i32),
+    /// Pay this many coins per byte of the transaction's size, rounded up
+    PerByte(f64),
+}
+
+impl Fee {
+    /// Fee owed by a transaction of `size` bytes
+    ///
+    /// Negative fees, rates that are negative or not a number, and fees that do not
+    /// fit in an amount are [`ChainError::InvalidAmount`].
+    pub fn for_size(&self, size: usize) -> Result<i32, ChainError> {
+        let fee = match *self {
+            Fee::Fixed(fee) => fee,
+            Fee::PerByte(rate) if !rate.is_finite() || rate < 0.0 => {
+                return Err(ChainError::InvalidAmount(rate as i32));
+            }
+            Fee::PerByte(rate) => {
+                let fee = (rate * size as f64).ceil();
+                if fee > f64::from(i32::MAX) {
+                    return Err(ChainError::InvalidAmount(i32::MAX));
+                }
+                fee as i32
+            }
+        };
+        if fee < 0 {
+            return Err(ChainError::InvalidAmount(fee));
+        }
+        Ok(fee)
+    }
+}
+
+/// Transaction - with UTXO model
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,                    // Transaction hash
+    pub vin: Vec<TXInput>,             // Inputs (coins being spent)
+    pub vout: Vec<TXOutput>,           // Outputs (new coins)
+    pub timestamp: i64,
+}
+
+impl Transaction {
+    /// Create a coinbase transaction paying `reward` (block subsidy plus fees) to `to`
+    pub fn new_coinbase(to: &str, reward: i32, data: Option<String>) -> Self {
+        let timestamp = Utc::now().timestamp();
+        let txout = TXOutput::new(reward, to);
+
+        // Coinbase has no real input
+        let txin = TXInput {
+            txid: String::new(),
+            vout: 0,
+            signature: data.unwrap_or_else(|| format!("Reward to {}", to)),
+            pub_key: String::new(),
+        };
+
+        let mut tx = Transaction {
+            id: String::new(),
+            vin: vec![txin],
+            vout: vec![txout],
+            timestamp,
+        };
+        tx.id = tx.calculate_hash();
+        tx
+    }
+
+    /// Create a regular UTXO transaction sending `amount` to `to` and paying `fee`
+    /// to the miner
+    pub fn new_utxo_transaction(
+        from_wallet: &Wallet,
+        to: &str,
+        amount: i32,
+        fee: Fee,
+        utxo_set: &UtxoSet,
+    ) -> Result<Self, ChainError> {
+        if amount <= 0 {
+            return Err(ChainError::InvalidAmount(amount));
+        }
+        let from_pub_key_hash = hash_pub_key(&from_wallet.public_key);
+
+        // A per-byte fee depends on the size, which depends on how many inputs and
+        // outputs the fee makes us need; rebuild until the fee covers the final size
+        let mut fee_amount = fee.for_size(0)?;
+        loop {
+            let needed = amount
+                .checked_add(fee_amount)
+                .ok_or(ChainError::InvalidAmount(fee_amount))?;
+
+            // Find spendable outputs
+            let (accumulated, valid_outputs) =
+                find_spendable_outputs(&from_pub_key_hash, needed, utxo_set);
+
+            if accumulated < i64::from(needed) {
+                return Err(ChainError::InsufficientFunds {
+                    needed,
+                    available: i32::try_from(accumulated).expect("less than an amount"),
+                });
+            }
+
+            // Build inputs (signed below, once the whole transaction is known)
+            let inputs = valid_outputs
+                .into_iter()
+                .map(|outpoint| {
+                    TXInput::new(outpoint.txid, outpoint.vout, String::new(), from_wallet.public_key.clone())
+                })
+                .collect();
+
+            // Build outputs
+            let mut outputs = vec![TXOutput::new(amount, to)];
+
+            // Add change output if necessary; whatever is not paid out is the fee
+            if accumulated > i64::from(needed) {
+                // Less than the last coin picked, so it fits an output
+                let change = i32::try_from(accumulated - i64::from(needed)).expect("less than one output");
+                outputs.push(TXOutput::new(change, &from_wallet.get_address()));
+            }
+
+            let mut tx = Transaction {
+                id: String::new(),
+                vin: inputs,
+                vout: outputs,
+                timestamp: Utc::now().timestamp(),
+            };
+            tx.sign(from_wallet);
+            tx.id = tx.calculate_hash();
+
+            let required = fee.for_size(tx.size())?;
+            if required <= fee_amount {
+                return Ok(tx);
+            }
+            fee_amount = required;
+        }
+    }
+
+    /// Sign every input with `wallet` over the transaction's signing digest
+    ///
+    /// Call this before computing the id, since the id covers the signatures.
+    pub fn sign(&mut self, wallet: &Wallet) {
+        let digest = self.signing_digest();
+        let signature = wallet.sign(&digest);
+        for input in &mut self.vin {
+            input.signature = signature.clone();
+        }
+    }
+
+    /// Canonical digest that input signatures commit to
+    ///
+    /// The hash of the encoding with every signature left empty: it covers every
+    /// input's outpoint and public key, every output and the timestamp, but not the
+    /// signatures themselves or the id.
+    pub fn signing_digest(&self) -> [u8; 32] {
+        let mut unsigned = Vec::new();
+        self.encode_parts(&mut unsigned, false);
+        Sha256::digest(unsigned).into()
+    }
+
+    /// Size in bytes of the transaction's canonical encoding, used for per-byte fees
+    pub fn size(&self) -> usize {
+        self.encode().len()
+    }
+
+    // version | vin count | inputs | vout count | outputs | timestamp
+    fn encode_parts(&self, out: &mut Vec<u8>, with_signatures: bool) {
+        out.extend(TX_VERSION.to_le_bytes());
+        write_compact_size(out, self.vin.len() as u64);
+        for input in &self.vin {
+            if with_signatures {
+                input.encode_to(out);
+            } else {
+                TXInput { signature: String::new(), ..input.clone() }.encode_to(out);
+            }
+        }
+        write_compact_size(out, self.vout.len() as u64);
+        for output in &self.vout {
+            output.encode_to(out);
+        }
+        out.extend(self.timestamp.to_le_bytes());
+    }
+
+    /// Check if transaction is coinbase
+    pub fn is_coinbase(&self) -> bool {
+        self.vin.len() == 1 && self.vin[0].txid.is_empty()
+    }
+
+    /// Calculate transaction hash: SHA-256 of the canonical encoding
+    pub fn calculate_hash(&self) -> String {
+        sha256_hex(&self.encode())
+    }
+
+    /// Fully validate the transaction against `view` and return its fee
+    ///
+    /// Checks that every input references a distinct unspent output, that the input's
+    /// public key owns that output and signed this transaction, that every output is
+    /// positive and that the outputs do not exceed the inputs. The fee is whatever the
+    /// inputs leave over. A coinbase has nothing to spend and always has a zero fee;
+    /// how much it may claim is checked at the block level.
+    pub fn verify<V: UtxoView + ?Sized>(&self, view: &V) -> Result<i64, TxValidationError> {
+        if self.vout.is_empty() {
+            return Err(TxValidationError::NoOutputs);
+        }
+        // Once the subsidy has run out a coinbase in a block without fees pays zero
+        let min_value = if self.is_coinbase() { 0 } else { 1 };
+        for (index, output) in self.vout.iter().enumerate() {
+            if output.value < min_value {
+                return Err(TxValidationError::NonPositiveOutput { index, value: output.value });
+            }
+        }
+        if self.is_coinbase() {
+            return Ok(0);
+        }
+        if self.vin.is_empty() {
+            return Err(TxValidationError::NoInputs);
+        }
+
+        let digest = self.signing_digest();
+        let mut seen = HashSet::new();
+        let mut input_total: i64 = 0;
+        for (index, input) in self.vin.iter().enumerate() {
+            let outpoint = input.outpoint();
+            if input.txid.is_empty() {
+                return Err(TxValidationError::UnexpectedCoinbase);
+            }
+            if !seen.insert(outpoint.clone()) {
+                return Err(TxValidationError::DuplicateInput(outpoint));
+            }
+
+            // Find the output being spent
+            let output = view
+                .output(&outpoint)
+                .ok_or(TxValidationError::UnknownOutpoint(outpoint))?;
+
+            if !input.can_unlock_output_with(&output.pub_key_hash) {
+                return Err(TxValidationError::PubKeyMismatch { index });
+            }
+            if !verify_signature(&input.pub_key, &digest, &input.signature) {
+                return Err(TxValidationError::BadSignature { index });
+            }
+            input_total += i64::from(output.value);
+        }
+
+        let output_total = self.output_total();
+        if output_total > input_total {
+            return Err(TxValidationError::Overspend {
+                inputs: input_total,
+                outputs: output_total,
+            });
+        }
+        Ok(input_total - output_total)
+    }
+
+    /// Sum of all output values
+    pub fn output_total(&self) -> i64 {
+        self.vout.iter().map(|output| i64::from(output.value)).sum()
+    }
+}
+
+/// The id is not encoded; it is the hash of the encoding itself
+impl Encode for Transaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.encode_parts(out, true);
+    }
+}
+
+impl Decode for Transaction {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let version = reader.u32()?;
+        if version != TX_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let vin = (0..reader.count()?)
+            .map(|_| TXInput::decode_from(reader))
+            .collect::<Result<_, _>>()?;
+        let vout = (0..reader.count()?)
+            .map(|_| TXOutput::decode_from(reader))
+            .collect::<Result<_, _>>()?;
+        let mut tx = Transaction {
+            id: String::new(),
+            vin,
+            vout,
+            timestamp: reader.i64()?,
+        };
+        tx.id = tx.calculate_hash();
+        Ok(tx)
+    }
+}
+
+/// Validate the non-coinbase transactions of one block in order, returning each fee
+///
+/// Later transactions may spend outputs of earlier ones, but no outpoint may be spent
+/// twice across the batch.
+pub fn verify_transactions<V: UtxoView + ?Sized>(
+    view: &V,
+    transactions: &[Transaction],
+) -> Result<Vec<i64>, ChainError> {
+    let mut overlay = UtxoOverlay::new(view);
+    let mut fees = Vec::with_capacity(transactions.len());
+    for tx in transactions {
+        let invalid = |error| ChainError::InvalidTransaction { txid: tx.id.clone(), error };
+        if tx.is_coinbase() {
+            return Err(invalid(TxValidationError::UnexpectedCoinbase));
+        }
+        if let Some(outpoint) = tx.vin.iter().map(TXInput::outpoint).find(|o| overlay.is_spent(o)) {
+            return Err(invalid(TxValidationError::DuplicateInput(outpoint)));
+        }
+        fees.push(tx.verify(&overlay).map_err(invalid)?);
+        overlay.apply(tx);
+    }
+    Ok(fees)
+}
+
+/// Find spendable outputs for a transaction
+fn find_spendable_outputs(
+    pub_key_hash: &str,
+    amount: i32,
+    utxo_set: &UtxoSet,
+) -> (i64, Vec<OutPoint>) {
+    let mut accumulated = 0;
+    let mut unspent_outputs = Vec::new();
+
+    for (outpoint, output) in utxo_set.outputs_for(pub_key_hash) {
+        if accumulated >= i64::from(amount) {
+            break;
+        }
+        accumulated += i64::from(output.value);
+        unspent_outputs.push(outpoint.clone());
+    }
+
+    (accumulated, unspent_outputs)
+}
@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use super::block::Block;
+use super::encoding::{write_compact_size, write_str, Decode, Encode, Reader};
+use super::error::{ChainError, DecodeError};
+use super::transaction::{TXOutput, Transaction};
+
+/// Reference to a single transaction output: `(txid, vout)`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String,
+    pub vout: usize,
+}
+
+impl OutPoint {
+    pub fn new(txid: impl Into<String>, vout: usize) -> Self {
+        OutPoint {
+            txid: txid.into(),
+            vout,
+        }
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
+/// txid | vout, the same layout an input uses to reference it
+impl Encode for OutPoint {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        write_str(out, &self.txid);
+        write_compact_size(out, self.vout as u64);
+    }
+}
+
+impl Decode for OutPoint {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let txid = reader.string()?;
+        let vout = reader.compact_size()?;
+        Ok(OutPoint {
+            txid,
+            vout: usize::try_from(vout).map_err(|_| DecodeError::NonCanonicalSize(vout))?,
+        })
+    }
+}
+
+/// Read access to unspent outputs, so validation can run against the confirmed set
+/// or against the set plus pending changes
+pub trait UtxoView {
+    fn output(&self, outpoint: &OutPoint) -> Option<&TXOutput>;
+}
+
+/// Pending spends and new outputs layered over another view
+///
+/// Used to validate a batch of transactions in order without touching the
+/// underlying set.
+pub struct UtxoOverlay<'a, V: UtxoView + ?Sized> {
+    base: &'a V,
+    created: HashMap<OutPoint, TXOutput>,
+    spent: HashSet<OutPoint>,
+}
+
+impl<'a, V: UtxoView + ?Sized> UtxoOverlay<'a, V> {
+    pub fn new(base: &'a V) -> Self {
+        UtxoOverlay {
+            base,
+            created: HashMap::new(),
+            spent: HashSet::new(),
+        }
+    }
+
+    /// Whether an earlier transaction in this overlay already spent `outpoint`
+    pub fn is_spent(&self, outpoint: &OutPoint) -> bool {
+        self.spent.contains(outpoint)
+    }
+
+    /// Record the spends and outputs of `tx`
+    pub fn apply(&mut self, tx: &Transaction) {
+        if !tx.is_coinbase() {
+            for input in &tx.vin {
+                let outpoint = input.outpoint();
+                self.created.remove(&outpoint);
+                self.spent.insert(outpoint);
+            }
+        }
+        for (vout, output) in tx.vout.iter().enumerate() {
+            self.created.insert(OutPoint::new(tx.id.clone(), vout), output.clone());
+        }
+    }
+}
+
+impl<V: UtxoView + ?Sized> UtxoView for UtxoOverlay<'_, V> {
+    fn output(&self, outpoint: &OutPoint) -> Option<&TXOutput> {
+        if self.spent.contains(outpoint) {
+            return None;
+        }
+        self.created.get(outpoint).or_else(|| self.base.output(outpoint))
+    }
+}
+
+/// Outputs a block spent, in the order it spent them - everything needed to undo it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockUndo {
+    pub spent: Vec<(OutPoint, TXOutput)>,
+}
+
+impl BlockUndo {
+    /// Coins a block created out of thin air: everything it paid out minus
+    /// everything it spent
+    pub fn minted(&self, block: &Block) -> i64 {
+        let created: i64 = block.transactions.iter().map(Transaction::output_total).sum();
+        let spent: i64 = self.spent.iter().map(|(_, output)| i64::from(output.value)).sum();
+        created - spent
+    }
+}
+
+/// count | per spent output: outpoint | output
+impl Encode for BlockUndo {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        write_compact_size(out, self.spent.len() as u64);
+        for (outpoint, output) in &self.spent {
+            outpoint.encode_to(out);
+            output.encode_to(out);
+        }
+    }
+}
+
+impl Decode for BlockUndo {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        let spent = (0..reader.count()?)
+            .map(|_| Ok((OutPoint::decode_from(reader)?, TXOutput::decode_from(reader)?)))
+            .collect::<Result<_, DecodeError>>()?;
+        Ok(BlockUndo { spent })
+    }
+}
+
+/// Unspent transaction outputs keyed by outpoint
+///
+/// Spending one output of a transaction never disturbs its siblings, and the
+/// per-address index makes balance and coin-selection queries proportional to the
+/// number of coins an address owns rather than to the whole set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UtxoSet {
+    utxos: HashMap<OutPoint, TXOutput>,
+    by_address: HashMap<String, BTreeSet<OutPoint>>,
+    total_value: i64,
+}
+
+impl UtxoView for UtxoSet {
+    fn output(&self, outpoint: &OutPoint) -> Option<&TXOutput> {
+        self.utxos.get(outpoint)
+    }
+}
+
+impl UtxoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up an unspent output
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TXOutput> {
+        self.utxos.get(outpoint)
+    }
+
+    pub fn contains(&self, outpoint: &OutPoint) -> bool {
+        self.utxos.contains_key(outpoint)
+    }
+
+    /// Number of unspent outputs
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&OutPoint, &TXOutput)> {
+        self.utxos.iter()
+    }
+
+    /// Unspent outputs locked to `address`, in outpoint order
+    pub fn outputs_for<'a>(
+        &'a self,
+        address: &str,
+    ) -> impl Iterator<Item = (&'a OutPoint, &'a TXOutput)> + 'a {
+        self.by_address
+            .get(address)
+            .into_iter()
+            .flatten()
+            .map(|outpoint| (outpoint, &self.utxos[outpoint]))
+    }
+
+    /// Sum of every unspent output - the coins currently in existence
+    pub fn total_value(&self) -> i64 {
+        self.total_value
+    }
+
+    /// Sum of the unspent outputs locked to `address`
+    pub fn balance(&self, address: &str) -> i64 {
+        self.outputs_for(address).map(|(_, output)| i64::from(output.value)).sum()
+    }
+
+    /// Add an output, returning the one it replaced (if any)
+    pub fn insert(&mut self, outpoint: OutPoint, output: TXOutput) -> Option<TXOutput> {
+        let previous = self.remove(&outpoint);
+        self.by_address
+            .entry(output.pub_key_hash.clone())
+            .or_default()
+            .insert(outpoint.clone());
+        self.total_value += i64::from(output.value);
+        self.utxos.insert(outpoint, output);
+        previous
+    }
+
+    /// Remove an output, returning it if it was unspent
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<TXOutput> {
+        let output = self.utxos.remove(outpoint)?;
+        self.total_value -= i64::from(output.value);
+        self.unindex(outpoint, &output.pub_key_hash);
+        Some(output)
+    }
+
+    fn unindex(&mut self, outpoint: &OutPoint, address: &str) {
+        if let Some(outpoints) = self.by_address.get_mut(address) {
+            outpoints.remove(outpoint);
+            if outpoints.is_empty() {
+                self.by_address.remove(address);
+            }
+        }
+    }
+
+    /// Add the outputs of `tx`
+    ///
+    /// Refuses, leaving the set as it was, if any of them would overwrite an unspent
+    /// output with the same outpoint (as Bitcoin's BIP30 does): a copy of an unspent
+    /// transaction would otherwise replace its coins, and undoing the copy would
+    /// destroy them.
+    pub fn add_outputs(&mut self, tx: &Transaction) -> Result<(), ChainError> {
+        let outpoints = (0..tx.vout.len()).map(|vout| OutPoint::new(tx.id.clone(), vout));
+        if let Some(outpoint) = outpoints.clone().find(|outpoint| self.contains(outpoint)) {
+            return Err(ChainError::DuplicateOutput(outpoint));
+        }
+        for (outpoint, output) in outpoints.zip(&tx.vout) {
+            self.insert(outpoint, output.clone());
+        }
+        Ok(())
+    }
+
+    /// Spend the inputs and add the outputs of every transaction in `block`
+    ///
+    /// Transactions may spend outputs created earlier in the same block. If any input
+    /// references a missing output, or any output one that is still unspent, the set
+    /// is left exactly as it was.
+    pub fn apply_block(&mut self, block: &Block) -> Result<BlockUndo, ChainError> {
+        let mut undo = BlockUndo::default();
+        for (applied, tx) in block.transactions.iter().enumerate() {
+            if let Err(e) = self.apply_transaction(tx, &mut undo) {
+                self.rollback(&block.transactions[..applied], &undo);
+                return Err(e);
+            }
+        }
+        Ok(undo)
+    }
+
+    fn apply_transaction(&mut self, tx: &Transaction, undo: &mut BlockUndo) -> Result<(), ChainError> {
+        let spent_before = undo.spent.len();
+        if !tx.is_coinbase() {
+            for input in &tx.vin {
+                let outpoint = input.outpoint();
+                match self.remove(&outpoint) {
+                    Some(output) => undo.spent.push((outpoint, output)),
+                    None => {
+                        self.unspend(undo, spent_before);
+                        return Err(ChainError::UnknownOutput {
+                            txid: input.txid.clone(),
+                            vout: input.vout,
+                        });
+                    }
+                }
+            }
+        }
+        if let Err(error) = self.add_outputs(tx) {
+            self.unspend(undo, spent_before);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    // Put back what the transaction being applied already spent
+    fn unspend(&mut self, undo: &mut BlockUndo, spent_before: usize) {
+        for (outpoint, output) in undo.spent.drain(spent_before..).rev() {
+            self.insert(outpoint, output);
+        }
+    }
+
+    /// Revert `block`, given the undo data `apply_block` returned for it
+    pub fn undo_block(&mut self, block: &Block, undo: &BlockUndo) {
+        self.rollback(&block.transactions, undo);
+    }
+
+    // Walk the transactions backwards so an output created and spent inside the same
+    // block is restored by its spender and then removed again by its creator.
+    fn rollback(&mut self, transactions: &[Transaction], undo: &BlockUndo) {
+        let mut spent = undo.spent.iter().rev();
+        for tx in transactions.iter().rev() {
+            for vout in 0..tx.vout.len() {
+                self.remove(&OutPoint::new(tx.id.clone(), vout));
+            }
+            if !tx.is_coinbase() {
+                for (outpoint, output) in spent.by_ref().take(tx.vin.len()) {
+                    self.insert(outpoint.clone(), output.clone());
+                }
+            }
+        }
+    }
+}
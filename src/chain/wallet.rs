@@ -0,0 +1,89 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use std::fmt;
+
+use super::hash::hash_pub_key;
+
+/// Wallet holding an Ed25519 key pair
+///
+/// The public key is kept as a hex string because that is what gets embedded in
+/// `TXInput::pub_key` and hashed into the wallet address.
+#[derive(Clone)]
+pub struct Wallet {
+    signing_key: SigningKey,
+    pub public_key: String,
+}
+
+impl Wallet {
+    /// Create a wallet with a fresh key pair from the operating system's CSPRNG
+    pub fn new() -> Self {
+        Self::from_signing_key(SigningKey::generate(&mut OsRng))
+    }
+
+    /// Restore a wallet from its 32-byte secret key
+    pub fn from_secret_bytes(secret: &[u8; 32]) -> Self {
+        Self::from_signing_key(SigningKey::from_bytes(secret))
+    }
+
+    fn from_signing_key(signing_key: SigningKey) -> Self {
+        let public_key = hex::encode(signing_key.verifying_key().as_bytes());
+        Wallet {
+            signing_key,
+            public_key,
+        }
+    }
+
+    /// Secret key bytes, for backing the wallet up
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        self.signing_key.to_bytes()
+    }
+
+    /// Get wallet address (public key hash)
+    pub fn get_address(&self) -> String {
+        hash_pub_key(&self.public_key)
+    }
+
+    /// Sign a message, returning the hex-encoded 64-byte signature
+    pub fn sign(&self, message: &[u8]) -> String {
+        hex::encode(self.signing_key.sign(message).to_bytes())
+    }
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Wallet {
+    // Never print the secret key
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallet")
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Check a hex-encoded signature over `message` against a hex-encoded public key
+///
+/// Both must be lowercase hex and the signature is verified strictly, so a valid
+/// signature cannot be re-encoded into a second valid one (which would change the
+/// txid). Malformed keys or signatures simply fail.
+pub fn verify_signature(pub_key: &str, message: &[u8], signature: &str) -> bool {
+    let Some(key) = decode_array::<32>(pub_key).and_then(|b| VerifyingKey::from_bytes(&b).ok())
+    else {
+        return false;
+    };
+    let Some(signature) = decode_array::<64>(signature).map(|b| Signature::from_bytes(&b)) else {
+        return false;
+    };
+    key.verify_strict(message, &signature).is_ok()
+}
+
+// Only the canonical encoding: `hex::decode` also takes uppercase digits
+fn decode_array<const N: usize>(hex_str: &str) -> Option<[u8; N]> {
+    if hex_str.bytes().any(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    hex::decode(hex_str).ok()?.try_into().ok()
+}
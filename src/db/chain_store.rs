@@ -0,0 +1,157 @@
+use deadpool_postgres::Transaction;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+use super::database::{column, Database};
+use super::error::DbError;
+use crate::chain::{hash_pub_key, Block, ChainEvent};
+
+/// Confirmed blocks and transactions written to normalized Postgres tables, so chain
+/// history can be queried with SQL
+///
+/// The tables and views come from migration 3 of [`crate::migrations`], which
+/// [`Database::new`] applies. One row per block, transaction, input and output;
+/// deleting a block cascades to everything in it. `chain_transfers` presents
+/// payments with the same fields as the blockchain103 `Transaction` (sender,
+/// receiver, amount, block_index, transaction_type), leaving out change paid back to
+/// the sender.
+///
+/// Only the active chain is stored: disconnected blocks are deleted again. Feed it
+/// from [`Blockchain::subscribe`](crate::chain::Blockchain::subscribe) with
+/// [`follow`](Self::follow), or write blocks directly.
+#[derive(Debug, Clone)]
+pub struct PgChainStore {
+    db: Database,
+}
+
+impl PgChainStore {
+    pub fn new(db: Database) -> Self {
+        PgChainStore { db }
+    }
+
+    /// Height of the highest stored block, if any
+    pub async fn tip_height(&self) -> Result<Option<u64>, DbError> {
+        let client = self.db.get_client().await?;
+        let row = client.query_one("SELECT MAX(height) FROM chain_blocks", &[]).await?;
+        column::<Option<i64>>(&row, 0)?
+            .map(|height| u64::try_from(height).map_err(|_| DbError::NegativeHeight(height)))
+            .transpose()
+    }
+
+    /// Write a block that was connected to the active chain, with all its
+    /// transactions, in one database transaction
+    pub async fn connect_block(&self, block: &Block) -> Result<(), DbError> {
+        let mut client = self.db.get_client().await?;
+        let db = client.transaction().await?;
+        insert_block(&db, block).await?;
+        db.commit().await?;
+        Ok(())
+    }
+
+    /// Remove a block that was disconnected from the active chain, along with its
+    /// transactions
+    pub async fn disconnect_block(&self, block: &Block) -> Result<(), DbError> {
+        let client = self.db.get_client().await?;
+        client.execute("DELETE FROM chain_blocks WHERE hash = $1", &[&block.hash]).await?;
+        Ok(())
+    }
+
+    /// Bring the store in line with `chain`, the active chain from genesis, e.g. after
+    /// it was offline; returns the number of blocks written
+    ///
+    /// Stored blocks from the first one `chain` no longer contains upwards are
+    /// deleted first. Either all of it happens or, on an error, none of it.
+    pub async fn catch_up(&self, chain: &[Block]) -> Result<usize, DbError> {
+        let mut client = self.db.get_client().await?;
+        let db = client.transaction().await?;
+        let mut start = 0;
+        for row in db.query("SELECT height, hash FROM chain_blocks ORDER BY height", &[]).await? {
+            let height: i64 = column(&row, 0)?;
+            let hash: String = column(&row, 1)?;
+            let index = usize::try_from(height).map_err(|_| DbError::NegativeHeight(height))?;
+            if chain.get(index).is_none_or(|block| block.hash != hash) {
+                db.execute("DELETE FROM chain_blocks WHERE height >= $1", &[&height]).await?;
+                break;
+            }
+            start = index + 1;
+        }
+
+        let missing = chain.get(start..).unwrap_or_default();
+        for block in missing {
+            insert_block(&db, block).await?;
+        }
+        db.commit().await?;
+        Ok(missing.len())
+    }
+
+    /// Mirror every change of the active chain into Postgres
+    ///
+    /// The task ends when the chain is dropped, on the first database error, or if it
+    /// falls so far behind that events were lost.
+    pub fn follow(self, mut events: broadcast::Receiver<ChainEvent>) -> JoinHandle<Result<(), DbError>> {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(ChainEvent::BlockConnected(block)) => self.connect_block(&block).await?,
+                    Ok(ChainEvent::BlockDisconnected(block)) => self.disconnect_block(&block).await?,
+                    Ok(ChainEvent::Reorg { .. }) => {}
+                    Err(RecvError::Closed) => return Ok(()),
+                    Err(RecvError::Lagged(missed)) => return Err(DbError::Lagged(missed)),
+                }
+            }
+        })
+    }
+}
+
+// Insert `block` and its transactions within `db`
+async fn insert_block(db: &Transaction<'_>, block: &Block) -> Result<(), DbError> {
+    let height = i64::try_from(block.id).map_err(|_| DbError::HeightOutOfRange(block.id))?;
+
+    db.execute(
+        "INSERT INTO chain_blocks (hash, height, previous_hash, merkle_root, timestamp, target, nonce)
+         VALUES ($1, $2, $3, $4, $5, $6, $7::text::numeric)",
+        &[
+            &block.hash,
+            &height,
+            &block.previous_hash,
+            &block.merkle_root,
+            &block.timestamp,
+            &block.target.to_string(),
+            &block.nonce.to_string(),
+        ],
+    )
+    .await?;
+
+    for (position, tx) in block.transactions.iter().enumerate() {
+        // A wallet signs all inputs of its own transactions, so the first input
+        // identifies the sender
+        let (sender, transaction_type) = match tx.vin.first() {
+            Some(input) if !tx.is_coinbase() => (Some(hash_pub_key(&input.pub_key)), "transfer"),
+            _ => (None, "coinbase"),
+        };
+        db.execute(
+            "INSERT INTO chain_transactions (txid, block_hash, block_index, position, sender, transaction_type, timestamp)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[&tx.id, &block.hash, &height, &(position as i32), &sender, &transaction_type, &tx.timestamp],
+        )
+        .await?;
+
+        if !tx.is_coinbase() {
+            for (vin, input) in tx.vin.iter().enumerate() {
+                db.execute(
+                    "INSERT INTO chain_inputs (txid, vin, prev_txid, prev_vout, address) VALUES ($1, $2, $3, $4, $5)",
+                    &[&tx.id, &(vin as i32), &input.txid, &(input.vout as i32), &hash_pub_key(&input.pub_key)],
+                )
+                .await?;
+            }
+        }
+        for (vout, output) in tx.vout.iter().enumerate() {
+            db.execute(
+                "INSERT INTO chain_outputs (txid, vout, receiver, amount) VALUES ($1, $2, $3, $4)",
+                &[&tx.id, &(vout as i32), &output.pub_key_hash, &output.value],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
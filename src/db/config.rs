@@ -0,0 +1,46 @@
+use super::error::DbError;
+
+/// Pool size when `DATABASE_POOL_SIZE` is not set; small enough for the Supabase free
+/// tier
+pub const DEFAULT_POOL_SIZE: usize = 10;
+
+/// Where the database is and how many connections to keep open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbConfig {
+    pub url: String,
+    pub pool_size: usize,
+}
+
+impl DbConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        DbConfig {
+            url: url.into(),
+            pool_size: DEFAULT_POOL_SIZE,
+        }
+    }
+
+    /// Read `DATABASE_URL` and, optionally, `DATABASE_POOL_SIZE`
+    ///
+    /// Binaries that keep them in `.env` should call `dotenv::dotenv()` first.
+    pub fn from_env() -> Result<Self, DbError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Like [`from_env`](Self::from_env), with variables from `lookup`
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, DbError> {
+        let url = lookup("DATABASE_URL").ok_or_else(|| DbError::Config("DATABASE_URL is not set".to_string()))?;
+        let pool_size = match lookup("DATABASE_POOL_SIZE") {
+            None => DEFAULT_POOL_SIZE,
+            Some(size) => match size.trim().parse() {
+                Ok(size) if size > 0 => size,
+                _ => {
+                    return Err(DbError::Config(format!(
+                        "DATABASE_POOL_SIZE must be a positive number, got `{}`",
+                        size
+                    )));
+                }
+            },
+        };
+        Ok(DbConfig { url, pool_size })
+    }
+}
@@ -0,0 +1,55 @@
+use deadpool_postgres::{Client, Pool};
+use tokio_postgres::Row;
+use tokio_postgres::types::FromSql;
+
+use super::config::DbConfig;
+use super::error::DbError;
+use crate::migrations;
+
+/// Connection pool shared by all queries
+///
+/// User queries live in `users.rs` and wallet queries in `wallets.rs`.
+#[derive(Debug, Clone)]
+pub struct Database {
+    pool: Pool,
+}
+
+impl Database {
+    /// Connect and apply any pending migrations, so a fresh database is ready to use
+    pub async fn new(config: &DbConfig) -> Result<Self, DbError> {
+        let db = Self::connect(config)?;
+        migrations::migrate_up(&db.pool, None).await?;
+        Ok(db)
+    }
+
+    /// [`new`](Self::new) with the configuration from the environment
+    pub async fn from_env() -> Result<Self, DbError> {
+        Self::new(&DbConfig::from_env()?).await
+    }
+
+    /// Create the pool without touching the schema, e.g. to run migrations by hand
+    pub fn connect(config: &DbConfig) -> Result<Self, DbError> {
+        let mut cfg = deadpool_postgres::Config::new();
+        cfg.url = Some(config.url.clone());
+        cfg.pool = Some(deadpool_postgres::PoolConfig::new(config.pool_size));
+        cfg.manager = Some(deadpool_postgres::ManagerConfig {
+            recycling_method: deadpool_postgres::RecyclingMethod::Fast,
+        });
+        let pool = cfg.create_pool(Some(deadpool_postgres::Runtime::Tokio1), tokio_postgres::NoTls)?;
+        Ok(Database { pool })
+    }
+
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Get a connection from the pool
+    pub async fn get_client(&self) -> Result<Client, DbError> {
+        Ok(self.pool.get().await?)
+    }
+}
+
+/// Read column `index` of `row`, reporting a type mismatch as [`DbError::Mapping`]
+pub(super) fn column<'a, T: FromSql<'a>>(row: &'a Row, index: usize) -> Result<T, DbError> {
+    row.try_get(index).map_err(DbError::Mapping)
+}
@@ -0,0 +1,86 @@
+use deadpool_postgres::{CreatePoolError, PoolError};
+use thiserror::Error;
+use tokio_postgres::error::SqlState;
+use uuid::Uuid;
+
+use crate::migrations::MigrationError;
+
+/// Why a database operation failed
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("invalid database configuration: {0}")]
+    Config(String),
+
+    #[error("could not create connection pool: {0}")]
+    CreatePool(#[from] CreatePoolError),
+
+    #[error("could not get a connection: {0}")]
+    Pool(#[from] PoolError),
+
+    #[error("query failed: {0}")]
+    Query(tokio_postgres::Error),
+
+    #[error("could not read row: {0}")]
+    Mapping(tokio_postgres::Error),
+
+    #[error(transparent)]
+    Migration(#[from] MigrationError),
+
+    #[error("email {0} is already registered")]
+    DuplicateEmail(String),
+
+    #[error("CNIC {0} is already registered")]
+    DuplicateCnic(String),
+
+    #[error("wallet {0} already belongs to a user")]
+    DuplicateWalletId(String),
+
+    #[error("wallet {0} already exists")]
+    DuplicateWallet(String),
+
+    #[error("no user with id {0}")]
+    UserNotFound(Uuid),
+
+    #[error("user {0} is already verified")]
+    AlreadyVerified(Uuid),
+
+    #[error("no wallet {0}")]
+    WalletNotFound(String),
+
+    #[error("page {page} of {per_page} users is out of range")]
+    InvalidPage { page: i64, per_page: i64 },
+
+    #[error("block height {0} does not fit in the chain tables")]
+    HeightOutOfRange(u64),
+
+    #[error("chain tables hold a block at negative height {0}")]
+    NegativeHeight(i64),
+
+    #[error("missed {0} chain events; the chain tables are out of date")]
+    Lagged(u64),
+}
+
+impl From<tokio_postgres::Error> for DbError {
+    /// Unique violations are recognised by the constraint Postgres generated for the
+    /// column; anything else is a plain query error
+    fn from(error: tokio_postgres::Error) -> Self {
+        let duplicate = error
+            .as_db_error()
+            .filter(|db_error| *db_error.code() == SqlState::UNIQUE_VIOLATION)
+            .and_then(|db_error| Some((db_error.constraint()?, db_error.detail().unwrap_or_default())));
+        // The detail reads "Key (email)=(a@example.com) already exists."
+        let value = |detail: &str| {
+            detail
+                .split_once(")=(")
+                .and_then(|(_, rest)| rest.rsplit_once(") already exists"))
+                .map_or_else(String::new, |(value, _)| value.to_string())
+        };
+        match duplicate {
+            Some(("users_email_key", detail)) => DbError::DuplicateEmail(value(detail)),
+            Some(("users_cnic_key", detail)) => DbError::DuplicateCnic(value(detail)),
+            Some(("users_wallet_id_key", detail)) => DbError::DuplicateWalletId(value(detail)),
+            Some(("wallets_pkey", detail)) => DbError::DuplicateWallet(value(detail)),
+            _ => DbError::Query(error),
+        }
+    }
+}
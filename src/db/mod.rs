@@ -0,0 +1,30 @@
+//! Postgres access for users, wallets and the confirmed chain
+//!
+//! [`Database`] wraps a connection pool configured from `DATABASE_URL` and
+//! `DATABASE_POOL_SIZE`, applies the [migrations](crate::migrations) when it is
+//! created, and returns a [`DbError`] from every query. [`PgChainStore`] mirrors a
+//! chain into the same database.
+//!
+//! ```no_run
+//! use rust101::db::Database;
+//!
+//! # async fn example() -> Result<(), rust101::db::DbError> {
+//! let db = Database::from_env().await?;
+//! let user = db.create_user("a@example.com", "A", "4210112345671", "wallet_a", "pubkey", "secret").await?;
+//! db.create_wallet(&user.wallet_id).await?;
+//! db.update_wallet_balance(&user.wallet_id, 12).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod chain_store;
+mod config;
+mod database;
+mod error;
+mod users;
+mod wallets;
+
+pub use chain_store::PgChainStore;
+pub use config::{DbConfig, DEFAULT_POOL_SIZE};
+pub use database::Database;
+pub use error::DbError;
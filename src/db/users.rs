@@ -0,0 +1,156 @@
+use tokio_postgres::Row;
+use tokio_postgres::types::ToSql;
+use uuid::Uuid;
+
+use super::database::{column, Database};
+use super::error::DbError;
+use crate::models::User;
+
+/// Columns selected for every [`User`], in the order [`user_from_row`] reads them
+const USER_COLUMNS: &str =
+    "id, email, full_name, cnic, wallet_id, public_key, encrypted_private_key, is_verified, created_at, updated_at";
+
+fn user_from_row(row: &Row) -> Result<User, DbError> {
+    Ok(User {
+        id: column(row, 0)?,
+        email: column(row, 1)?,
+        full_name: column(row, 2)?,
+        cnic: column(row, 3)?,
+        wallet_id: column(row, 4)?,
+        public_key: column(row, 5)?,
+        encrypted_private_key: column(row, 6)?,
+        is_verified: column(row, 7)?,
+        created_at: column(row, 8)?,
+        updated_at: column(row, 9)?,
+    })
+}
+
+/// User queries
+///
+/// Soft-deleted users are invisible to all of them, but keep their email, CNIC and
+/// wallet_id reserved.
+impl Database {
+    pub async fn create_user(
+        &self,
+        email: &str,
+        full_name: &str,
+        cnic: &str,
+        wallet_id: &str,
+        public_key: &str,
+        encrypted_private_key: &str,
+    ) -> Result<User, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_one(
+                &format!(
+                    "INSERT INTO users (email, full_name, cnic, wallet_id, public_key, encrypted_private_key)
+                     VALUES ($1, $2, $3, $4, $5, $6)
+                     RETURNING {}",
+                    USER_COLUMNS
+                ),
+                &[&email, &full_name, &cnic, &wallet_id, &public_key, &encrypted_private_key],
+            )
+            .await?;
+        user_from_row(&row)
+    }
+
+    pub async fn get_user(&self, id: Uuid) -> Result<User, DbError> {
+        self.find_user_by("id", &id).await?.ok_or(DbError::UserNotFound(id))
+    }
+
+    pub async fn find_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        self.find_user_by("email", &email).await
+    }
+
+    pub async fn find_user_by_wallet_id(&self, wallet_id: &str) -> Result<Option<User>, DbError> {
+        self.find_user_by("wallet_id", &wallet_id).await
+    }
+
+    pub async fn find_user_by_cnic(&self, cnic: &str) -> Result<Option<User>, DbError> {
+        self.find_user_by("cnic", &cnic).await
+    }
+
+    /// `field` is always one of ours, never user input
+    async fn find_user_by(&self, field: &str, value: &(dyn ToSql + Sync)) -> Result<Option<User>, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_opt(
+                &format!("SELECT {} FROM users WHERE {} = $1 AND deleted_at IS NULL", USER_COLUMNS, field),
+                &[value],
+            )
+            .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    /// Users oldest first; `page` starts at 0 and `per_page` must be positive
+    pub async fn list_users(&self, page: i64, per_page: i64) -> Result<Vec<User>, DbError> {
+        let offset = page
+            .checked_mul(per_page)
+            .filter(|_| page >= 0 && per_page > 0)
+            .ok_or(DbError::InvalidPage { page, per_page })?;
+        let client = self.get_client().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT {} FROM users WHERE deleted_at IS NULL ORDER BY created_at, id LIMIT $1 OFFSET $2",
+                    USER_COLUMNS
+                ),
+                &[&per_page, &offset],
+            )
+            .await?;
+        rows.iter().map(user_from_row).collect()
+    }
+
+    pub async fn count_users(&self) -> Result<i64, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_one("SELECT COUNT(*) FROM users WHERE deleted_at IS NULL", &[])
+            .await?;
+        column(&row, 0)
+    }
+
+    pub async fn update_email(&self, id: Uuid, email: &str) -> Result<User, DbError> {
+        self.update_user("email = $2", "", &[&id, &email]).await?.ok_or(DbError::UserNotFound(id))
+    }
+
+    pub async fn update_full_name(&self, id: Uuid, full_name: &str) -> Result<User, DbError> {
+        self.update_user("full_name = $2", "", &[&id, &full_name]).await?.ok_or(DbError::UserNotFound(id))
+    }
+
+    /// Verification only goes one way: from unverified to verified
+    pub async fn mark_verified(&self, id: Uuid) -> Result<User, DbError> {
+        match self.update_user("is_verified = TRUE", "AND NOT is_verified", &[&id]).await? {
+            Some(user) => Ok(user),
+            None => {
+                self.get_user(id).await?;
+                Err(DbError::AlreadyVerified(id))
+            }
+        }
+    }
+
+    pub async fn soft_delete_user(&self, id: Uuid) -> Result<(), DbError> {
+        self.update_user("deleted_at = now()", "", &[&id]).await?.ok_or(DbError::UserNotFound(id))?;
+        Ok(())
+    }
+
+    /// Update a live user and bump `updated_at`; `$1` is the id, and `set` and
+    /// `filter` are never user input
+    async fn update_user(
+        &self,
+        set: &str,
+        filter: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Option<User>, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_opt(
+                &format!(
+                    "UPDATE users SET {}, updated_at = now() WHERE id = $1 AND deleted_at IS NULL {} RETURNING {}",
+                    set, filter, USER_COLUMNS
+                ),
+                params,
+            )
+            .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+}
@@ -0,0 +1,59 @@
+use tokio_postgres::Row;
+
+use super::database::{column, Database};
+use super::error::DbError;
+use crate::models::Wallet;
+
+const WALLET_COLUMNS: &str = "wallet_id, balance, created_at, updated_at";
+
+fn wallet_from_row(row: &Row) -> Result<Wallet, DbError> {
+    Ok(Wallet {
+        wallet_id: column(row, 0)?,
+        balance: column(row, 1)?,
+        created_at: column(row, 2)?,
+        updated_at: column(row, 3)?,
+    })
+}
+
+/// Wallet queries; every wallet belongs to the user with the same `wallet_id`
+impl Database {
+    /// Open an empty wallet for a user
+    pub async fn create_wallet(&self, wallet_id: &str) -> Result<Wallet, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_one(
+                &format!("INSERT INTO wallets (wallet_id) VALUES ($1) RETURNING {}", WALLET_COLUMNS),
+                &[&wallet_id],
+            )
+            .await?;
+        wallet_from_row(&row)
+    }
+
+    pub async fn get_wallet(&self, wallet_id: &str) -> Result<Option<Wallet>, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_opt(
+                &format!("SELECT {} FROM wallets WHERE wallet_id = $1", WALLET_COLUMNS),
+                &[&wallet_id],
+            )
+            .await?;
+        row.as_ref().map(wallet_from_row).transpose()
+    }
+
+    pub async fn update_wallet_balance(&self, wallet_id: &str, new_balance: i64) -> Result<Wallet, DbError> {
+        let client = self.get_client().await?;
+        let row = client
+            .query_opt(
+                &format!(
+                    "UPDATE wallets SET balance = $1::int8, updated_at = now() WHERE wallet_id = $2 RETURNING {}",
+                    WALLET_COLUMNS
+                ),
+                &[&new_balance, &wallet_id],
+            )
+            .await?;
+        row.as_ref()
+            .map(wallet_from_row)
+            .transpose()?
+            .ok_or_else(|| DbError::WalletNotFound(wallet_id.to_string()))
+    }
+}
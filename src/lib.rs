@@ -1,16 +1,55 @@
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpResponse, HttpServer};
+use serde::Serialize;
 use std::net::TcpListener;
 
+pub mod chain;
+pub mod db;
+pub mod migrations;
 pub mod models;
+pub mod p2p;
+
+use p2p::{Node, P2pError};
 
 async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
 
-pub fn run(listener: TcpListener) -> Result<Server, std::io::Error> {
-    let server = HttpServer::new(|| App::new().route("/health_check", web::get().to(health_check)))
-        .listen(listener)?
-        .run();
+/// JSON for what the node answered, or 503 once it has shut down
+fn node_response<T: Serialize>(answer: Result<T, P2pError>) -> HttpResponse {
+    match answer {
+        Ok(value) => HttpResponse::Ok().json(value),
+        Err(error) => HttpResponse::ServiceUnavailable().body(error.to_string()),
+    }
+}
+
+async fn p2p_peers(node: web::Data<Node>) -> HttpResponse {
+    node_response(node.peers().await)
+}
+
+async fn p2p_bans(node: web::Data<Node>) -> HttpResponse {
+    node_response(node.bans().await)
+}
+
+async fn p2p_addresses(node: web::Data<Node>) -> HttpResponse {
+    node_response(node.addresses().await)
+}
+
+/// Serve on `listener`; with a `node`, its peers, bans and address book are
+/// readable as JSON under `/p2p/peers`, `/p2p/bans` and `/p2p/addresses`
+pub fn run(listener: TcpListener, node: Option<Node>) -> Result<Server, std::io::Error> {
+    let server = HttpServer::new(move || {
+        let app = App::new().route("/health_check", web::get().to(health_check));
+        match node.clone() {
+            Some(node) => app
+                .app_data(web::Data::new(node))
+                .route("/p2p/peers", web::get().to(p2p_peers))
+                .route("/p2p/bans", web::get().to(p2p_bans))
+                .route("/p2p/addresses", web::get().to(p2p_addresses)),
+            None => app,
+        }
+    })
+    .listen(listener)?
+    .run();
     Ok(server)
-}
\ No newline at end of file
+}
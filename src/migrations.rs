@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use deadpool_postgres::{Pool, PoolError};
+use thiserror::Error;
+use tokio_postgres::Client;
+
+/// One versioned schema change, with the SQL to apply and to revert it
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $file, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $file, ".down.sql")),
+        }
+    };
+}
+
+/// Every migration, oldest first; the SQL lives in `migrations/` and is embedded at
+/// compile time
+///
+/// Needs PostgreSQL 13 or newer, for the built-in `gen_random_uuid()`.
+pub const MIGRATIONS: &[Migration] = &[
+    migration!(1, "create_users", "0001_create_users"),
+    migration!(2, "create_wallets", "0002_create_wallets"),
+    migration!(3, "create_chain_tables", "0003_create_chain_tables"),
+    migration!(4, "add_users_deleted_at", "0004_add_users_deleted_at"),
+    migration!(5, "wallet_balance_bigint", "0005_wallet_balance_bigint"),
+];
+
+/// Records which migrations have been applied
+const STATUS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    version    BIGINT PRIMARY KEY,
+    name       TEXT NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Advisory lock held while migrating, so two processes starting at once do not
+/// both apply the same migration
+const LOCK_KEY: i64 = 0x0072_7573_7431_3031;
+
+/// Why the schema could not be migrated
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("could not get a connection: {0}")]
+    Pool(#[from] PoolError),
+
+    #[error("query failed: {0}")]
+    Query(#[from] tokio_postgres::Error),
+
+    #[error("migration {version} ({name}) failed: {source}")]
+    Failed {
+        version: i64,
+        name: &'static str,
+        source: tokio_postgres::Error,
+    },
+
+    #[error("database has migration {0} applied, which this build does not know")]
+    UnknownVersion(i64),
+
+    #[error("no migration with version {0}")]
+    NoSuchVersion(i64),
+}
+
+/// Where one migration stands in a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    /// `None` while pending
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+/// Version of the newest migration in this build
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Apply pending migrations up to and including `target`, or all of them;
+/// returns the versions applied
+///
+/// Each migration runs in its own transaction together with its row in
+/// `schema_migrations`.
+pub async fn migrate_up(pool: &Pool, target: Option<i64>) -> Result<Vec<i64>, MigrationError> {
+    let target = check_target(target.unwrap_or_else(latest_version))?;
+    let mut client = pool.get().await?;
+    locked(&mut client, async |client| {
+        let applied = applied_versions(client).await?;
+        let mut done = Vec::new();
+        for migration in MIGRATIONS {
+            if migration.version > target || applied.contains(&migration.version) {
+                continue;
+            }
+            let db = client.transaction().await?;
+            db.batch_execute(migration.up).await.map_err(|source| failed(migration, source))?;
+            db.execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await?;
+            db.commit().await?;
+            done.push(migration.version);
+        }
+        Ok(done)
+    })
+    .await
+}
+
+/// Revert applied migrations newer than `target`, newest first; returns the versions
+/// reverted. A target of 0 reverts everything.
+pub async fn migrate_down(pool: &Pool, target: i64) -> Result<Vec<i64>, MigrationError> {
+    let target = check_target(target)?;
+    let mut client = pool.get().await?;
+    locked(&mut client, async |client| {
+        let applied = applied_versions(client).await?;
+        let mut done = Vec::new();
+        for migration in MIGRATIONS.iter().rev() {
+            if migration.version <= target || !applied.contains(&migration.version) {
+                continue;
+            }
+            let db = client.transaction().await?;
+            db.batch_execute(migration.down).await.map_err(|source| failed(migration, source))?;
+            db.execute("DELETE FROM schema_migrations WHERE version = $1", &[&migration.version])
+                .await?;
+            db.commit().await?;
+            done.push(migration.version);
+        }
+        Ok(done)
+    })
+    .await
+}
+
+/// Every known migration with when it was applied, plus any applied migration this
+/// build does not know
+pub async fn status(pool: &Pool) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let client = pool.get().await?;
+    client.batch_execute(STATUS_TABLE).await?;
+    let rows = client
+        .query("SELECT version, name, applied_at FROM schema_migrations ORDER BY version", &[])
+        .await?;
+    let mut statuses: Vec<MigrationStatus> = MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            name: migration.name.to_string(),
+            applied_at: None,
+        })
+        .collect();
+    for row in rows {
+        let version: i64 = row.get(0);
+        match statuses.iter_mut().find(|status| status.version == version) {
+            Some(status) => status.applied_at = Some(row.get(2)),
+            None => statuses.push(MigrationStatus {
+                version,
+                name: row.get(1),
+                applied_at: Some(row.get(2)),
+            }),
+        }
+    }
+    statuses.sort_by_key(|status| status.version);
+    Ok(statuses)
+}
+
+fn check_target(target: i64) -> Result<i64, MigrationError> {
+    if target == 0 || MIGRATIONS.iter().any(|migration| migration.version == target) {
+        Ok(target)
+    } else {
+        Err(MigrationError::NoSuchVersion(target))
+    }
+}
+
+fn failed(migration: &Migration, source: tokio_postgres::Error) -> MigrationError {
+    MigrationError::Failed {
+        version: migration.version,
+        name: migration.name,
+        source,
+    }
+}
+
+/// Versions recorded in `schema_migrations`, refusing to touch a database migrated by
+/// a newer build
+async fn applied_versions(client: &Client) -> Result<Vec<i64>, MigrationError> {
+    client.batch_execute(STATUS_TABLE).await?;
+    let applied: Vec<i64> = client
+        .query("SELECT version FROM schema_migrations ORDER BY version", &[])
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    if let Some(&unknown) = applied.iter().find(|&&version| check_target(version).is_err()) {
+        return Err(MigrationError::UnknownVersion(unknown));
+    }
+    Ok(applied)
+}
+
+/// Run `migrate` while holding the migration lock on this connection
+async fn locked<T>(
+    client: &mut Client,
+    migrate: impl AsyncFnOnce(&mut Client) -> Result<T, MigrationError>,
+) -> Result<T, MigrationError> {
+    client.execute("SELECT pg_advisory_lock($1)", &[&LOCK_KEY]).await?;
+    let result = migrate(client).await;
+    client.execute("SELECT pg_advisory_unlock($1)", &[&LOCK_KEY]).await?;
+    result
+}
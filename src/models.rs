@@ -15,3 +15,11 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wallet {
+    pub wallet_id: String,
+    pub balance: i64, // Whole coins, like amounts on the chain
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
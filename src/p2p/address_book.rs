@@ -0,0 +1,240 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use super::error::P2pError;
+
+/// Addresses kept at most; gossip about more is ignored
+pub const MAX_KNOWN_ADDRESSES: usize = 4096;
+
+/// Failed attempts in a row after which an address learned from gossip is forgotten
+pub const MAX_FAILURES: u32 = 10;
+
+/// Longest wait between attempts at an address that keeps failing
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+/// How the node learned about an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressSource {
+    Seed,   // From the node's configuration
+    Manual, // Passed to `Node::connect`
+    Gossip, // From a peer's `addr` or `version`
+}
+
+/// An address the node may connect to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownAddress {
+    pub addr: SocketAddr,
+    pub source: AddressSource,
+    pub last_attempt: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>, // Last completed handshake
+    pub failures: u32,                       // Failed attempts since the last success
+}
+
+impl KnownAddress {
+    /// When the address may be tried again: straight away until it fails, then
+    /// after a wait that doubles with each failure
+    pub fn next_attempt(&self) -> Option<DateTime<Utc>> {
+        let last_attempt = self.last_attempt.filter(|_| self.failures > 0)?;
+        let backoff = 1i64 << self.failures.min(12);
+        Some(last_attempt + Duration::seconds(backoff.min(MAX_BACKOFF_SECS)))
+    }
+}
+
+/// An IP address the node refuses to talk to until `until`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    pub ip: IpAddr,
+    pub until: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// What the book writes to disk
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    addresses: Vec<KnownAddress>,
+    bans: Vec<Ban>,
+}
+
+/// Known peer addresses and banned IPs, optionally kept in a JSON file
+///
+/// Every method that depends on the time takes `now`, so callers decide which
+/// clock counts. Changes are only written by [`save`](Self::save).
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    path: Option<PathBuf>,
+    addresses: BTreeMap<SocketAddr, KnownAddress>,
+    bans: BTreeMap<IpAddr, Ban>,
+    dirty: bool, // Changed since the last save
+}
+
+impl AddressBook {
+    /// A book that is never written anywhere
+    pub fn in_memory() -> Self {
+        AddressBook::default()
+    }
+
+    /// Load the book saved at `path`, or start an empty one if there is none yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, P2pError> {
+        let path = path.as_ref().to_path_buf();
+        let snapshot: Snapshot = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Snapshot::default(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(AddressBook {
+            path: Some(path),
+            addresses: snapshot.addresses.into_iter().map(|known| (known.addr, known)).collect(),
+            bans: snapshot.bans.into_iter().map(|ban| (ban.ip, ban)).collect(),
+            dirty: false,
+        })
+    }
+
+    /// Write the book to its file if anything changed since it was loaded or last saved
+    ///
+    /// The file is replaced in one rename, so a crash leaves either the old or the
+    /// new book.
+    pub fn save(&mut self) -> Result<(), P2pError> {
+        let Some(path) = self.path.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        let snapshot = Snapshot {
+            addresses: self.addresses.values().cloned().collect(),
+            bans: self.bans.values().cloned().collect(),
+        };
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(&snapshot)?)?;
+        std::fs::rename(&temporary, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Remember `addr`; returns whether it was new
+    ///
+    /// Gossip never fills the book past [`MAX_KNOWN_ADDRESSES`].
+    pub fn add(&mut self, addr: SocketAddr, source: AddressSource) -> bool {
+        if self.addresses.contains_key(&addr)
+            || (source == AddressSource::Gossip && self.addresses.len() >= MAX_KNOWN_ADDRESSES)
+        {
+            return false;
+        }
+        let known = KnownAddress {
+            addr,
+            source,
+            last_attempt: None,
+            last_success: None,
+            failures: 0,
+        };
+        self.addresses.insert(addr, known);
+        self.dirty = true;
+        true
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<&KnownAddress> {
+        self.addresses.get(addr)
+    }
+
+    /// Every known address, in address order
+    pub fn addresses(&self) -> impl Iterator<Item = &KnownAddress> {
+        self.addresses.values()
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) -> Option<KnownAddress> {
+        let removed = self.addresses.remove(addr);
+        self.dirty |= removed.is_some();
+        removed
+    }
+
+    /// Note that the node started connecting to `addr`
+    pub fn attempted(&mut self, addr: &SocketAddr, now: DateTime<Utc>) {
+        if let Some(known) = self.addresses.get_mut(addr) {
+            known.last_attempt = Some(now);
+            self.dirty = true;
+        }
+    }
+
+    /// Note a completed handshake with `addr`
+    pub fn succeeded(&mut self, addr: &SocketAddr, now: DateTime<Utc>) {
+        if let Some(known) = self.addresses.get_mut(addr) {
+            known.last_success = Some(now);
+            known.failures = 0;
+            self.dirty = true;
+        }
+    }
+
+    /// Note that connecting to `addr` failed; gossiped addresses that fail
+    /// [`MAX_FAILURES`] times in a row are forgotten
+    pub fn failed(&mut self, addr: &SocketAddr) {
+        let Some(known) = self.addresses.get_mut(addr) else {
+            return;
+        };
+        known.failures += 1;
+        if known.source == AddressSource::Gossip && known.failures >= MAX_FAILURES {
+            self.addresses.remove(addr);
+        }
+        self.dirty = true;
+    }
+
+    /// Refuse `ip` until `until`, replacing any shorter ban
+    pub fn ban(&mut self, ip: IpAddr, until: DateTime<Utc>, reason: &str) {
+        if self.bans.get(&ip).is_some_and(|ban| ban.until >= until) {
+            return;
+        }
+        let ban = Ban {
+            ip,
+            until,
+            reason: reason.to_string(),
+        };
+        self.bans.insert(ip, ban);
+        self.dirty = true;
+    }
+
+    /// Lift the ban on `ip`; returns whether there was one
+    pub fn unban(&mut self, ip: &IpAddr) -> bool {
+        let lifted = self.bans.remove(ip).is_some();
+        self.dirty |= lifted;
+        lifted
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr, now: DateTime<Utc>) -> bool {
+        self.bans.get(ip).is_some_and(|ban| ban.until > now)
+    }
+
+    /// Bans still in force, in IP order
+    pub fn bans(&self, now: DateTime<Utc>) -> Vec<Ban> {
+        self.bans.values().filter(|ban| ban.until > now).cloned().collect()
+    }
+
+    /// Forget bans that ran out
+    pub fn expire_bans(&mut self, now: DateTime<Utc>) {
+        let before = self.bans.len();
+        self.bans.retain(|_, ban| ban.until > now);
+        self.dirty |= self.bans.len() != before;
+    }
+
+    /// Addresses worth trying now, fewest recent failures first
+    pub fn dial_candidates(&self, now: DateTime<Utc>) -> Vec<SocketAddr> {
+        let mut candidates: Vec<&KnownAddress> = self
+            .addresses
+            .values()
+            .filter(|known| !self.is_banned(&known.addr.ip(), now))
+            .filter(|known| known.next_attempt().is_none_or(|next| next <= now))
+            .collect();
+        candidates.sort_by_key(|known| known.failures);
+        candidates.into_iter().map(|known| known.addr).collect()
+    }
+
+    /// Up to `max` addresses to share with a peer, the most recently reached first
+    pub fn sample(&self, max: usize, now: DateTime<Utc>) -> Vec<SocketAddr> {
+        let mut shareable: Vec<&KnownAddress> = self
+            .addresses
+            .values()
+            .filter(|known| !self.is_banned(&known.addr.ip(), now))
+            .collect();
+        shareable.sort_by_key(|known| std::cmp::Reverse(known.last_success));
+        shareable.into_iter().take(max).map(|known| known.addr).collect()
+    }
+}
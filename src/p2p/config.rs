@@ -0,0 +1,92 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::error::P2pError;
+
+/// Outbound connections kept open when `P2P_MAX_OUTBOUND` is not set
+pub const DEFAULT_MAX_OUTBOUND: usize = 8;
+
+/// Inbound connections accepted when `P2P_MAX_INBOUND` is not set
+pub const DEFAULT_MAX_INBOUND: usize = 32;
+
+/// How long a misbehaving peer stays banned when `P2P_BAN_SECONDS` is not set
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How a node presents itself, whom it connects to and how patient it is with peers
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    pub user_agent: String,
+    pub handshake_timeout: Duration,
+    pub ping_interval: Duration,    // A peer still owing the previous pong is dropped
+    pub block_timeout: Duration,    // A block request unanswered this long is retried elsewhere
+    pub headers_timeout: Duration,  // A peer this slow to send requested headers is dropped
+    pub tx_timeout: Duration,       // A transaction request unanswered this long goes elsewhere
+    pub trickle_interval: Duration, // How often queued transaction announcements go out
+    pub max_outbound: usize,        // Outbound connections the node dials until it has
+    pub max_inbound: usize,         // Inbound connections beyond this are refused
+    pub seeds: Vec<SocketAddr>,     // Added to the address book on start
+    pub ban_duration: Duration,
+    pub address_book: Option<PathBuf>, // JSON file of known addresses and bans; None keeps them in memory
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            user_agent: format!("/rust101:{}/", env!("CARGO_PKG_VERSION")),
+            handshake_timeout: Duration::from_secs(10),
+            ping_interval: Duration::from_secs(60),
+            block_timeout: Duration::from_secs(10),
+            headers_timeout: Duration::from_secs(30),
+            tx_timeout: Duration::from_secs(5),
+            trickle_interval: Duration::from_millis(200),
+            max_outbound: DEFAULT_MAX_OUTBOUND,
+            max_inbound: DEFAULT_MAX_INBOUND,
+            seeds: Vec::new(),
+            ban_duration: DEFAULT_BAN_DURATION,
+            address_book: None,
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Defaults overridden by `P2P_SEEDS` (comma-separated `host:port`),
+    /// `P2P_MAX_OUTBOUND`, `P2P_MAX_INBOUND`, `P2P_BAN_SECONDS` and
+    /// `P2P_ADDRESS_BOOK`, all optional
+    pub fn from_env() -> Result<Self, P2pError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Like [`from_env`](Self::from_env), with variables from `lookup`
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, P2pError> {
+        let mut config = NodeConfig::default();
+        if let Some(seeds) = lookup("P2P_SEEDS") {
+            for seed in seeds.split(',').map(str::trim).filter(|seed| !seed.is_empty()) {
+                let addrs = seed
+                    .to_socket_addrs()
+                    .map_err(|error| P2pError::Config(format!("P2P_SEEDS entry `{}`: {}", seed, error)))?;
+                config.seeds.extend(addrs);
+            }
+        }
+        if let Some(max) = lookup("P2P_MAX_OUTBOUND") {
+            config.max_outbound = parse_count("P2P_MAX_OUTBOUND", &max)?;
+        }
+        if let Some(max) = lookup("P2P_MAX_INBOUND") {
+            config.max_inbound = parse_count("P2P_MAX_INBOUND", &max)?;
+        }
+        if let Some(seconds) = lookup("P2P_BAN_SECONDS") {
+            config.ban_duration = Duration::from_secs(parse_count("P2P_BAN_SECONDS", &seconds)? as u64);
+        }
+        if let Some(path) = lookup("P2P_ADDRESS_BOOK") {
+            config.address_book = Some(PathBuf::from(path));
+        }
+        Ok(config)
+    }
+}
+
+fn parse_count(name: &str, value: &str) -> Result<usize, P2pError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| P2pError::Config(format!("{} must be a whole number, got `{}`", name, value)))
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use super::error::P2pError;
+use super::frame::{read_message, write_message};
+use super::message::Message;
+
+/// Messages buffered per direction; a peer that lets its queue fill up is dropped
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+/// A link to one peer as a pair of message channels, whatever carries the bytes
+///
+/// If the peer breaks the framing rules (for example with an oversized frame) the
+/// receiver yields that error and then closes. Dropping `receiver` and every clone
+/// of `sender` closes the link.
+pub struct Connection {
+    pub addr: SocketAddr,
+    pub sender: mpsc::Sender<Message>,
+    pub receiver: mpsc::Receiver<Result<Message, P2pError>>,
+}
+
+impl Connection {
+    /// Carry framed messages over `stream` with one reader and one writer task
+    pub fn tcp(stream: TcpStream) -> std::io::Result<Self> {
+        let addr = stream.peer_addr()?;
+        stream.set_nodelay(true)?;
+        let (mut read_half, mut write_half) = stream.into_split();
+        let (inbound, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let (sender, mut outbound) = mpsc::channel::<Message>(CHANNEL_CAPACITY);
+
+        // The reader stops after the first malformed frame
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = read_message(&mut read_half) => {
+                        let failed = message.is_err();
+                        if inbound.send(message).await.is_err() || failed {
+                            break;
+                        }
+                    }
+                    _ = inbound.closed() => break,
+                }
+            }
+        });
+        // Dropping the write half when the channel closes shuts the socket down
+        tokio::spawn(async move {
+            while let Some(message) = outbound.recv().await {
+                if write_message(&mut write_half, &message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Connection { addr, sender, receiver })
+    }
+
+    /// Both ends of an in-memory link: what one sends, the other receives
+    ///
+    /// The first end sees its peer at `b` and the second at `a`.
+    pub fn pair(a: SocketAddr, b: SocketAddr) -> (Connection, Connection) {
+        let (a_sender, a_outbound) = mpsc::channel(CHANNEL_CAPACITY);
+        let (b_sender, b_outbound) = mpsc::channel(CHANNEL_CAPACITY);
+        let (a_inbound, a_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let (b_inbound, b_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(forward(a_outbound, b_inbound));
+        tokio::spawn(forward(b_outbound, a_inbound));
+        let first = Connection {
+            addr: b,
+            sender: a_sender,
+            receiver: a_receiver,
+        };
+        let second = Connection {
+            addr: a,
+            sender: b_sender,
+            receiver: b_receiver,
+        };
+        (first, second)
+    }
+}
+
+/// Pass messages from one end of a pair to the other until either goes away
+async fn forward(mut from: mpsc::Receiver<Message>, to: mpsc::Sender<Result<Message, P2pError>>) {
+    loop {
+        tokio::select! {
+            message = from.recv() => {
+                let Some(message) = message else { break };
+                if to.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+            _ = to.closed() => break,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::net::IpAddr;
+use thiserror::Error;
+
+use crate::chain::{ChainError, DecodeError, MempoolError, TxValidationError};
+
+use super::message::MIN_PROTOCOL_VERSION;
+
+/// Why talking to a peer, or asking the node for something, failed
+#[derive(Debug, Error)]
+pub enum P2pError {
+    #[error("connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("connection closed")]
+    Closed,
+
+    #[error("frame does not start with the network magic")]
+    BadMagic,
+
+    #[error("frame of {0} bytes exceeds the size limit")]
+    FrameTooLarge(usize),
+
+    #[error("frame checksum does not match its payload")]
+    BadChecksum,
+
+    #[error("malformed message: {0}")]
+    Decode(#[from] DecodeError),
+
+    #[error("peer speaks protocol version {0}, at least {MIN_PROTOCOL_VERSION} is required")]
+    UnsupportedVersion(u32),
+
+    #[error("peer is on another chain, with genesis block {0}")]
+    WrongNetwork(String),
+
+    #[error("connected to ourselves")]
+    SelfConnection,
+
+    #[error("expected {expected}, got {got}")]
+    UnexpectedMessage { expected: &'static str, got: &'static str },
+
+    #[error("handshake timed out")]
+    HandshakeTimeout,
+
+    #[error("peer did not answer a ping")]
+    PingTimeout,
+
+    #[error("peer is not reading its messages")]
+    SlowPeer,
+
+    #[error("peer sent an invalid header: {0}")]
+    InvalidHeader(ChainError),
+
+    #[error("peer sent headers that do not connect to any we know")]
+    UnconnectedHeaders,
+
+    #[error("peer sent {0} headers in one message")]
+    TooManyHeaders(usize),
+
+    #[error("peer did not answer a request for headers")]
+    Stalled,
+
+    #[error("peer sent an invalid block: {0}")]
+    InvalidBlock(ChainError),
+
+    #[error("peer sent an invalid transaction: {0}")]
+    InvalidTransaction(MempoolError),
+
+    #[error("peer sent {0} inventory items in one message")]
+    TooManyInventory(usize),
+
+    #[error("peer sent {0} addresses in one message")]
+    TooManyAddrs(usize),
+
+    #[error("peer sent {0} transactions or announcements over its allowance")]
+    TxFlood(usize),
+
+    #[error("{0} is banned")]
+    Banned(IpAddr),
+
+    #[error("no room for another peer")]
+    TooManyPeers,
+
+    #[error("invalid network configuration: {0}")]
+    Config(String),
+
+    #[error("address book is corrupt: {0}")]
+    AddressBook(#[from] serde_json::Error),
+
+    #[error("node has shut down")]
+    NodeStopped,
+
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+
+    #[error(transparent)]
+    Mempool(#[from] MempoolError),
+}
+
+/// Misbehavior score at which a peer is disconnected and its address banned
+pub const BAN_SCORE: u32 = 100;
+
+impl P2pError {
+    /// Points a peer earns for causing this error
+    ///
+    /// Breaking the protocol on purpose costs [`BAN_SCORE`] at once; mistakes an
+    /// honest peer could make now and then cost less, and failures that are nobody's
+    /// fault (timeouts, closed links, blocks whose parent we lack) cost nothing.
+    pub fn misbehavior(&self) -> u32 {
+        match self {
+            P2pError::FrameTooLarge(_)
+            | P2pError::InvalidHeader(_)
+            | P2pError::TooManyHeaders(_)
+            | P2pError::InvalidBlock(_) => BAN_SCORE,
+            P2pError::BadChecksum | P2pError::Decode(_) => 50,
+            P2pError::UnconnectedHeaders
+            | P2pError::UnexpectedMessage { .. }
+            | P2pError::TooManyInventory(_)
+            | P2pError::TooManyAddrs(_)
+            | P2pError::TxFlood(_) => 20,
+            P2pError::InvalidTransaction(MempoolError::Coinbase) => 10,
+            P2pError::InvalidTransaction(MempoolError::Invalid(error)) => match error {
+                // The parent may just not have reached us yet
+                TxValidationError::UnknownOutpoint(_) => 0,
+                _ => 10,
+            },
+            _ => 0,
+        }
+    }
+}
+
+/// Whether a block rejected with `error` says something bad about whoever sent it
+///
+/// A missing parent, a clock running ahead of ours or our own storage failing do not.
+pub(crate) fn block_is_invalid(error: &ChainError) -> bool {
+    !matches!(
+        error,
+        ChainError::UnknownParent(_) | ChainError::TimestampTooFarAhead(_) | ChainError::Storage(_)
+    )
+}
@@ -0,0 +1,63 @@
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::chain::{Decode, Encode};
+
+use super::error::P2pError;
+use super::message::Message;
+
+/// First bytes of every frame, so a stray connection from something else is noticed
+pub const MAGIC: [u8; 4] = *b"r101";
+
+/// Largest payload a frame may carry; comfortably above the biggest valid block
+pub const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+/// magic 4 | payload length u32 | checksum 4 | payload
+const FRAME_HEADER_SIZE: usize = 12;
+
+// First four bytes of the payload's SHA-256
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    Sha256::digest(payload)[..4].try_into().expect("digest is 32 bytes")
+}
+
+/// Frame one message: magic | payload length u32 | checksum | payload, where the
+/// payload is the message's canonical encoding
+pub fn encode_frame(message: &Message) -> Vec<u8> {
+    let payload = message.encode();
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+    frame.extend(MAGIC);
+    frame.extend((payload.len() as u32).to_le_bytes());
+    frame.extend(checksum(&payload));
+    frame.extend(payload);
+    frame
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Message) -> Result<(), P2pError> {
+    writer.write_all(&encode_frame(message)).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read the next framed message; a connection closed between frames is
+/// [`P2pError::Closed`]
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message, P2pError> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut header).await.map_err(|error| match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => P2pError::Closed,
+        _ => P2pError::Io(error),
+    })?;
+    if header[..4] != MAGIC {
+        return Err(P2pError::BadMagic);
+    }
+    let len = u32::from_le_bytes(header[4..8].try_into().expect("four bytes")) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(P2pError::FrameTooLarge(len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    if header[8..] != checksum(&payload) {
+        return Err(P2pError::BadChecksum);
+    }
+    Ok(Message::decode(&payload)?)
+}
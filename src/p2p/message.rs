@@ -0,0 +1,288 @@
+use serde::Serialize;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use crate::chain::{write_compact_size, write_str, Block, BlockHeader, Decode, DecodeError, Encode, Reader, Transaction};
+
+/// Protocol version this node speaks
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version a peer may speak
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Most headers sent in reply to one `getheaders`
+pub const MAX_HEADERS: usize = 2000;
+
+/// Most items in one `inv`, `getdata` or `notfound`
+pub const MAX_INV_ITEMS: usize = 50_000;
+
+/// Most addresses sent in one `addr`
+pub const MAX_ADDRS: usize = 1000;
+
+/// What an inventory item refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvKind {
+    Block,
+    Tx,
+}
+
+/// A block or transaction announced or requested by hash
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Inventory {
+    pub kind: InvKind,
+    pub hash: String,
+}
+
+impl Inventory {
+    pub fn block(hash: impl Into<String>) -> Self {
+        Inventory {
+            kind: InvKind::Block,
+            hash: hash.into(),
+        }
+    }
+
+    pub fn tx(txid: impl Into<String>) -> Self {
+        Inventory {
+            kind: InvKind::Tx,
+            hash: txid.into(),
+        }
+    }
+}
+
+/// What each side says about itself when a connection opens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Version {
+    pub version: u32,
+    pub genesis_hash: String,      // Nodes on different chains refuse each other
+    pub best_height: u64,
+    pub nonce: u64,                // Random per node, to notice connecting to ourselves
+    pub listen_port: u16,          // 0 when not accepting connections
+    pub user_agent: String,
+    pub timestamp: i64,
+}
+
+/// Everything peers say to each other
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Opens the handshake
+    Version(Version),
+    /// Accepts the peer's `Version`
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    /// Blocks or transactions the sender has
+    Inv(Vec<Inventory>),
+    /// Ask for the full blocks or transactions
+    GetData(Vec<Inventory>),
+    /// Requested items the sender does not have
+    NotFound(Vec<Inventory>),
+    Block(Block),
+    Tx(Transaction),
+    /// Ask for headers after the first locator hash the receiver knows
+    GetHeaders { locator: Vec<String>, stop: Option<String> },
+    Headers(Vec<BlockHeader>),
+    /// Ask for addresses of other nodes
+    GetAddr,
+    /// Addresses of nodes the sender knows
+    Addr(Vec<SocketAddr>),
+}
+
+impl Message {
+    /// Name of the message type, for errors and logs
+    pub fn command(&self) -> &'static str {
+        match self {
+            Message::Version(_) => "version",
+            Message::Verack => "verack",
+            Message::Ping(_) => "ping",
+            Message::Pong(_) => "pong",
+            Message::Inv(_) => "inv",
+            Message::GetData(_) => "getdata",
+            Message::NotFound(_) => "notfound",
+            Message::Block(_) => "block",
+            Message::Tx(_) => "tx",
+            Message::GetHeaders { .. } => "getheaders",
+            Message::Headers(_) => "headers",
+            Message::GetAddr => "getaddr",
+            Message::Addr(_) => "addr",
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Message::Version(_) => 0,
+            Message::Verack => 1,
+            Message::Ping(_) => 2,
+            Message::Pong(_) => 3,
+            Message::Inv(_) => 4,
+            Message::GetData(_) => 5,
+            Message::NotFound(_) => 6,
+            Message::Block(_) => 7,
+            Message::Tx(_) => 8,
+            Message::GetHeaders { .. } => 9,
+            Message::Headers(_) => 10,
+            Message::GetAddr => 11,
+            Message::Addr(_) => 12,
+        }
+    }
+}
+
+// Hashes travel as their 32 raw bytes; anything else encodes as zeros and will not
+// match any block or transaction
+fn write_hash(out: &mut Vec<u8>, hash: &str) {
+    let mut bytes = [0u8; 32];
+    if hex::decode_to_slice(hash, &mut bytes).is_err() {
+        bytes = [0u8; 32];
+    }
+    out.extend(bytes);
+}
+
+fn read_hash(reader: &mut Reader<'_>) -> Result<String, DecodeError> {
+    Ok(hex::encode(reader.array::<32>()?))
+}
+
+// Addresses travel as 16 bytes of IPv6 (IPv4 mapped into it) and the port
+fn write_addr(out: &mut Vec<u8>, addr: &SocketAddr) {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    };
+    out.extend(ip.octets());
+    out.extend(addr.port().to_le_bytes());
+}
+
+fn read_addr(reader: &mut Reader<'_>) -> Result<SocketAddr, DecodeError> {
+    let ip = Ipv6Addr::from(reader.array::<16>()?);
+    let port = u16::from_le_bytes(reader.array()?);
+    let ip = ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4);
+    Ok(SocketAddr::new(ip, port))
+}
+
+fn write_inventory(out: &mut Vec<u8>, items: &[Inventory]) {
+    write_compact_size(out, items.len() as u64);
+    for item in items {
+        out.push(match item.kind {
+            InvKind::Block => 0,
+            InvKind::Tx => 1,
+        });
+        write_hash(out, &item.hash);
+    }
+}
+
+fn read_inventory(reader: &mut Reader<'_>) -> Result<Vec<Inventory>, DecodeError> {
+    (0..reader.count()?)
+        .map(|_| {
+            let kind = match reader.u8()? {
+                0 => InvKind::Block,
+                1 => InvKind::Tx,
+                flag => return Err(DecodeError::InvalidFlag(flag)),
+            };
+            Ok(Inventory {
+                kind,
+                hash: read_hash(reader)?,
+            })
+        })
+        .collect()
+}
+
+/// Message type byte | fields
+///
+/// Inventory is a count of (kind byte, hash) pairs; a locator is a count of hashes
+/// followed by 0, or 1 and the stop hash; addresses are a count of (IPv6, port).
+impl Encode for Message {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.push(self.tag());
+        match self {
+            Message::Version(version) => {
+                out.extend(version.version.to_le_bytes());
+                write_hash(out, &version.genesis_hash);
+                out.extend(version.best_height.to_le_bytes());
+                out.extend(version.nonce.to_le_bytes());
+                out.extend(version.listen_port.to_le_bytes());
+                write_str(out, &version.user_agent);
+                out.extend(version.timestamp.to_le_bytes());
+            }
+            Message::Verack => {}
+            Message::Ping(nonce) | Message::Pong(nonce) => out.extend(nonce.to_le_bytes()),
+            Message::Inv(items) | Message::GetData(items) | Message::NotFound(items) => {
+                write_inventory(out, items)
+            }
+            Message::Block(block) => block.encode_to(out),
+            Message::Tx(tx) => tx.encode_to(out),
+            Message::GetHeaders { locator, stop } => {
+                write_compact_size(out, locator.len() as u64);
+                for hash in locator {
+                    write_hash(out, hash);
+                }
+                match stop {
+                    None => out.push(0),
+                    Some(hash) => {
+                        out.push(1);
+                        write_hash(out, hash);
+                    }
+                }
+            }
+            Message::Headers(headers) => {
+                write_compact_size(out, headers.len() as u64);
+                for header in headers {
+                    header.encode_to(out);
+                }
+            }
+            Message::GetAddr => {}
+            Message::Addr(addrs) => {
+                write_compact_size(out, addrs.len() as u64);
+                for addr in addrs {
+                    write_addr(out, addr);
+                }
+            }
+        }
+    }
+}
+
+impl Decode for Message {
+    fn decode_from(reader: &mut Reader<'_>) -> Result<Self, DecodeError> {
+        Ok(match reader.u8()? {
+            0 => {
+                let version = reader.u32()?;
+                let genesis_hash = read_hash(reader)?;
+                let best_height = reader.u64()?;
+                let nonce = reader.u64()?;
+                let listen_port = u16::from_le_bytes(reader.array()?);
+                Message::Version(Version {
+                    version,
+                    genesis_hash,
+                    best_height,
+                    nonce,
+                    listen_port,
+                    user_agent: reader.string()?,
+                    timestamp: reader.i64()?,
+                })
+            }
+            1 => Message::Verack,
+            2 => Message::Ping(reader.u64()?),
+            3 => Message::Pong(reader.u64()?),
+            4 => Message::Inv(read_inventory(reader)?),
+            5 => Message::GetData(read_inventory(reader)?),
+            6 => Message::NotFound(read_inventory(reader)?),
+            7 => Message::Block(Block::decode_from(reader)?),
+            8 => Message::Tx(Transaction::decode_from(reader)?),
+            9 => {
+                let locator = (0..reader.count()?)
+                    .map(|_| read_hash(reader))
+                    .collect::<Result<_, _>>()?;
+                let stop = match reader.u8()? {
+                    0 => None,
+                    1 => Some(read_hash(reader)?),
+                    flag => return Err(DecodeError::InvalidFlag(flag)),
+                };
+                Message::GetHeaders { locator, stop }
+            }
+            10 => Message::Headers(
+                (0..reader.count()?)
+                    .map(|_| BlockHeader::decode_from(reader))
+                    .collect::<Result<_, _>>()?,
+            ),
+            11 => Message::GetAddr,
+            12 => Message::Addr((0..reader.count()?).map(|_| read_addr(reader)).collect::<Result<_, _>>()?),
+            tag => return Err(DecodeError::InvalidFlag(tag)),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! Peer-to-peer networking for the [chain](crate::chain)
+//!
+//! Nodes talk over TCP in length-prefixed, checksummed [frames](encode_frame)
+//! carrying a [`Message`]. A connection opens with a `version`/`verack` handshake
+//! in both directions; peers on a different genesis block, or speaking a protocol
+//! older than [`MIN_PROTOCOL_VERSION`], are refused. After that blocks and
+//! transactions are announced with `inv` and fetched with `getdata`, each from one
+//! peer at a time; nothing is announced to a peer known to have it, and transaction
+//! announcements go out in rate-limited batches and are taken in at a limited rate
+//! per peer. A node that is behind syncs header-first: it validates its peers'
+//! `headers` and then downloads the blocks in parallel from all of them, reporting
+//! its [`SyncProgress`].
+//!
+//! Each node keeps an [`AddressBook`] of peers to dial and IPs it has banned.
+//! Peers that send invalid blocks, headers or oversized frames are scored and
+//! banned (see [`P2pError::misbehavior`]); [`NodeConfig::from_env`] reads the
+//! seeds and connection limits.
+//!
+//! A node runs on a [`Transport`]: TCP, or a [`SimNetwork`] that hosts any number
+//! of nodes in one process with set latency, message loss and partitions. Under a
+//! seed and tokio's paused time a run repeats, which makes forks, reorgs and
+//! healing partitions testable.
+//!
+//! ```no_run
+//! use rust101::chain::{Block, Blockchain, ChainParams};
+//! use rust101::p2p::{Node, NodeConfig};
+//!
+//! # async fn example() -> Result<(), rust101::p2p::P2pError> {
+//! let params = ChainParams::with_difficulty(3);
+//! let genesis = Block::genesis(&params, "shared-genesis-address", 1_700_000_000);
+//! let node = Node::start(Blockchain::with_genesis(params, genesis)?, NodeConfig::from_env()?)?;
+//! node.listen("127.0.0.1:8333").await?;
+//! node.connect("127.0.0.1:8334").await?;
+//! node.mine_block("my-address").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod address_book;
+mod config;
+mod connection;
+mod error;
+mod frame;
+mod message;
+mod node;
+mod peer;
+mod relay;
+mod sim;
+mod sync;
+mod transport;
+
+pub use address_book::{AddressBook, AddressSource, Ban, KnownAddress, MAX_FAILURES, MAX_KNOWN_ADDRESSES};
+pub use config::{NodeConfig, DEFAULT_BAN_DURATION, DEFAULT_MAX_INBOUND, DEFAULT_MAX_OUTBOUND};
+pub use connection::{Connection, CHANNEL_CAPACITY};
+pub use error::{P2pError, BAN_SCORE};
+pub use frame::{encode_frame, read_message, write_message, MAGIC, MAX_FRAME_SIZE};
+pub use message::{
+    InvKind, Inventory, Message, Version, MAX_ADDRS, MAX_HEADERS, MAX_INV_ITEMS, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+pub use node::{Node, NodeEvent};
+pub use peer::{PeerId, PeerInfo};
+pub use relay::{MAX_INV_PER_TRICKLE, MAX_TXS_IN_FLIGHT, MAX_TXS_WANTED, TX_BURST, TX_RATE};
+pub use sim::{LinkConditions, SimNetwork, SimStats};
+pub use sync::{SyncProgress, DOWNLOAD_WINDOW, MAX_BLOCKS_IN_FLIGHT};
+pub use transport::Transport;
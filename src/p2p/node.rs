@@ -0,0 +1,871 @@
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::{BTreeMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::ToSocketAddrs;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+
+use crate::chain::{
+    AddOutcome, Block, BlockStatus, Blockchain, ChainError, ChainEvent, Mempool, MempoolError, Miner, MiningHandle,
+    Transaction, TxValidationError,
+};
+
+use super::address_book::{AddressBook, AddressSource, Ban, KnownAddress};
+use super::config::NodeConfig;
+use super::connection::{Connection, CHANNEL_CAPACITY};
+use super::error::{block_is_invalid, P2pError, BAN_SCORE};
+use super::message::{InvKind, Inventory, Message, Version, MAX_ADDRS, MAX_HEADERS, MAX_INV_ITEMS, PROTOCOL_VERSION};
+use super::peer::{run_session, PeerEvent, PeerId, PeerInfo};
+use super::relay::TxRelay;
+use super::sync::{BlockSync, SyncProgress, SYNC_INTERVAL};
+use super::transport::Transport;
+
+/// Events buffered per [`Node::subscribe`] receiver before it starts missing them
+const EVENT_CAPACITY: usize = 256;
+
+/// How often the node tops up its outbound connections and saves its address book
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What happened on the network, for whoever called [`Node::subscribe`]
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    PeerConnected(PeerInfo),
+    PeerDisconnected { id: PeerId, addr: SocketAddr, reason: String },
+    /// An IP is refused until the ban runs out, after misbehaving or [`Node::ban`]
+    PeerBanned(Ban),
+    BlockRejected { peer: PeerId, hash: String, error: ChainError },
+    TransactionRejected { peer: PeerId, txid: String, error: MempoolError },
+    /// Sent whenever the progress changes, checked a few times a second
+    SyncProgress(SyncProgress),
+}
+
+type Query = Box<dyn FnOnce(&mut NodeState) + Send>;
+
+enum Command {
+    AddConnection {
+        connection: Connection,
+        inbound: bool,
+        reply: oneshot::Sender<Result<PeerId, P2pError>>,
+    },
+    /// A connection the node opened to an address from its book
+    Dialed {
+        addr: SocketAddr,
+        result: Result<Connection, P2pError>,
+    },
+    Query(Query),
+}
+
+/// A peer-to-peer node: a chain and mempool kept in step with peers
+///
+/// One task owns the chain, the mempool and the peer table and handles every
+/// message; each connection gets a reader, a writer and a session task (see
+/// [`Connection`]). `Node` is a cheap handle to that task, which stops once every
+/// handle is dropped.
+///
+/// Blocks and transactions are announced with `inv`, only to peers not known to
+/// have them. New blocks are announced at once; transactions are batched and go
+/// out every [`NodeConfig::trickle_interval`]. An announced transaction is fetched
+/// with `getdata` from one announcer at a time; a block we do not know, or a peer
+/// with a longer chain, makes the node ask for headers first and download the
+/// blocks they name from every peer that has them (see [`SyncProgress`]).
+///
+/// The node keeps [`NodeConfig::max_outbound`] connections open by dialing
+/// addresses from its [`AddressBook`]: the configured seeds, whatever it was told
+/// to [`connect`](Node::connect) to and what peers share with `getaddr`/`addr`.
+/// Peers that break the rules collect misbehavior points (see
+/// [`P2pError::misbehavior`]) and are banned once they reach [`BAN_SCORE`].
+///
+/// Timeouts, bans and dial backoff all follow tokio's clock, so a node on a
+/// [`SimNetwork`](super::SimNetwork) under paused time behaves as it would over
+/// hours of real time.
+#[derive(Clone)]
+pub struct Node {
+    commands: mpsc::Sender<Command>,
+    events: broadcast::Sender<NodeEvent>,
+    transport: Transport,
+}
+
+impl Node {
+    /// Start a node around `chain` on TCP, loading the address book the config names
+    pub fn start(chain: Blockchain, config: NodeConfig) -> Result<Node, P2pError> {
+        Node::start_on(chain, config, Transport::Tcp)
+    }
+
+    /// Start a node that listens and dials on `transport`
+    pub fn start_on(chain: Blockchain, config: NodeConfig, transport: Transport) -> Result<Node, P2pError> {
+        let mut book = match &config.address_book {
+            Some(path) => AddressBook::open(path)?,
+            None => AddressBook::in_memory(),
+        };
+        for seed in &config.seeds {
+            book.add(*seed, AddressSource::Seed);
+        }
+        let (commands, command_receiver) = mpsc::channel(64);
+        let (peer_events, peer_event_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let mut rng = transport.rng();
+        let state = NodeState {
+            sync: BlockSync::new(&chain, config.block_timeout, config.headers_timeout),
+            relay: TxRelay::new(config.tx_timeout),
+            progress: None,
+            chain_events: chain.subscribe(),
+            chain,
+            mempool: Mempool::default(),
+            config,
+            clock: Clock::new(),
+            nonce: rng.r#gen(),
+            rng,
+            transport: transport.clone(),
+            listen_port: 0,
+            peers: BTreeMap::new(),
+            next_peer_id: 0,
+            book,
+            dialing: HashSet::new(),
+            commands: commands.downgrade(),
+            peer_events,
+            node_events: events.clone(),
+        };
+        tokio::spawn(state.run(command_receiver, peer_event_receiver));
+        Ok(Node {
+            commands,
+            events,
+            transport,
+        })
+    }
+
+    /// Receive every future [`NodeEvent`]
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Accept peers on `addr`; returns the address actually bound, e.g. for port 0
+    pub async fn listen(&self, addr: impl ToSocketAddrs) -> Result<SocketAddr, P2pError> {
+        let mut listener = self.transport.bind(addr).await?;
+        let local = listener.local_addr()?;
+        self.query(move |state| state.listen_port = local.port()).await?;
+
+        // A weak handle, so listening alone does not keep the node running
+        let commands = self.commands.downgrade();
+        let events = self.events.clone();
+        let transport = self.transport.clone();
+        tokio::spawn(async move {
+            while let Some(connection) = listener.accept().await {
+                let Some(commands) = commands.upgrade() else {
+                    break;
+                };
+                let node = Node {
+                    commands,
+                    events: events.clone(),
+                    transport: transport.clone(),
+                };
+                // Refused connections are simply dropped
+                let _ = node.add_connection(connection, true).await;
+            }
+        });
+        Ok(local)
+    }
+
+    /// Open an outbound connection and remember the address; the handshake continues
+    /// in the background
+    ///
+    /// Manual connections come on top of [`NodeConfig::max_outbound`].
+    pub async fn connect(&self, addr: impl ToSocketAddrs) -> Result<PeerId, P2pError> {
+        let connection = self.transport.dial(addr).await?;
+        let addr = connection.addr;
+        // Only once the peer is in the table, or maintenance could dial it again
+        let id = self.add_connection(connection, false).await?;
+        self.query(move |state| state.book.add(addr, AddressSource::Manual)).await?;
+        Ok(id)
+    }
+
+    /// Take over an established link to a peer
+    ///
+    /// Fails with [`P2pError::Banned`] for banned addresses and with
+    /// [`P2pError::TooManyPeers`] for inbound links beyond
+    /// [`NodeConfig::max_inbound`]; the link is closed then.
+    pub async fn add_connection(&self, connection: Connection, inbound: bool) -> Result<PeerId, P2pError> {
+        let (reply, id) = oneshot::channel();
+        let command = Command::AddConnection {
+            connection,
+            inbound,
+            reply,
+        };
+        self.commands.send(command).await.map_err(|_| P2pError::NodeStopped)?;
+        id.await.map_err(|_| P2pError::NodeStopped)?
+    }
+
+    async fn query<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut NodeState) -> T + Send + 'static,
+    ) -> Result<T, P2pError> {
+        let (reply, result) = oneshot::channel();
+        let query: Query = Box::new(move |state| {
+            let _ = reply.send(f(state));
+        });
+        self.commands
+            .send(Command::Query(query))
+            .await
+            .map_err(|_| P2pError::NodeStopped)?;
+        result.await.map_err(|_| P2pError::NodeStopped)
+    }
+
+    /// Run `f` against the node's chain
+    pub async fn chain<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Blockchain) -> T + Send + 'static,
+    ) -> Result<T, P2pError> {
+        self.query(move |state| f(&state.chain)).await
+    }
+
+    /// Run `f` against the node's mempool
+    pub async fn mempool<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Mempool) -> T + Send + 'static,
+    ) -> Result<T, P2pError> {
+        self.query(move |state| f(&state.mempool)).await
+    }
+
+    /// Peers that completed the handshake
+    pub async fn peers(&self) -> Result<Vec<PeerInfo>, P2pError> {
+        self.query(|state| state.peer_infos()).await
+    }
+
+    /// Addresses in the node's book
+    pub async fn addresses(&self) -> Result<Vec<KnownAddress>, P2pError> {
+        self.query(|state| state.book.addresses().cloned().collect()).await
+    }
+
+    /// Bans in force
+    pub async fn bans(&self) -> Result<Vec<Ban>, P2pError> {
+        self.query(|state| state.book.bans(state.clock.now())).await
+    }
+
+    /// Refuse `ip` for `duration`, dropping any peers connected from it
+    pub async fn ban(&self, ip: IpAddr, duration: Duration, reason: &str) -> Result<(), P2pError> {
+        let reason = reason.to_string();
+        self.query(move |state| state.ban(ip, duration, reason)).await
+    }
+
+    /// Lift the ban on `ip`; returns whether there was one
+    pub async fn unban(&self, ip: IpAddr) -> Result<bool, P2pError> {
+        self.query(move |state| state.book.unban(&ip)).await
+    }
+
+    /// How far the node is from the best chain its peers announced
+    pub async fn sync_progress(&self) -> Result<SyncProgress, P2pError> {
+        self.query(|state| state.sync.progress(&state.chain)).await
+    }
+
+    /// Admit a transaction to the mempool and announce it to every peer
+    pub async fn submit_transaction(&self, tx: Transaction) -> Result<AddOutcome, P2pError> {
+        Ok(self.query(move |state| state.accept_transaction(None, tx)).await??)
+    }
+
+    /// Hand a block to the chain and announce it to every peer if it is connected
+    pub async fn submit_block(&self, block: Block) -> Result<BlockStatus, P2pError> {
+        Ok(self.query(move |state| state.accept_block(None, block)).await??)
+    }
+
+    /// Mine the best-paying mempool transactions into a block on the current tip and
+    /// submit it
+    ///
+    /// Returns `None` if another block extended the tip first.
+    pub async fn mine_block(&self, miner_address: &str) -> Result<Option<Block>, P2pError> {
+        let miner_address = miner_address.to_string();
+        let (template, tip_events) = self
+            .query(move |state| {
+                let transactions = state.mempool.block_template(state.chain.params.max_block_size);
+                (state.chain.prepare_block(transactions, &miner_address), state.chain.subscribe())
+            })
+            .await?;
+        let handle = MiningHandle::new();
+        let watcher = handle.cancel_on_new_tip(tip_events);
+        let (block, report) = Miner::default().mine_async(template?, handle).await;
+        watcher.abort();
+        if !report.found() {
+            return Ok(None);
+        }
+        match self.submit_block(block.clone()).await? {
+            BlockStatus::Connected | BlockStatus::Reorganized { .. } => Ok(Some(block)),
+            BlockStatus::AlreadyKnown | BlockStatus::SideBranch => Ok(None),
+        }
+    }
+}
+
+/// Wall-clock time that advances with tokio's clock, so that it jumps along with
+/// paused time
+struct Clock {
+    started: DateTime<Utc>,
+    instant: Instant,
+}
+
+impl Clock {
+    fn new() -> Self {
+        Clock {
+            started: Utc::now(),
+            instant: Instant::now(),
+        }
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.started + chrono::Duration::from_std(self.instant.elapsed()).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+/// A connection from its start; `version` is set once the handshake completes
+struct Peer {
+    addr: SocketAddr,
+    inbound: bool,
+    sender: mpsc::Sender<Message>,
+    version: Option<Version>,
+    session: AbortHandle,
+    pending_ping: Option<u64>,
+    score: u32, // Misbehavior points
+}
+
+/// Everything the node task owns
+struct NodeState {
+    chain: Blockchain,
+    mempool: Mempool,
+    chain_events: broadcast::Receiver<ChainEvent>,
+    sync: BlockSync,
+    relay: TxRelay,
+    progress: Option<SyncProgress>, // Last reported
+    config: NodeConfig,
+    clock: Clock,
+    rng: StdRng, // Nonces
+    transport: Transport,
+    nonce: u64,
+    listen_port: u16,
+    peers: BTreeMap<PeerId, Peer>, // Ordered, so simulated runs repeat
+    next_peer_id: PeerId,
+    book: AddressBook,
+    dialing: HashSet<SocketAddr>,
+    commands: mpsc::WeakSender<Command>, // For dial tasks to hand back their connection
+    peer_events: mpsc::Sender<PeerEvent>,
+    node_events: broadcast::Sender<NodeEvent>,
+}
+
+impl NodeState {
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>, mut peer_events: mpsc::Receiver<PeerEvent>) {
+        let mut ping = tokio::time::interval(self.config.ping_interval);
+        ping.tick().await;
+        let mut sync = tokio::time::interval(SYNC_INTERVAL);
+        let mut maintenance = tokio::time::interval(MAINTENANCE_INTERVAL);
+        let mut trickle = tokio::time::interval(self.config.trickle_interval);
+        loop {
+            // In a fixed order, so simulated runs repeat; timers go first since they
+            // are ready once per period and cannot starve the channels
+            tokio::select! {
+                biased;
+                _ = ping.tick() => self.ping_peers(),
+                _ = sync.tick() => self.check_sync(),
+                _ = maintenance.tick() => self.maintain_peers(),
+                _ = trickle.tick() => self.trickle(),
+                command = commands.recv() => match command {
+                    Some(Command::AddConnection { connection, inbound, reply }) => {
+                        let _ = reply.send(self.add_connection(connection, inbound));
+                    }
+                    Some(Command::Dialed { addr, result }) => self.dialed(addr, result),
+                    Some(Command::Query(query)) => query(&mut self),
+                    None => break,
+                },
+                Some(event) = peer_events.recv() => self.handle_peer_event(event),
+            }
+        }
+        for peer in self.peers.values() {
+            peer.session.abort();
+        }
+        // Nowhere left to report a failure; the next start reads the last good save
+        let _ = self.book.save();
+    }
+
+    fn emit(&self, event: NodeEvent) {
+        // Nobody listening is fine
+        let _ = self.node_events.send(event);
+    }
+
+    fn version(&self) -> Version {
+        Version {
+            version: PROTOCOL_VERSION,
+            genesis_hash: self.chain.blocks[0].hash.clone(),
+            best_height: self.chain.get_latest_block().id,
+            nonce: self.nonce,
+            listen_port: self.listen_port,
+            user_agent: self.config.user_agent.clone(),
+            timestamp: self.clock.now().timestamp(),
+        }
+    }
+
+    fn peer_infos(&self) -> Vec<PeerInfo> {
+        let mut peers: Vec<PeerInfo> = self
+            .peers
+            .iter()
+            .filter_map(|(&id, peer)| {
+                Some(PeerInfo {
+                    id,
+                    addr: peer.addr,
+                    inbound: peer.inbound,
+                    version: peer.version.clone()?,
+                    score: peer.score,
+                })
+            })
+            .collect();
+        peers.sort_by_key(|peer| peer.id);
+        peers
+    }
+
+    fn add_connection(&mut self, connection: Connection, inbound: bool) -> Result<PeerId, P2pError> {
+        let addr = connection.addr;
+        if self.book.is_banned(&addr.ip(), self.clock.now()) {
+            return Err(P2pError::Banned(addr.ip()));
+        }
+        if inbound && self.peers.values().filter(|peer| peer.inbound).count() >= self.config.max_inbound {
+            return Err(P2pError::TooManyPeers);
+        }
+        let id = self.next_peer_id;
+        self.next_peer_id += 1;
+        let sender = connection.sender.clone();
+        let session = tokio::spawn(run_session(
+            id,
+            connection,
+            self.version(),
+            self.config.handshake_timeout,
+            self.peer_events.clone(),
+        ));
+        let peer = Peer {
+            addr,
+            inbound,
+            sender,
+            version: None,
+            session: session.abort_handle(),
+            pending_ping: None,
+            score: 0,
+        };
+        self.peers.insert(id, peer);
+        Ok(id)
+    }
+
+    /// Drop a peer, charging it for `reason` and banning it if that takes its score
+    /// to [`BAN_SCORE`]
+    fn disconnect(&mut self, id: PeerId, reason: P2pError) {
+        let Some(peer) = self.peers.remove(&id) else {
+            return;
+        };
+        peer.session.abort();
+        self.sync.remove_peer(id);
+        self.relay.remove_peer(id);
+        if !peer.inbound && peer.version.is_none() {
+            match reason {
+                // Not worth trying again
+                P2pError::SelfConnection | P2pError::WrongNetwork(_) => {
+                    self.book.remove(&peer.addr);
+                }
+                _ => self.book.failed(&peer.addr),
+            }
+        }
+        let score = peer.score + reason.misbehavior();
+        let reason = reason.to_string();
+        self.emit(NodeEvent::PeerDisconnected {
+            id,
+            addr: peer.addr,
+            reason: reason.clone(),
+        });
+        if score >= BAN_SCORE {
+            self.ban(peer.addr.ip(), self.config.ban_duration, reason);
+        }
+    }
+
+    /// Charge a peer for `error`, dropping it once it reaches [`BAN_SCORE`]
+    fn misbehaving(&mut self, id: PeerId, error: P2pError) {
+        let Some(peer) = self.peers.get_mut(&id) else {
+            return;
+        };
+        if peer.score + error.misbehavior() >= BAN_SCORE {
+            self.disconnect(id, error);
+        } else {
+            peer.score += error.misbehavior();
+        }
+    }
+
+    /// Ban `ip` and drop every peer connected from it
+    fn ban(&mut self, ip: IpAddr, duration: Duration, reason: String) {
+        let until = self.clock.now() + chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+        self.book.ban(ip, until, &reason);
+        let banned: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.addr.ip() == ip)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in banned {
+            self.disconnect(id, P2pError::Banned(ip));
+        }
+        self.emit(NodeEvent::PeerBanned(Ban { ip, until, reason }));
+    }
+
+    /// Dial addresses from the book until there are enough outbound peers, and save
+    /// the book
+    fn maintain_peers(&mut self) {
+        let now = self.clock.now();
+        self.book.expire_bans(now);
+        let outbound = self.peers.values().filter(|peer| !peer.inbound).count() + self.dialing.len();
+        let wanted = self.config.max_outbound.saturating_sub(outbound);
+        let candidates: Vec<SocketAddr> = self
+            .book
+            .dial_candidates(now)
+            .into_iter()
+            .filter(|addr| !self.dialing.contains(addr) && !self.connected_to(addr))
+            .take(wanted)
+            .collect();
+        for addr in candidates {
+            self.book.attempted(&addr, now);
+            self.dialing.insert(addr);
+            let commands = self.commands.clone();
+            let transport = self.transport.clone();
+            let timeout = self.config.handshake_timeout;
+            tokio::spawn(async move {
+                let result = match tokio::time::timeout(timeout, transport.dial(addr)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(P2pError::HandshakeTimeout),
+                };
+                if let Some(commands) = commands.upgrade() {
+                    let _ = commands.send(Command::Dialed { addr, result }).await;
+                }
+            });
+        }
+        // A failed save is retried on the next tick
+        let _ = self.book.save();
+    }
+
+    /// Whether a peer is connected from `addr`, or listens there
+    fn connected_to(&self, addr: &SocketAddr) -> bool {
+        self.peers.values().any(|peer| {
+            let listen_port = peer.version.as_ref().map(|version| version.listen_port);
+            peer.addr == *addr || (peer.addr.ip() == addr.ip() && listen_port == Some(addr.port()))
+        })
+    }
+
+    fn dialed(&mut self, addr: SocketAddr, result: Result<Connection, P2pError>) {
+        self.dialing.remove(&addr);
+        match result {
+            // Only refused if the address was banned while we dialed
+            Ok(connection) => {
+                let _ = self.add_connection(connection, false);
+            }
+            Err(_) => self.book.failed(&addr),
+        }
+    }
+
+    /// Queue `message` for a peer, dropping peers that stopped reading
+    fn send(&mut self, id: PeerId, message: Message) {
+        let Some(peer) = self.peers.get(&id) else {
+            return;
+        };
+        if peer.sender.try_send(message).is_err() {
+            self.disconnect(id, P2pError::SlowPeer);
+        }
+    }
+
+    /// Send each peer its batch of transaction announcements
+    fn trickle(&mut self) {
+        let mempool = &self.mempool;
+        for (id, message) in self.relay.trickle(|txid| mempool.contains(txid)) {
+            self.send(id, message);
+        }
+    }
+
+    fn ping_peers(&mut self) {
+        let ready: Vec<PeerId> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.version.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ready {
+            let peer = self.peers.get_mut(&id).expect("peer is connected");
+            if peer.pending_ping.is_some() {
+                self.disconnect(id, P2pError::PingTimeout);
+                continue;
+            }
+            let nonce = self.rng.r#gen();
+            peer.pending_ping = Some(nonce);
+            self.send(id, Message::Ping(nonce));
+        }
+    }
+
+    fn handle_peer_event(&mut self, event: PeerEvent) {
+        match event {
+            PeerEvent::Ready { id, version } => {
+                let Some(peer) = self.peers.get_mut(&id) else {
+                    return;
+                };
+                self.sync.add_peer(id, version.best_height);
+                self.relay.add_peer(id, self.clock.now());
+                peer.version = Some(version.clone());
+                let info = PeerInfo {
+                    id,
+                    addr: peer.addr,
+                    inbound: peer.inbound,
+                    version,
+                    score: peer.score,
+                };
+                if info.inbound {
+                    // Where the peer says it accepts connections itself
+                    if info.version.listen_port != 0 {
+                        let addr = SocketAddr::new(info.addr.ip(), info.version.listen_port);
+                        self.book.add(addr, AddressSource::Gossip);
+                    }
+                } else {
+                    self.book.succeeded(&info.addr, self.clock.now());
+                    self.send(id, Message::GetAddr);
+                }
+                // Parents before children, so the peer can take each in turn
+                for tx in self.mempool.block_template(usize::MAX) {
+                    self.relay.queue_to(id, &tx.id);
+                }
+                self.emit(NodeEvent::PeerConnected(info));
+            }
+            PeerEvent::Message(id, message) => {
+                if self.peers.contains_key(&id) {
+                    self.handle_message(id, message);
+                }
+            }
+            PeerEvent::Closed(id, reason) => self.disconnect(id, reason),
+        }
+        self.send_requests();
+    }
+
+    /// Ask peers for the headers, blocks and transactions we are missing
+    fn send_requests(&mut self) {
+        for (id, message) in self.sync.schedule(&self.chain) {
+            self.send(id, message);
+        }
+        let mempool = &self.mempool;
+        for (id, message) in self.relay.schedule(|txid| mempool.contains(txid)) {
+            self.send(id, message);
+        }
+    }
+
+    /// Retry or drop overdue requests, send new ones and report progress
+    fn check_sync(&mut self) {
+        for id in self.sync.expire() {
+            self.disconnect(id, P2pError::Stalled);
+        }
+        self.relay.expire();
+        self.send_requests();
+        let progress = self.sync.progress(&self.chain);
+        if self.progress != Some(progress) {
+            self.progress = Some(progress);
+            self.emit(NodeEvent::SyncProgress(progress));
+        }
+    }
+
+    fn handle_message(&mut self, id: PeerId, message: Message) {
+        match message {
+            Message::Version(_) | Message::Verack => {
+                self.disconnect(
+                    id,
+                    P2pError::UnexpectedMessage {
+                        expected: "anything but a handshake message",
+                        got: message.command(),
+                    },
+                );
+            }
+            Message::Ping(nonce) => self.send(id, Message::Pong(nonce)),
+            Message::Pong(nonce) => {
+                if let Some(peer) = self.peers.get_mut(&id)
+                    && peer.pending_ping == Some(nonce)
+                {
+                    peer.pending_ping = None;
+                }
+            }
+            Message::Inv(items) | Message::GetData(items) | Message::NotFound(items) if items.len() > MAX_INV_ITEMS => {
+                self.misbehaving(id, P2pError::TooManyInventory(items.len()))
+            }
+            Message::Inv(items) => {
+                let (blocks, txs): (Vec<Inventory>, Vec<Inventory>) =
+                    items.into_iter().partition(|item| item.kind == InvKind::Block);
+                for item in blocks {
+                    if !self.has(&item) && !self.sync.knows_header(&item.hash) {
+                        self.sync.request_headers(id);
+                    }
+                    self.relay.mark_known(id, item);
+                }
+                // Transactions are no use until we have the blocks they spend from
+                let behind = self.sync.header_height() > self.chain.get_latest_block().id;
+                let txids = txs.into_iter().map(|item| item.hash).collect();
+                let (now, mempool) = (self.clock.now(), &self.mempool);
+                if let Err(error) = self.relay.on_inv(id, txids, now, |txid| behind || mempool.contains(txid)) {
+                    self.misbehaving(id, error);
+                }
+            }
+            Message::GetData(items) => self.serve(id, items),
+            Message::NotFound(items) => {
+                self.sync.on_not_found(id, &items);
+                self.relay.on_not_found(id, &items);
+            }
+            Message::Block(block) => {
+                self.relay.mark_known(id, Inventory::block(block.hash.clone()));
+                self.receive_block(id, block);
+            }
+            Message::Tx(tx) => match self.relay.on_tx(id, &tx.id, self.clock.now()) {
+                Ok(()) => {
+                    let _ = self.accept_transaction(Some(id), tx);
+                }
+                Err(error) => self.misbehaving(id, error),
+            },
+            Message::GetHeaders { locator, stop } => {
+                let headers = self.chain.headers_after(&locator, stop.as_deref(), MAX_HEADERS);
+                self.send(id, Message::Headers(headers));
+            }
+            Message::Headers(headers) => {
+                let now = self.clock.now().timestamp();
+                if let Err(error) = self.sync.on_headers(id, headers, &self.chain, now) {
+                    self.disconnect(id, error);
+                }
+            }
+            Message::GetAddr => {
+                let addrs = self.book.sample(MAX_ADDRS, self.clock.now());
+                self.send(id, Message::Addr(addrs));
+            }
+            Message::Addr(addrs) if addrs.len() > MAX_ADDRS => {
+                self.misbehaving(id, P2pError::TooManyAddrs(addrs.len()))
+            }
+            Message::Addr(addrs) => {
+                for addr in addrs {
+                    self.book.add(addr, AddressSource::Gossip);
+                }
+            }
+        }
+    }
+
+    fn has(&self, item: &Inventory) -> bool {
+        match item.kind {
+            InvKind::Block => self.chain.chain_work(&item.hash).is_some(),
+            InvKind::Tx => self.mempool.contains(&item.hash),
+        }
+    }
+
+    /// Answer a `getdata` with the blocks and transactions we have
+    fn serve(&mut self, id: PeerId, items: Vec<Inventory>) {
+        let mut missing = Vec::new();
+        for item in items {
+            let message = match item.kind {
+                InvKind::Block => self.chain.get_block(&item.hash).cloned().map(Message::Block),
+                InvKind::Tx => self.mempool.get(&item.hash).map(|entry| Message::Tx(entry.tx.clone())),
+            };
+            match message {
+                Some(message) => {
+                    self.relay.mark_known(id, item);
+                    self.send(id, message);
+                }
+                None => missing.push(item),
+            }
+        }
+        if !missing.is_empty() {
+            self.send(id, Message::NotFound(missing));
+        }
+    }
+
+    /// Submit a downloaded block, then any buffered blocks that were waiting for it
+    fn receive_block(&mut self, id: PeerId, block: Block) {
+        let mut next = self.sync.on_block(id, block, &self.chain).map(|block| (id, block));
+        while let Some((source, block)) = next {
+            let hash = block.hash.clone();
+            next = match self.accept_block(Some(source), block) {
+                Ok(_) => self.sync.take_child(&hash),
+                Err(ChainError::UnknownParent(_)) => None,
+                Err(error) if block_is_invalid(&error) => {
+                    self.misbehaving(source, P2pError::InvalidBlock(error.clone()));
+                    self.sync.block_failed(&hash, error, &self.chain);
+                    None
+                }
+                Err(_) => {
+                    self.sync.block_deferred(source, &hash);
+                    None
+                }
+            };
+        }
+    }
+
+    /// Submit a block from `source` (or from this node) and announce whatever it
+    /// connected
+    fn accept_block(&mut self, source: Option<PeerId>, block: Block) -> Result<BlockStatus, ChainError> {
+        let hash = block.hash.clone();
+        let result = self.chain.submit_block(block);
+        match (&result, source) {
+            (Err(ChainError::UnknownParent(_)), Some(id)) => self.sync.request_headers(id),
+            (Err(error), Some(peer)) => self.emit(NodeEvent::BlockRejected {
+                peer,
+                hash,
+                error: error.clone(),
+            }),
+            _ => {}
+        }
+        self.apply_chain_events(source);
+        result
+    }
+
+    /// Keep the mempool in step with the chain and announce newly connected blocks
+    fn apply_chain_events(&mut self, source: Option<PeerId>) {
+        let mut connected = Vec::new();
+        let mut disconnected = Vec::new();
+        let mut lagged = false;
+        loop {
+            match self.chain_events.try_recv() {
+                Ok(ChainEvent::BlockConnected(block)) => {
+                    self.sync.block_connected(&block);
+                    self.relay.block_connected();
+                    self.mempool.remove_for_block(&block);
+                    connected.push(block.hash);
+                }
+                // Tip first; readmitted once the whole reorg is known
+                Ok(ChainEvent::BlockDisconnected(block)) => disconnected.push(block),
+                Ok(ChainEvent::Reorg { .. }) => {}
+                Err(TryRecvError::Lagged(_)) => lagged = true,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+        // Oldest first, so transactions come back after the parents they spend
+        for block in disconnected.iter().rev() {
+            self.mempool.readmit_block(block, &self.chain.utxo_set);
+        }
+        if lagged {
+            // Missed events may have confirmed or orphaned anything in the pool
+            self.mempool.revalidate(&self.chain.utxo_set);
+        } else if !disconnected.is_empty() {
+            self.mempool.remove_unspendable(&self.chain.utxo_set);
+        }
+        for (id, message) in self.relay.announce_blocks(&connected, source) {
+            self.send(id, message);
+        }
+    }
+
+    fn accept_transaction(&mut self, source: Option<PeerId>, tx: Transaction) -> Result<AddOutcome, MempoolError> {
+        let txid = tx.id.clone();
+        let result = self.mempool.add(tx, &self.chain.utxo_set);
+        match (&result, source) {
+            (Ok(_), _) => self.relay.queue(&txid, source),
+            (Err(MempoolError::AlreadyKnown(_)), _) | (Err(_), None) => {}
+            (Err(error), Some(peer)) => {
+                // Missing inputs may just mean the parent has not reached us yet
+                if !matches!(error, MempoolError::Invalid(TxValidationError::UnknownOutpoint(_))) {
+                    self.relay.reject(&txid);
+                }
+                self.emit(NodeEvent::TransactionRejected {
+                    peer,
+                    txid,
+                    error: error.clone(),
+                });
+                self.misbehaving(peer, P2pError::InvalidTransaction(error.clone()));
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use super::connection::Connection;
+use super::error::P2pError;
+use super::message::{Message, Version, MIN_PROTOCOL_VERSION};
+
+/// Node-local number of a connection
+pub type PeerId = u64;
+
+/// A connected peer, as reported by [`Node::peers`](super::Node::peers)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PeerInfo {
+    pub id: PeerId,
+    pub addr: SocketAddr,
+    pub inbound: bool,
+    pub version: Version,
+    pub score: u32, // Misbehavior so far; the peer is banned at BAN_SCORE
+}
+
+/// What a peer's session task tells the node
+pub(crate) enum PeerEvent {
+    /// The handshake completed
+    Ready { id: PeerId, version: Version },
+    Message(PeerId, Message),
+    /// The link closed or the handshake failed
+    Closed(PeerId, P2pError),
+}
+
+/// Check a peer's `Version` against ours
+fn check_version(ours: &Version, theirs: &Version) -> Result<(), P2pError> {
+    if theirs.version < MIN_PROTOCOL_VERSION {
+        return Err(P2pError::UnsupportedVersion(theirs.version));
+    }
+    if theirs.genesis_hash != ours.genesis_hash {
+        return Err(P2pError::WrongNetwork(theirs.genesis_hash.clone()));
+    }
+    if theirs.nonce == ours.nonce {
+        return Err(P2pError::SelfConnection);
+    }
+    Ok(())
+}
+
+/// Both sides send `Version` straight away and answer the other's with `Verack`;
+/// the handshake is done once each side has both
+async fn handshake(
+    sender: &mpsc::Sender<Message>,
+    receiver: &mut mpsc::Receiver<Result<Message, P2pError>>,
+    ours: &Version,
+) -> Result<Version, P2pError> {
+    sender
+        .send(Message::Version(ours.clone()))
+        .await
+        .map_err(|_| P2pError::Closed)?;
+    let mut theirs = None;
+    let mut acknowledged = false;
+    while theirs.is_none() || !acknowledged {
+        match receiver.recv().await.ok_or(P2pError::Closed)?? {
+            Message::Version(version) if theirs.is_none() => {
+                check_version(ours, &version)?;
+                sender.send(Message::Verack).await.map_err(|_| P2pError::Closed)?;
+                theirs = Some(version);
+            }
+            Message::Verack if !acknowledged => acknowledged = true,
+            other => {
+                return Err(P2pError::UnexpectedMessage {
+                    expected: "version or verack",
+                    got: other.command(),
+                });
+            }
+        }
+    }
+    Ok(theirs.expect("loop ends once the version arrived"))
+}
+
+/// Handshake over `connection`, then forward everything the peer sends to the node
+/// until the link closes
+pub(crate) async fn run_session(
+    id: PeerId,
+    connection: Connection,
+    ours: Version,
+    handshake_timeout: Duration,
+    events: mpsc::Sender<PeerEvent>,
+) {
+    let Connection {
+        sender, mut receiver, ..
+    } = connection;
+    let result: Result<Infallible, P2pError> = async {
+        let theirs = tokio::time::timeout(handshake_timeout, handshake(&sender, &mut receiver, &ours))
+            .await
+            .map_err(|_| P2pError::HandshakeTimeout)??;
+        events
+            .send(PeerEvent::Ready { id, version: theirs })
+            .await
+            .map_err(|_| P2pError::NodeStopped)?;
+        while let Some(message) = receiver.recv().await {
+            events
+                .send(PeerEvent::Message(id, message?))
+                .await
+                .map_err(|_| P2pError::NodeStopped)?;
+        }
+        Err(P2pError::Closed)
+    }
+    .await;
+    let Err(error) = result;
+    let _ = events.send(PeerEvent::Closed(id, error)).await;
+}
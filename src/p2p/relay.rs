@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::time::Instant;
+
+use super::error::P2pError;
+use super::message::{InvKind, Inventory, Message};
+use super::peer::PeerId;
+
+/// Transactions announced to one peer per trickle; the rest wait for the next one
+pub const MAX_INV_PER_TRICKLE: usize = 100;
+
+/// Transactions requested from one peer at a time
+pub const MAX_TXS_IN_FLIGHT: usize = 100;
+
+/// Transactions a peer announced that wait to be requested; later announcements
+/// from that peer are ignored and charged
+pub const MAX_TXS_WANTED: usize = 5000;
+
+/// Transaction announcements and unrequested transactions taken from one peer per
+/// second, on average
+pub const TX_RATE: u32 = 100;
+
+/// Transaction announcements and unrequested transactions one peer may send at
+/// once after being quiet
+pub const TX_BURST: u32 = 5000;
+
+/// Inventory remembered per peer as already known to it
+const KNOWN_INVENTORY: usize = 10_000;
+
+/// Rejected transactions remembered so they are not fetched again
+const RECENT_REJECTS: usize = 10_000;
+
+/// A set that forgets its oldest entries beyond `capacity`
+struct RecentSet<T> {
+    capacity: usize,
+    order: VecDeque<T>,
+    items: HashSet<T>,
+}
+
+impl<T: Clone + Eq + Hash> RecentSet<T> {
+    fn new(capacity: usize) -> Self {
+        RecentSet {
+            capacity,
+            order: VecDeque::new(),
+            items: HashSet::new(),
+        }
+    }
+
+    /// Returns whether `item` was new
+    fn insert(&mut self, item: T) -> bool {
+        if !self.items.insert(item.clone()) {
+            return false;
+        }
+        self.order.push_back(item);
+        if self.order.len() > self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.items.remove(&oldest);
+        }
+        true
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        self.items.contains(item)
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.items.clear();
+    }
+}
+
+/// Allowance that refills at [`TX_RATE`] a second up to [`TX_BURST`]
+struct TokenBucket {
+    tokens: f64,
+    updated: DateTime<Utc>,
+}
+
+impl TokenBucket {
+    fn full(now: DateTime<Utc>) -> Self {
+        TokenBucket {
+            tokens: f64::from(TX_BURST),
+            updated: now,
+        }
+    }
+
+    /// Take up to `wanted` tokens at `now`, returning how many there were
+    fn take(&mut self, wanted: usize, now: DateTime<Utc>) -> usize {
+        // A clock that went back refills nothing
+        let elapsed = (now - self.updated).to_std().unwrap_or_default();
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * f64::from(TX_RATE)).min(f64::from(TX_BURST));
+        self.updated = self.updated.max(now);
+        let taken = wanted.min(self.tokens as usize);
+        self.tokens -= taken as f64;
+        taken
+    }
+}
+
+/// What relay knows about one ready peer
+struct RelayPeer {
+    known: RecentSet<Inventory>, // Announced by or to the peer, or sent either way
+    queued: VecDeque<String>,    // Transactions to announce on the next trickle
+    wanted: VecDeque<String>,    // Transactions the peer announced, not yet requested
+    txs_in_flight: usize,
+    allowance: TokenBucket, // For transactions and announcements from the peer
+}
+
+struct TxRequest {
+    peer: PeerId,
+    due: Instant,
+    alternatives: VecDeque<PeerId>, // Other peers that announced it, in order
+}
+
+/// Announce-then-fetch relay of transactions, and who already knows which blocks
+///
+/// Every peer has a filter of the inventory it is known to have, so nothing is
+/// announced to a peer that announced or sent it to us. New transactions are not
+/// announced straight away but queued per peer and sent in batches of at most
+/// [`MAX_INV_PER_TRICKLE`] on every trickle, which hides where a transaction
+/// started and bounds what one peer gets sent. A transaction announced by several
+/// peers is requested from the first only; if that peer does not deliver in time,
+/// the next announcer is asked. Transactions that failed validation are not
+/// fetched again until the next block.
+///
+/// What a peer sends is rate-limited too: every transaction it announces, and
+/// every transaction it sends unasked, takes a token from a bucket that refills at
+/// [`TX_RATE`] a second. Whatever goes over is dropped and reported as
+/// [`P2pError::TxFlood`], for the node to charge the peer.
+pub(crate) struct TxRelay {
+    peers: BTreeMap<PeerId, RelayPeer>, // Ordered, so batches go out in the same order every run
+    requests: BTreeMap<String, TxRequest>,
+    rejected: RecentSet<String>,
+    tx_timeout: Duration,
+}
+
+impl TxRelay {
+    pub(crate) fn new(tx_timeout: Duration) -> Self {
+        TxRelay {
+            peers: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            rejected: RecentSet::new(RECENT_REJECTS),
+            tx_timeout,
+        }
+    }
+
+    pub(crate) fn add_peer(&mut self, id: PeerId, now: DateTime<Utc>) {
+        let peer = RelayPeer {
+            known: RecentSet::new(KNOWN_INVENTORY),
+            queued: VecDeque::new(),
+            wanted: VecDeque::new(),
+            txs_in_flight: 0,
+            allowance: TokenBucket::full(now),
+        };
+        self.peers.insert(id, peer);
+    }
+
+    pub(crate) fn remove_peer(&mut self, id: PeerId) {
+        self.peers.remove(&id);
+        let requests: Vec<String> = self
+            .requests
+            .iter()
+            .filter(|(_, request)| request.peer == id)
+            .map(|(txid, _)| txid.clone())
+            .collect();
+        for txid in requests {
+            self.release(&txid);
+        }
+    }
+
+    /// Note that peer `id` has `item`
+    pub(crate) fn mark_known(&mut self, id: PeerId, item: Inventory) {
+        if let Some(peer) = self.peers.get_mut(&id) {
+            peer.known.insert(item);
+        }
+    }
+
+    /// Take in transactions `id` announced at `now`; those we lack and have not
+    /// rejected are requested on the next [`schedule`](Self::schedule)
+    ///
+    /// Announcements past the peer's allowance or [`MAX_TXS_WANTED`] are dropped
+    /// and make this fail.
+    pub(crate) fn on_inv(
+        &mut self,
+        id: PeerId,
+        txids: Vec<String>,
+        now: DateTime<Utc>,
+        have: impl Fn(&str) -> bool,
+    ) -> Result<(), P2pError> {
+        let Some(peer) = self.peers.get_mut(&id) else {
+            return Ok(());
+        };
+        let announced = txids.len();
+        let allowed = peer.allowance.take(announced, now);
+        let mut dropped = announced - allowed;
+        for txid in txids.into_iter().take(allowed) {
+            peer.known.insert(Inventory::tx(txid.clone()));
+            if have(&txid) || self.rejected.contains(&txid) {
+                continue;
+            }
+            if peer.wanted.len() >= MAX_TXS_WANTED {
+                dropped += 1;
+                continue;
+            }
+            // Repeats are dropped by `schedule`
+            match self.requests.get_mut(&txid) {
+                Some(request) if request.peer != id && !request.alternatives.contains(&id) => {
+                    request.alternatives.push_back(id);
+                }
+                Some(_) => {}
+                None => peer.wanted.push_back(txid),
+            }
+        }
+        match dropped {
+            0 => Ok(()),
+            _ => Err(P2pError::TxFlood(dropped)),
+        }
+    }
+
+    /// `getdata` for announced transactions, up to [`MAX_TXS_IN_FLIGHT`] per peer
+    pub(crate) fn schedule(&mut self, have: impl Fn(&str) -> bool) -> Vec<(PeerId, Message)> {
+        let now = Instant::now();
+        let mut messages = Vec::new();
+        for (&id, peer) in &mut self.peers {
+            let mut items = Vec::new();
+            while peer.txs_in_flight < MAX_TXS_IN_FLIGHT
+                && let Some(txid) = peer.wanted.pop_front()
+            {
+                if have(&txid) || self.rejected.contains(&txid) {
+                    continue;
+                }
+                if let Some(request) = self.requests.get_mut(&txid) {
+                    // Someone else announced it first since
+                    if request.peer != id && !request.alternatives.contains(&id) {
+                        request.alternatives.push_back(id);
+                    }
+                    continue;
+                }
+                let request = TxRequest {
+                    peer: id,
+                    due: now + self.tx_timeout,
+                    alternatives: VecDeque::new(),
+                };
+                self.requests.insert(txid.clone(), request);
+                peer.txs_in_flight += 1;
+                items.push(Inventory::tx(txid));
+            }
+            if !items.is_empty() {
+                messages.push((id, Message::GetData(items)));
+            }
+        }
+        messages
+    }
+
+    /// Forget the request for `txid` so the next announcer still connected is asked
+    fn release(&mut self, txid: &str) {
+        let Some(request) = self.requests.remove(txid) else {
+            return;
+        };
+        if let Some(peer) = self.peers.get_mut(&request.peer) {
+            peer.txs_in_flight -= 1;
+        }
+        // The next announcer goes first; the others line up behind it again
+        let mut next = true;
+        for id in request.alternatives {
+            if let Some(peer) = self.peers.get_mut(&id) {
+                if next {
+                    peer.wanted.push_front(txid.to_string());
+                } else {
+                    peer.wanted.push_back(txid.to_string());
+                }
+                next = false;
+            }
+        }
+    }
+
+    /// Release transaction requests past their deadline
+    pub(crate) fn expire(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .requests
+            .iter()
+            .filter(|(_, request)| request.due <= now)
+            .map(|(txid, _)| txid.clone())
+            .collect();
+        for txid in expired {
+            self.release(&txid);
+        }
+    }
+
+    pub(crate) fn on_not_found(&mut self, id: PeerId, items: &[Inventory]) {
+        for item in items.iter().filter(|item| item.kind == InvKind::Tx) {
+            if self.requests.get(&item.hash).is_some_and(|request| request.peer == id) {
+                self.release(&item.hash);
+            }
+        }
+    }
+
+    /// A transaction arrived from `id` at `now`, asked for or not
+    ///
+    /// One we did not ask `id` for takes from its allowance; past that it fails,
+    /// and the transaction should be dropped.
+    pub(crate) fn on_tx(&mut self, id: PeerId, txid: &str, now: DateTime<Utc>) -> Result<(), P2pError> {
+        let requested = self.requests.get(txid).is_some_and(|request| request.peer == id);
+        if !requested
+            && let Some(peer) = self.peers.get_mut(&id)
+            && peer.allowance.take(1, now) == 0
+        {
+            return Err(P2pError::TxFlood(1));
+        }
+        self.mark_known(id, Inventory::tx(txid));
+        if let Some(request) = self.requests.remove(txid)
+            && let Some(peer) = self.peers.get_mut(&request.peer)
+        {
+            peer.txs_in_flight -= 1;
+        }
+        Ok(())
+    }
+
+    /// Do not fetch `txid` again until the next block
+    pub(crate) fn reject(&mut self, txid: &str) {
+        self.rejected.insert(txid.to_string());
+    }
+
+    /// A new block may make rejected transactions valid
+    pub(crate) fn block_connected(&mut self) {
+        self.rejected.clear();
+    }
+
+    /// Announce `txid` on the next trickle to every peer but `source` that does not
+    /// know it yet
+    pub(crate) fn queue(&mut self, txid: &str, source: Option<PeerId>) {
+        let ids: Vec<PeerId> = self.peers.keys().copied().filter(|id| Some(*id) != source).collect();
+        for id in ids {
+            self.queue_to(id, txid);
+        }
+    }
+
+    /// Announce `txid` to peer `id` on the next trickle unless it knows it
+    pub(crate) fn queue_to(&mut self, id: PeerId, txid: &str) {
+        if let Some(peer) = self.peers.get_mut(&id)
+            && peer.known.insert(Inventory::tx(txid))
+        {
+            peer.queued.push_back(txid.to_string());
+        }
+    }
+
+    /// One batch of queued announcements per peer, leaving out transactions that
+    /// are no longer `pending`
+    pub(crate) fn trickle(&mut self, pending: impl Fn(&str) -> bool) -> Vec<(PeerId, Message)> {
+        let mut messages = Vec::new();
+        for (&id, peer) in &mut self.peers {
+            let mut items = Vec::new();
+            while items.len() < MAX_INV_PER_TRICKLE
+                && let Some(txid) = peer.queued.pop_front()
+            {
+                if pending(&txid) {
+                    items.push(Inventory::tx(txid));
+                }
+            }
+            if !items.is_empty() {
+                messages.push((id, Message::Inv(items)));
+            }
+        }
+        messages
+    }
+
+    /// `inv` for each peer but `source` that does not know these blocks yet; blocks
+    /// are announced straight away
+    pub(crate) fn announce_blocks(&mut self, hashes: &[String], source: Option<PeerId>) -> Vec<(PeerId, Message)> {
+        let mut messages = Vec::new();
+        for (&id, peer) in &mut self.peers {
+            if Some(id) == source {
+                continue;
+            }
+            let items: Vec<Inventory> = hashes
+                .iter()
+                .map(|hash| Inventory::block(hash.clone()))
+                .filter(|item| peer.known.insert(item.clone()))
+                .collect();
+            if !items.is_empty() {
+                messages.push((id, Message::Inv(items)));
+            }
+        }
+        messages
+    }
+}
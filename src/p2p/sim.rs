@@ -0,0 +1,290 @@
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use super::connection::{Connection, CHANNEL_CAPACITY};
+use super::error::P2pError;
+use super::message::Message;
+use super::transport::Transport;
+
+/// Connections waiting for a simulated listener to take them
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Where simulated ports handed to dialers start
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// How messages travel between two hosts
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinkConditions {
+    pub latency: Duration,
+    pub jitter: Duration, // Up to this much more, picked per message
+    pub drop_rate: f64,   // Chance that a message is lost, from 0 to 1
+}
+
+/// Messages the network carried and lost so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SimStats {
+    pub delivered: u64,
+    pub dropped: u64,
+}
+
+struct SimState {
+    rng: StdRng,
+    conditions: LinkConditions,
+    links: HashMap<(IpAddr, IpAddr), LinkConditions>, // Overrides, keyed by the lower address first
+    groups: Option<HashMap<IpAddr, usize>>,            // The partition in force, by host
+    listeners: HashMap<SocketAddr, mpsc::Sender<Connection>>,
+    next_port: u16,
+    stats: SimStats,
+}
+
+impl SimState {
+    fn conditions(&self, a: IpAddr, b: IpAddr) -> LinkConditions {
+        self.links.get(&(a.min(b), a.max(b))).copied().unwrap_or(self.conditions)
+    }
+
+    /// Hosts left out of every group of a partition are together in one more
+    fn reachable(&self, a: IpAddr, b: IpAddr) -> bool {
+        match &self.groups {
+            Some(groups) => a == b || groups.get(&a) == groups.get(&b),
+            None => true,
+        }
+    }
+
+    fn ephemeral_port(&mut self) -> u16 {
+        let port = self.next_port;
+        self.next_port = self.next_port.checked_add(1).unwrap_or(FIRST_EPHEMERAL_PORT);
+        port
+    }
+}
+
+/// An in-process network of [`Node`](super::Node)s
+///
+/// Nodes started with [`transport`](Self::transport) listen and dial on this
+/// network instead of on sockets, as the host they were given. Messages are passed
+/// as values, in order per direction, after the link's latency plus a random part
+/// of its jitter; some are lost at the link's drop rate, and all of them between
+/// hosts a [`partition`](Self::partition) separates. Every random choice, the
+/// nodes' own included, comes from one RNG seeded here, and delays are measured
+/// on tokio's clock. Under paused time (`#[tokio::test(start_paused = true)]`) a
+/// run therefore takes no real time, and the same seed loses and delays the same
+/// messages every time.
+///
+/// Partitions only drop messages: links across one stay open until the nodes give
+/// up on each other (a ping goes unanswered), and dials across one fail.
+#[derive(Clone)]
+pub struct SimNetwork {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl fmt::Debug for SimNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SimNetwork").field("stats", &self.stats()).finish()
+    }
+}
+
+impl SimNetwork {
+    /// A network with instant, lossless links
+    pub fn new(seed: u64) -> Self {
+        let state = SimState {
+            rng: StdRng::seed_from_u64(seed),
+            conditions: LinkConditions::default(),
+            links: HashMap::new(),
+            groups: None,
+            listeners: HashMap::new(),
+            next_port: FIRST_EPHEMERAL_PORT,
+            stats: SimStats::default(),
+        };
+        SimNetwork {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().expect("simulated network lock")
+    }
+
+    /// What a node at `host` should run on
+    pub fn transport(&self, host: IpAddr) -> Transport {
+        Transport::Simulated {
+            network: self.clone(),
+            host,
+        }
+    }
+
+    /// Conditions for every link without its own
+    ///
+    /// # Panics
+    ///
+    /// If the drop rate is not between 0 and 1.
+    pub fn set_conditions(&self, conditions: LinkConditions) {
+        assert!((0.0..=1.0).contains(&conditions.drop_rate), "drop rate must be between 0 and 1");
+        self.lock().conditions = conditions;
+    }
+
+    /// Conditions for the link between hosts `a` and `b`, both ways
+    ///
+    /// # Panics
+    ///
+    /// If the drop rate is not between 0 and 1.
+    pub fn set_link(&self, a: IpAddr, b: IpAddr, conditions: LinkConditions) {
+        assert!((0.0..=1.0).contains(&conditions.drop_rate), "drop rate must be between 0 and 1");
+        self.lock().links.insert((a.min(b), a.max(b)), conditions);
+    }
+
+    /// Split the network: hosts only reach hosts in the same group, and hosts in no
+    /// group only each other
+    pub fn partition(&self, groups: &[&[IpAddr]]) {
+        let groups = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, hosts)| hosts.iter().map(move |host| (*host, group)))
+            .collect();
+        self.lock().groups = Some(groups);
+    }
+
+    /// Undo [`partition`](Self::partition)
+    pub fn heal(&self) {
+        self.lock().groups = None;
+    }
+
+    pub fn stats(&self) -> SimStats {
+        self.lock().stats
+    }
+
+    /// A seed for a node's own RNG
+    pub(crate) fn next_seed(&self) -> u64 {
+        self.lock().rng.next_u64()
+    }
+
+    /// Listen on `addr`; an unspecified IP means `host` and port 0 picks a free port
+    pub(crate) fn bind(&self, host: IpAddr, addr: SocketAddr) -> io::Result<SimListener> {
+        let mut state = self.lock();
+        let ip = if addr.ip().is_unspecified() { host } else { addr.ip() };
+        let port = if addr.port() == 0 { state.ephemeral_port() } else { addr.port() };
+        let addr = SocketAddr::new(ip, port);
+        // A listener whose node stopped no longer holds its address
+        if state.listeners.get(&addr).is_some_and(|listener| !listener.is_closed()) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+        let (sender, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+        state.listeners.insert(addr, sender);
+        Ok(SimListener { addr, incoming })
+    }
+
+    /// Connect `host` to whoever listens on `addr`, taking one round trip
+    pub(crate) async fn dial(&self, host: IpAddr, addr: SocketAddr) -> Result<Connection, P2pError> {
+        let (listener, local, round_trip) = {
+            let mut state = self.lock();
+            let round_trip = state.conditions(host, addr.ip()).latency * 2;
+            let listener = state.listeners.get(&addr).cloned();
+            let local = SocketAddr::new(host, state.ephemeral_port());
+            (listener, local, round_trip)
+        };
+        tokio::time::sleep(round_trip).await;
+        if !self.lock().reachable(host, addr.ip()) {
+            return Err(io::Error::from(io::ErrorKind::HostUnreachable).into());
+        }
+        let refused = || P2pError::from(io::Error::from(io::ErrorKind::ConnectionRefused));
+        let listener = listener.ok_or_else(refused)?;
+        let (ours, theirs) = self.link(local, addr);
+        listener.send(theirs).await.map_err(|_| refused())?;
+        Ok(ours)
+    }
+
+    /// Both ends of a link between `a` and `b`; the first is `a`'s
+    fn link(&self, a: SocketAddr, b: SocketAddr) -> (Connection, Connection) {
+        let (a_sender, a_outbound) = mpsc::channel(CHANNEL_CAPACITY);
+        let (b_sender, b_outbound) = mpsc::channel(CHANNEL_CAPACITY);
+        let (a_inbound, a_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let (b_inbound, b_receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(self.clone().carry(a.ip(), b.ip(), a_outbound, b_inbound));
+        tokio::spawn(self.clone().carry(b.ip(), a.ip(), b_outbound, a_inbound));
+        let first = Connection {
+            addr: b,
+            sender: a_sender,
+            receiver: a_receiver,
+        };
+        let second = Connection {
+            addr: a,
+            sender: b_sender,
+            receiver: b_receiver,
+        };
+        (first, second)
+    }
+
+    /// How long a message from `from` to `to` takes, or `None` if it is lost
+    fn fate(&self, from: IpAddr, to: IpAddr) -> Option<Duration> {
+        let mut state = self.lock();
+        let conditions = state.conditions(from, to);
+        if !state.reachable(from, to) || state.rng.gen_bool(conditions.drop_rate) {
+            state.stats.dropped += 1;
+            return None;
+        }
+        Some(conditions.latency + state.rng.gen_range(Duration::ZERO..=conditions.jitter))
+    }
+
+    /// Move messages one way along a link until either end goes away
+    ///
+    /// A message never overtakes an earlier one, as on a stream.
+    async fn carry(
+        self,
+        from: IpAddr,
+        to: IpAddr,
+        mut outbound: mpsc::Receiver<Message>,
+        inbound: mpsc::Sender<Result<Message, P2pError>>,
+    ) {
+        let (queue, mut queued) = mpsc::unbounded_channel::<(Instant, Message)>();
+        let network = self.clone();
+        tokio::spawn(async move {
+            while let Some((at, message)) = queued.recv().await {
+                tokio::time::sleep_until(at).await;
+                if inbound.send(Ok(message)).await.is_err() {
+                    break;
+                }
+                network.lock().stats.delivered += 1;
+            }
+        });
+
+        let mut last = Instant::now();
+        loop {
+            // Biased, so runs repeat
+            tokio::select! {
+                biased;
+                _ = queue.closed() => break,
+                message = outbound.recv() => {
+                    let Some(message) = message else { break };
+                    if let Some(delay) = self.fate(from, to) {
+                        last = last.max(Instant::now() + delay);
+                        if queue.send((last, message)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Connections dialed to one address on a [`SimNetwork`]
+pub(crate) struct SimListener {
+    addr: SocketAddr,
+    incoming: mpsc::Receiver<Connection>,
+}
+
+impl SimListener {
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub(crate) async fn accept(&mut self) -> Option<Connection> {
+        self.incoming.recv().await
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::chain::{locator_heights, validate_header, Block, BlockHeader, Blockchain, ChainError};
+
+use super::error::P2pError;
+use super::message::{InvKind, Inventory, Message, MAX_HEADERS};
+use super::peer::PeerId;
+
+/// Blocks requested from one peer at a time
+pub const MAX_BLOCKS_IN_FLIGHT: usize = 16;
+
+/// How far past the last block both chains share blocks are downloaded; bounds what
+/// is buffered while an earlier block is still on its way
+pub const DOWNLOAD_WINDOW: u64 = 1024;
+
+/// How often requests are checked for timeouts and new ones sent
+pub(crate) const SYNC_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How far a node is from having the best chain its peers told it about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub header_height: u64,       // Tip of the best valid header chain
+    pub block_height: u64,        // Tip of the active chain
+    pub headers_requested: usize, // Peers that owe us headers
+    pub blocks_in_flight: usize,
+    pub blocks_buffered: usize,   // Downloaded, waiting for an earlier block
+}
+
+impl SyncProgress {
+    /// Every block of the best header chain is connected and no headers are pending
+    pub fn is_synced(&self) -> bool {
+        self.block_height >= self.header_height && self.headers_requested == 0
+    }
+
+    /// Share of the best header chain whose blocks are connected, in percent
+    pub fn percent(&self) -> f64 {
+        if self.header_height == 0 {
+            return 100.0;
+        }
+        100.0 * self.block_height.min(self.header_height) as f64 / self.header_height as f64
+    }
+}
+
+/// A validated header and the branch it completes
+struct HeaderNode {
+    header: BlockHeader,
+    height: u64,
+    work: u128, // Cumulative, as in the block tree
+}
+
+/// What sync knows about one ready peer
+struct SyncPeer {
+    best_height: u64,               // Highest block the peer is known to have
+    headers_due: Option<Instant>,   // Set while a getheaders is unanswered
+    wants_headers: bool,            // Ask on the next schedule even if not ahead
+    continue_from: Option<String>,  // Last hash of a full headers batch
+    blocks_in_flight: usize,
+}
+
+struct BlockRequest {
+    peer: PeerId,
+    due: Instant,
+}
+
+/// Header-first block download
+///
+/// Headers are fetched from any peer ahead of us and checked with the same rules as
+/// the block tree ([`validate_header`]: linkage, retargeting, proof-of-work and
+/// timestamps) before a single body is requested. Every valid header is kept, and
+/// the branch with the most work is the one whose blocks are downloaded, spread
+/// over the peers that have them. A request that times out or comes back `notfound`
+/// is retried, preferably from a peer not yet asked for that block. Blocks that
+/// arrive before their parent are buffered until it is connected.
+pub(crate) struct BlockSync {
+    tree: HashMap<String, HeaderNode>,
+    best: Vec<String>,                           // Best header chain, by height
+    invalid: HashMap<String, ChainError>,        // Headers whose block failed validation
+    peers: BTreeMap<PeerId, SyncPeer>,           // Ordered, so requests go out in the same order every run
+    in_flight: HashMap<String, BlockRequest>,
+    tried: HashMap<String, HashSet<PeerId>>,     // Peers a block request already failed with
+    buffered: HashMap<String, (PeerId, Block)>,  // Keyed by the parent hash
+    block_timeout: Duration,
+    headers_timeout: Duration,
+}
+
+impl BlockSync {
+    /// Start from the active chain of `chain`
+    pub(crate) fn new(chain: &Blockchain, block_timeout: Duration, headers_timeout: Duration) -> Self {
+        let mut sync = BlockSync {
+            tree: HashMap::new(),
+            best: Vec::new(),
+            invalid: HashMap::new(),
+            peers: BTreeMap::new(),
+            in_flight: HashMap::new(),
+            tried: HashMap::new(),
+            buffered: HashMap::new(),
+            block_timeout,
+            headers_timeout,
+        };
+        for block in &chain.blocks {
+            sync.insert(block.hash.clone(), block.header());
+            sync.best.push(block.hash.clone());
+        }
+        sync
+    }
+
+    fn insert(&mut self, hash: String, header: BlockHeader) -> &HeaderNode {
+        let (height, work) = match self.tree.get(&header.previous_hash) {
+            Some(parent) => (parent.height + 1, parent.work),
+            None => (0, 0), // Genesis
+        };
+        let work = work + header.target.work();
+        self.tree.entry(hash).or_insert(HeaderNode { header, height, work })
+    }
+
+    pub(crate) fn header_height(&self) -> u64 {
+        self.best.len() as u64 - 1
+    }
+
+    fn best_work(&self) -> u128 {
+        self.tree[self.best.last().expect("best chain holds genesis")].work
+    }
+
+    /// Make the branch ending in `tip` the best header chain
+    fn set_best_tip(&mut self, tip: &str) {
+        let mut branch = Vec::new();
+        let mut hash = tip.to_string();
+        let fork = loop {
+            let node = &self.tree[&hash];
+            if self.best.get(node.height as usize) == Some(&hash) {
+                break node.height;
+            }
+            branch.push(hash.clone());
+            hash = node.header.previous_hash.clone();
+        };
+        self.best.truncate(fork as usize + 1);
+        self.best.extend(branch.into_iter().rev());
+    }
+
+    /// Where the branch ending in `tip` leaves the best chain, and its headers from
+    /// there up to `tip`
+    fn branch(&self, tip: &str) -> (u64, Vec<BlockHeader>) {
+        let mut headers = Vec::new();
+        let mut hash = tip;
+        loop {
+            let node = &self.tree[hash];
+            if self.best.get(node.height as usize).map(String::as_str) == Some(hash) {
+                headers.reverse();
+                return (node.height, headers);
+            }
+            headers.push(node.header.clone());
+            hash = &node.header.previous_hash;
+        }
+    }
+
+    /// Hashes of the best header chain to ask peers for what follows
+    fn locator(&self) -> Vec<String> {
+        locator_heights(self.header_height())
+            .into_iter()
+            .map(|height| self.best[height as usize].clone())
+            .collect()
+    }
+
+    pub(crate) fn add_peer(&mut self, id: PeerId, best_height: u64) {
+        let peer = SyncPeer {
+            best_height,
+            headers_due: None,
+            wants_headers: false,
+            continue_from: None,
+            blocks_in_flight: 0,
+        };
+        self.peers.insert(id, peer);
+    }
+
+    pub(crate) fn remove_peer(&mut self, id: PeerId) {
+        self.peers.remove(&id);
+        let requests: Vec<String> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| request.peer == id)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in requests {
+            self.release(&hash);
+        }
+    }
+
+    /// Ask `id` for headers on the next [`schedule`](Self::schedule), e.g. because it
+    /// announced a block we do not know
+    pub(crate) fn request_headers(&mut self, id: PeerId) {
+        if let Some(peer) = self.peers.get_mut(&id) {
+            peer.wants_headers = true;
+        }
+    }
+
+    pub(crate) fn knows_header(&self, hash: &str) -> bool {
+        self.tree.contains_key(hash)
+    }
+
+    /// Validate and store a `headers` reply, switching to its branch if that has the
+    /// most work
+    ///
+    /// Timestamps are checked against `now`, the node's clock in seconds. An error
+    /// means the peer broke the protocol and should be dropped.
+    pub(crate) fn on_headers(
+        &mut self,
+        id: PeerId,
+        headers: Vec<BlockHeader>,
+        chain: &Blockchain,
+        now: i64,
+    ) -> Result<(), P2pError> {
+        let header_height = self.header_height();
+        let Some(peer) = self.peers.get_mut(&id) else {
+            return Ok(());
+        };
+        peer.headers_due = None;
+        if headers.len() > MAX_HEADERS {
+            return Err(P2pError::TooManyHeaders(headers.len()));
+        }
+        let Some(first) = headers.first() else {
+            // Nothing past our locator: the peer is not ahead of our best chain
+            peer.best_height = peer.best_height.min(header_height);
+            peer.continue_from = None;
+            return Ok(());
+        };
+
+        let parent = &first.previous_hash;
+        if let Some(error) = self.invalid.get(parent) {
+            return Err(P2pError::InvalidHeader(error.clone()));
+        }
+        let Some(parent) = self.tree.get(parent) else {
+            return Err(P2pError::UnconnectedHeaders);
+        };
+        let (fork, mut branch) = self.branch(&first.previous_hash);
+        let mut previous = first.previous_hash.clone();
+        let mut height = parent.height;
+        let mut hashes = Vec::with_capacity(headers.len());
+        for header in &headers {
+            height += 1;
+            let hash = header.hash();
+            if let Some(error) = self.invalid.get(&hash) {
+                return Err(P2pError::InvalidHeader(error.clone()));
+            }
+            if header.previous_hash != previous {
+                return Err(P2pError::InvalidHeader(ChainError::InvalidPreviousHash(height)));
+            }
+            if !self.tree.contains_key(&hash) {
+                let ancestor = |h: u64| match h.checked_sub(fork + 1) {
+                    None => self.tree[&self.best[h as usize]].header.clone(),
+                    Some(offset) => branch[offset as usize].clone(),
+                };
+                validate_header(&chain.params, height, header, ancestor, now).map_err(P2pError::InvalidHeader)?;
+            }
+            branch.push(header.clone());
+            previous = hash.clone();
+            hashes.push(hash);
+        }
+
+        for (hash, header) in hashes.into_iter().zip(headers.iter().cloned()) {
+            self.insert(hash, header);
+        }
+        let last = &self.tree[&previous];
+        let last_work = last.work;
+        let peer = self.peers.get_mut(&id).expect("peer was checked above");
+        peer.best_height = peer.best_height.max(last.height);
+        // A full batch means the peer has more
+        if headers.len() == MAX_HEADERS {
+            peer.continue_from = Some(previous.clone());
+            peer.wants_headers = true;
+        }
+        if last_work > self.best_work() {
+            self.set_best_tip(&previous);
+        }
+        Ok(())
+    }
+
+    /// Requests to send now: headers from peers ahead of us, and the next missing
+    /// blocks of the best header chain from peers with room for more
+    pub(crate) fn schedule(&mut self, chain: &Blockchain) -> Vec<(PeerId, Message)> {
+        let now = Instant::now();
+        let header_height = self.header_height();
+        let mut messages = Vec::new();
+
+        let locator = self.locator();
+        for (&id, peer) in &mut self.peers {
+            if peer.headers_due.is_some() || !(peer.wants_headers || peer.best_height > header_height) {
+                continue;
+            }
+            let locator = match peer.continue_from.take() {
+                Some(hash) => vec![hash],
+                None => locator.clone(),
+            };
+            peer.wants_headers = false;
+            peer.headers_due = Some(now + self.headers_timeout);
+            messages.push((id, Message::GetHeaders { locator, stop: None }));
+        }
+
+        // Download from the last block the best header chain shares with the active chain
+        let mut start = chain.get_latest_block().id.min(header_height);
+        while chain.height_of(&self.best[start as usize]).is_none() {
+            start -= 1;
+        }
+        let mut requests: BTreeMap<PeerId, Vec<Inventory>> = BTreeMap::new();
+        for height in start + 1..=header_height.min(start + DOWNLOAD_WINDOW) {
+            let hash = &self.best[height as usize];
+            if chain.chain_work(hash).is_some() || self.in_flight.contains_key(hash) || self.is_buffered(hash) {
+                continue;
+            }
+            // Nobody has this height or has room for it, so nobody does for later ones
+            let Some(id) = self.pick_peer(hash, height) else {
+                break;
+            };
+            self.peers.get_mut(&id).expect("picked a known peer").blocks_in_flight += 1;
+            let request = BlockRequest {
+                peer: id,
+                due: now + self.block_timeout,
+            };
+            self.in_flight.insert(hash.clone(), request);
+            requests.entry(id).or_default().push(Inventory::block(hash.clone()));
+        }
+        messages.extend(requests.into_iter().map(|(id, items)| (id, Message::GetData(items))));
+        messages
+    }
+
+    fn is_buffered(&self, hash: &str) -> bool {
+        let parent = &self.tree[hash].header.previous_hash;
+        self.buffered.get(parent).is_some_and(|(_, block)| block.hash == hash)
+    }
+
+    /// The least busy peer that has block `height` and room for another request,
+    /// preferring peers not yet tried for it
+    fn pick_peer(&self, hash: &str, height: u64) -> Option<PeerId> {
+        let tried = self.tried.get(hash);
+        self.peers
+            .iter()
+            .filter(|(_, peer)| peer.best_height >= height && peer.blocks_in_flight < MAX_BLOCKS_IN_FLIGHT)
+            .min_by_key(|(id, peer)| (tried.is_some_and(|tried| tried.contains(id)), peer.blocks_in_flight, **id))
+            .map(|(id, _)| *id)
+    }
+
+    /// Forget the request for `hash` so it is sent again, to someone else if possible
+    fn release(&mut self, hash: &str) {
+        if let Some(request) = self.in_flight.remove(hash) {
+            self.tried.entry(hash.to_string()).or_default().insert(request.peer);
+            if let Some(peer) = self.peers.get_mut(&request.peer) {
+                peer.blocks_in_flight -= 1;
+            }
+        }
+    }
+
+    /// Release block requests past their deadline, and return the peers that did
+    /// not answer a header request in time
+    pub(crate) fn expire(&mut self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| request.due <= now)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in expired {
+            self.release(&hash);
+        }
+        self.peers
+            .iter()
+            .filter(|(_, peer)| peer.headers_due.is_some_and(|due| due <= now))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub(crate) fn on_not_found(&mut self, id: PeerId, items: &[Inventory]) {
+        for item in items.iter().filter(|item| item.kind == InvKind::Block) {
+            if self.in_flight.get(&item.hash).is_some_and(|request| request.peer == id) {
+                self.release(&item.hash);
+            }
+        }
+    }
+
+    /// Take in a block from `id`; returns it if it can be submitted now, or keeps it
+    /// until its parent is connected
+    pub(crate) fn on_block(&mut self, id: PeerId, block: Block, chain: &Blockchain) -> Option<Block> {
+        if let Some(request) = self.in_flight.remove(&block.hash)
+            && let Some(peer) = self.peers.get_mut(&request.peer)
+        {
+            peer.blocks_in_flight -= 1;
+        }
+        self.tried.remove(&block.hash);
+        if chain.chain_work(&block.previous_hash).is_none() && self.tree.contains_key(&block.hash) {
+            self.buffered.insert(block.previous_hash.clone(), (id, block));
+            return None;
+        }
+        Some(block)
+    }
+
+    /// A buffered block whose parent `hash` was just connected, and who sent it
+    pub(crate) fn take_child(&mut self, hash: &str) -> Option<(PeerId, Block)> {
+        self.buffered.remove(hash)
+    }
+
+    /// The block `hash` from `id` could not be connected for a reason that says
+    /// nothing about the block itself: download it again, from someone else if possible
+    pub(crate) fn block_deferred(&mut self, id: PeerId, hash: &str) {
+        self.tried.entry(hash.to_string()).or_default().insert(id);
+    }
+
+    /// The block `hash` failed validation: never download its branch again and fall
+    /// back to the active chain
+    pub(crate) fn block_failed(&mut self, hash: &str, error: ChainError, chain: &Blockchain) {
+        self.invalid.insert(hash.to_string(), error);
+        let mut parent = hash.to_string();
+        while let Some((_, child)) = self.buffered.remove(&parent) {
+            parent = child.hash;
+        }
+        if self.best.contains(&hash.to_string()) {
+            self.set_best_tip(&chain.get_latest_block().hash);
+        }
+    }
+
+    /// Track a block the active chain connected, wherever it came from
+    pub(crate) fn block_connected(&mut self, block: &Block) {
+        if !self.tree.contains_key(&block.hash) {
+            self.insert(block.hash.clone(), block.header());
+        }
+        if self.in_flight.contains_key(&block.hash) {
+            self.release(&block.hash);
+        }
+        self.tried.remove(&block.hash);
+        if self.tree[&block.hash].work > self.best_work() {
+            self.set_best_tip(&block.hash);
+        }
+    }
+
+    pub(crate) fn progress(&self, chain: &Blockchain) -> SyncProgress {
+        SyncProgress {
+            header_height: self.header_height(),
+            block_height: chain.get_latest_block().id,
+            headers_requested: self.peers.values().filter(|peer| peer.headers_due.is_some()).count(),
+            blocks_in_flight: self.in_flight.len(),
+            blocks_buffered: self.buffered.len(),
+        }
+    }
+}
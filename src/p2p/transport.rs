@@ -0,0 +1,92 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpListener, TcpStream, ToSocketAddrs};
+
+use super::connection::Connection;
+use super::error::P2pError;
+use super::sim::{SimListener, SimNetwork};
+
+/// Pause after a failed accept, so running out of file descriptors does not spin
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// What carries a node's connections: TCP, or a [`SimNetwork`] in this process
+#[derive(Debug, Clone, Default)]
+pub enum Transport {
+    #[default]
+    Tcp,
+    /// Acting as `host` on `network`
+    Simulated { network: SimNetwork, host: IpAddr },
+}
+
+impl Transport {
+    /// Open a connection to `addr`
+    pub async fn dial(&self, addr: impl ToSocketAddrs) -> Result<Connection, P2pError> {
+        match self {
+            Transport::Tcp => Ok(Connection::tcp(TcpStream::connect(addr).await?)?),
+            Transport::Simulated { network, host } => network.dial(*host, resolve(addr).await?).await,
+        }
+    }
+
+    pub(crate) async fn bind(&self, addr: impl ToSocketAddrs) -> Result<Listener, P2pError> {
+        match self {
+            Transport::Tcp => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+            Transport::Simulated { network, host } => {
+                let listener = network.bind(*host, resolve(addr).await?)?;
+                Ok(Listener::Simulated(listener))
+            }
+        }
+    }
+
+    /// Randomness for a node: from the OS on TCP, from the network's seed when
+    /// simulated
+    pub(crate) fn rng(&self) -> StdRng {
+        match self {
+            Transport::Tcp => StdRng::from_entropy(),
+            Transport::Simulated { network, .. } => StdRng::seed_from_u64(network.next_seed()),
+        }
+    }
+}
+
+async fn resolve(addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+    lookup_host(addr)
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to"))
+}
+
+/// Where a node accepts connections
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    Simulated(SimListener),
+}
+
+impl Listener {
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr(),
+            Listener::Simulated(listener) => Ok(listener.local_addr()),
+        }
+    }
+
+    /// The next connection, skipping any that fail to set up; `None` once no more
+    /// can arrive
+    pub(crate) async fn accept(&mut self) -> Option<Connection> {
+        match self {
+            Listener::Tcp(listener) => loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        if let Ok(connection) = Connection::tcp(stream) {
+                            return Some(connection);
+                        }
+                    }
+                    // Usually out of descriptors or memory, which takes a while to clear up
+                    Err(_) => tokio::time::sleep(ACCEPT_RETRY_DELAY).await,
+                }
+            },
+            Listener::Simulated(listener) => listener.accept().await,
+        }
+    }
+}
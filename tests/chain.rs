@@ -0,0 +1,197 @@
+use rust101::chain::{verify_signature, Blockchain, ChainError, Fee, Transaction, TxValidationError, Wallet};
+
+#[test]
+fn genesis_reward_goes_to_genesis_address() {
+    let alice = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    assert_eq!(chain.blocks.len(), 1);
+    assert_eq!(chain.get_balance(&alice.get_address()), 50);
+    assert!(chain.is_chain_valid());
+}
+
+#[test]
+fn transfer_moves_coins_and_pays_miner() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let miner = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx], &miner.get_address()).unwrap();
+
+    assert_eq!(chain.get_balance(&alice.get_address()), 20);
+    assert_eq!(chain.get_balance(&bob.get_address()), 30);
+    assert_eq!(chain.get_balance(&miner.get_address()), 50);
+    assert_eq!(chain.validate(), Ok(()));
+}
+
+#[test]
+fn spending_more_than_balance_is_an_error() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let err = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 80, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap_err();
+    assert_eq!(err, ChainError::InsufficientFunds { needed: 80, available: 50 });
+}
+
+#[test]
+fn tampered_block_fails_validation() {
+    let alice = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    chain.add_block(vec![], &alice.get_address()).unwrap();
+
+    chain.blocks[1].nonce += 1;
+    assert_eq!(chain.validate(), Err(ChainError::InvalidHash(1)));
+}
+
+#[test]
+fn forged_signature_is_rejected() {
+    let alice = Wallet::new();
+    let mallory = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+
+    // Mallory claims Alice's coins using Alice's public key but her own signature
+    let mut tx = Transaction::new_utxo_transaction(&alice, &mallory.get_address(), 50, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let forged = mallory.sign(&tx.signing_digest());
+    tx.vin[0].signature = forged;
+    tx.id = tx.calculate_hash();
+
+    let expected = ChainError::InvalidTransaction {
+        txid: tx.id.clone(),
+        error: TxValidationError::BadSignature { index: 0 },
+    };
+    assert_eq!(chain.add_block(vec![tx], &mallory.get_address()).unwrap_err(), expected);
+}
+
+#[test]
+fn modifying_outputs_invalidates_signature() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mallory = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let mut tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    assert!(tx.verify(&chain.utxo_set).is_ok());
+
+    tx.vout[0].pub_key_hash = mallory.get_address();
+    assert_eq!(tx.verify(&chain.utxo_set), Err(TxValidationError::BadSignature { index: 0 }));
+}
+
+#[test]
+fn signatures_only_verify_in_lowercase_hex() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let mut tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let signature = tx.vin[0].signature.clone();
+    assert!(signature.bytes().any(|b| b.is_ascii_alphabetic()));
+    let message_signature = alice.sign(b"message");
+    assert!(verify_signature(&alice.public_key, b"message", &message_signature));
+    assert!(!verify_signature(&alice.public_key.to_uppercase(), b"message", &message_signature));
+
+    // The same signature with its letters flipped to uppercase would be a second txid
+    tx.vin[0].signature = signature.to_uppercase();
+    assert_eq!(tx.verify(&chain.utxo_set), Err(TxValidationError::BadSignature { index: 0 }));
+    tx.vin[0].signature = signature;
+    assert!(tx.verify(&chain.utxo_set).is_ok());
+}
+
+#[test]
+fn wallet_restores_from_secret_bytes() {
+    let wallet = Wallet::new();
+    let restored = Wallet::from_secret_bytes(&wallet.secret_bytes());
+    assert_eq!(restored.get_address(), wallet.get_address());
+}
+
+#[test]
+fn fixed_fee_is_collected_by_the_miner() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let miner = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(2), &chain.utxo_set)
+        .unwrap();
+    assert_eq!(tx.verify(&chain.utxo_set), Ok(2));
+    chain.add_block(vec![tx], &miner.get_address()).unwrap();
+
+    assert_eq!(chain.get_balance(&alice.get_address()), 18);
+    assert_eq!(chain.get_balance(&bob.get_address()), 30);
+    assert_eq!(chain.get_balance(&miner.get_address()), 52);
+    assert_eq!(chain.validate(), Ok(()));
+}
+
+#[test]
+fn per_byte_fee_covers_the_final_size() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let fee = Fee::PerByte(0.01);
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, fee, &chain.utxo_set)
+        .unwrap();
+    let paid = tx.verify(&chain.utxo_set).unwrap();
+    assert!(paid > 0);
+    assert!(paid >= i64::from(fee.for_size(tx.size()).unwrap()));
+}
+
+#[test]
+fn fees_that_are_negative_not_a_number_or_too_large_are_refused() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let cases = [
+        (Fee::Fixed(-1), ChainError::InvalidAmount(-1)),
+        (Fee::PerByte(-2.5), ChainError::InvalidAmount(-2)),
+        (Fee::PerByte(f64::NAN), ChainError::InvalidAmount(0)),
+        (Fee::PerByte(f64::INFINITY), ChainError::InvalidAmount(i32::MAX)),
+        (Fee::PerByte(1e12), ChainError::InvalidAmount(i32::MAX)),
+    ];
+    for (fee, expected) in cases {
+        let result = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, fee, &chain.utxo_set);
+        assert_eq!(result.unwrap_err(), expected, "{:?}", fee);
+    }
+    assert_eq!(Fee::PerByte(1e12).for_size(0), Ok(0));
+    assert_eq!(Fee::PerByte(0.5).for_size(3), Ok(2));
+}
+
+#[test]
+fn fee_counts_towards_required_funds() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let err = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 50, Fee::Fixed(1), &chain.utxo_set)
+        .unwrap_err();
+    assert_eq!(err, ChainError::InsufficientFunds { needed: 51, available: 50 });
+}
+
+#[test]
+fn coinbase_claiming_more_than_subsidy_plus_fees_is_invalid() {
+    let alice = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    chain.add_block(vec![], &alice.get_address()).unwrap();
+
+    // Inflate the reward and redo everything that commits to it
+    let block = &mut chain.blocks[1];
+    let coinbase = &mut block.transactions[0];
+    coinbase.vout[0].value = 60;
+    coinbase.id = coinbase.calculate_hash();
+    block.merkle_root = block.calculate_merkle_root();
+    block.hash = block.calculate_hash();
+    block.mine_block();
+
+    assert_eq!(
+        chain.validate(),
+        Err(ChainError::ExcessiveCoinbase { block: 1, claimed: 60, allowed: 50 })
+    );
+}
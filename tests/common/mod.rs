@@ -0,0 +1,9 @@
+//! Helpers for the tests that need a Postgres database
+//!
+//! Those tests are ignored by default. Point them at a database they may wipe and run
+//! them with `TEST_DATABASE_URL=postgresql://postgres@127.0.0.1:5432/rust101_test cargo test -- --ignored`.
+
+/// Postgres database the tests may wipe, from `TEST_DATABASE_URL`
+pub fn test_database_url() -> String {
+    std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must name a Postgres database the tests may wipe")
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::test_database_url;
+use rust101::db::{Database, DbConfig, DbError, DEFAULT_POOL_SIZE};
+use rust101::models::User;
+use uuid::Uuid;
+
+/// Create a user whose email, CNIC and wallet_id no other test run uses
+async fn new_user(db: &Database, tag: &str) -> User {
+    let id = Uuid::new_v4().simple().to_string();
+    db.create_user(
+        &format!("{}-{}@example.com", tag, id),
+        tag,
+        &format!("cnic-{}", id),
+        &format!("wallet-{}", id),
+        "pubkey",
+        "encrypted",
+    )
+    .await
+    .unwrap()
+}
+
+/// Environment with just these database variables set
+fn vars(url: Option<&'static str>, size: Option<&'static str>) -> impl Fn(&str) -> Option<String> {
+    move |name| match name {
+        "DATABASE_URL" => url.map(String::from),
+        "DATABASE_POOL_SIZE" => size.map(String::from),
+        _ => None,
+    }
+}
+
+#[test]
+fn config_is_read_from_environment_variables() {
+    let config = DbConfig::from_lookup(vars(Some("postgresql://db/app"), Some("4"))).unwrap();
+    assert_eq!(config, DbConfig { url: "postgresql://db/app".to_string(), pool_size: 4 });
+    let config = DbConfig::from_lookup(vars(Some("postgresql://db/app"), None)).unwrap();
+    assert_eq!(config.pool_size, DEFAULT_POOL_SIZE);
+
+    assert!(matches!(DbConfig::from_lookup(vars(None, Some("4"))), Err(DbError::Config(_))));
+    for size in ["0", "-1", "ten"] {
+        assert!(matches!(
+            DbConfig::from_lookup(vars(Some("postgresql://db/app"), Some(size))),
+            Err(DbError::Config(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn pages_out_of_range_are_refused_before_querying() {
+    // Nothing listens here; the pool only connects once a query needs it
+    let db = Database::connect(&DbConfig::new("postgresql://nobody@127.0.0.1:1/none")).unwrap();
+    for (page, per_page) in [(-1, 10), (0, 0), (0, -5), (i64::MAX, 2)] {
+        assert!(matches!(
+            db.list_users(page, per_page).await,
+            Err(DbError::InvalidPage { page: p, per_page: n }) if p == page && n == per_page
+        ));
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database: TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn users_are_found_updated_verified_and_soft_deleted() {
+    let url = test_database_url();
+    let db = Database::new(&DbConfig::new(url)).await.unwrap();
+    let alice = new_user(&db, "alice").await;
+    let bob = new_user(&db, "bob").await;
+    assert!(!alice.is_verified);
+
+    assert_eq!(db.get_user(alice.id).await.unwrap().email, alice.email);
+    assert_eq!(db.find_user_by_email(&alice.email).await.unwrap().unwrap().id, alice.id);
+    assert_eq!(db.find_user_by_wallet_id(&alice.wallet_id).await.unwrap().unwrap().id, alice.id);
+    assert_eq!(db.find_user_by_cnic(&alice.cnic).await.unwrap().unwrap().id, alice.id);
+    assert!(db.find_user_by_email("nobody@example.com").await.unwrap().is_none());
+
+    // Unique columns map to their own errors
+    let duplicate = db.create_user(&alice.email, "A", "other-cnic", "other-wallet", "pk", "sk").await;
+    assert!(matches!(duplicate, Err(DbError::DuplicateEmail(email)) if email == alice.email));
+    let duplicate = db.create_user("other@example.com", "A", &alice.cnic, "other-wallet", "pk", "sk").await;
+    assert!(matches!(duplicate, Err(DbError::DuplicateCnic(_))));
+    let duplicate = db.create_user("other@example.com", "A", "other-cnic", &alice.wallet_id, "pk", "sk").await;
+    assert!(matches!(duplicate, Err(DbError::DuplicateWalletId(_))));
+    assert!(matches!(db.update_email(bob.id, &alice.email).await, Err(DbError::DuplicateEmail(_))));
+
+    let renamed = db.update_full_name(alice.id, "Alice Liddell").await.unwrap();
+    assert_eq!(renamed.full_name, "Alice Liddell");
+    assert!(renamed.updated_at >= alice.updated_at);
+    let new_email = format!("liddell-{}", alice.email);
+    assert_eq!(db.update_email(alice.id, &new_email).await.unwrap().email, new_email);
+
+    assert!(db.mark_verified(alice.id).await.unwrap().is_verified);
+    assert!(matches!(db.mark_verified(alice.id).await, Err(DbError::AlreadyVerified(id)) if id == alice.id));
+
+    db.soft_delete_user(alice.id).await.unwrap();
+    assert!(matches!(db.get_user(alice.id).await, Err(DbError::UserNotFound(_))));
+    assert!(db.find_user_by_email(&new_email).await.unwrap().is_none());
+    assert!(matches!(db.soft_delete_user(alice.id).await, Err(DbError::UserNotFound(_))));
+    assert!(matches!(db.mark_verified(alice.id).await, Err(DbError::UserNotFound(_))));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database: TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn users_are_listed_page_by_page() {
+    let url = test_database_url();
+    let db = Database::new(&DbConfig::new(url)).await.unwrap();
+    let mut created = Vec::new();
+    for _ in 0..3 {
+        created.push(new_user(&db, "paged").await.id);
+    }
+
+    let mut listed = Vec::new();
+    for page in 0.. {
+        let users = db.list_users(page, 2).await.unwrap();
+        assert!(users.len() <= 2);
+        if users.is_empty() {
+            break;
+        }
+        listed.extend(users.into_iter().map(|user| user.id));
+    }
+    assert!(listed.len() as i64 >= db.count_users().await.unwrap() - 3);
+    let positions: Vec<usize> =
+        created.iter().map(|id| listed.iter().position(|listed| listed == id).unwrap()).collect();
+    assert!(positions.is_sorted());
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database: TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn wallet_balances_are_stored_per_user() {
+    let url = test_database_url();
+    let db = Database::new(&DbConfig::new(url)).await.unwrap();
+    let user = new_user(&db, "wallet").await;
+
+    let wallet = db.create_wallet(&user.wallet_id).await.unwrap();
+    assert_eq!(wallet.balance, 0);
+    assert!(matches!(db.create_wallet(&user.wallet_id).await, Err(DbError::DuplicateWallet(_))));
+    assert!(matches!(db.create_wallet("no-such-user").await, Err(DbError::Query(_))));
+
+    assert_eq!(db.update_wallet_balance(&user.wallet_id, 42).await.unwrap().balance, 42);
+    assert_eq!(db.get_wallet(&user.wallet_id).await.unwrap().unwrap().balance, 42);
+    assert!(db.get_wallet("no-such-wallet").await.unwrap().is_none());
+    assert!(matches!(
+        db.update_wallet_balance("no-such-wallet", 1).await,
+        Err(DbError::WalletNotFound(_))
+    ));
+}
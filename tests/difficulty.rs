@@ -0,0 +1,99 @@
+use rust101::chain::{Block, Blockchain, ChainError, ChainParams, Target, Wallet};
+
+/// Unmined blocks at the given timestamps, all carrying `target`
+fn blocks_at(timestamps: &[i64], target: Target) -> Vec<Block> {
+    timestamps
+        .iter()
+        .enumerate()
+        .map(|(id, &timestamp)| {
+            let mut block = Block::new(id as u64, String::new(), vec![], target);
+            block.timestamp = timestamp;
+            block
+        })
+        .collect()
+}
+
+#[test]
+fn leading_zero_targets_match_hex_prefixes() {
+    let target = Target::from_leading_zeros(2);
+    assert!(target.is_met_by(&format!("00{}", "f".repeat(62))));
+    assert!(!target.is_met_by(&format!("01{}", "0".repeat(62))));
+    assert!(!target.is_met_by("not a hash"));
+    assert_eq!(Target::from_leading_zeros(64), Target::from_be_bytes([0; 32]));
+}
+
+#[test]
+fn scaling_is_exact_and_saturates() {
+    let target = Target::from_leading_zeros(4);
+    assert_eq!(target.scale(1, 16), Target::from_leading_zeros(5));
+
+    let mut one = [0; 32];
+    one[31] = 1;
+    let mut shifted = [0; 32];
+    shifted[23] = 1;
+    assert_eq!(Target::from_be_bytes(one).scale(1 << 63, 1).scale(2, 1), Target::from_be_bytes(shifted));
+    assert_eq!(Target::from_be_bytes(shifted).scale(1, 1 << 63).scale(1, 2), Target::from_be_bytes(one));
+    assert_eq!(Target::MAX.scale(2, 1), Target::MAX);
+}
+
+#[test]
+fn retarget_follows_the_actual_window_time() {
+    let params = ChainParams {
+        retarget_interval: 4,
+        target_block_time: 10,
+        ..ChainParams::with_difficulty(1)
+    };
+    let start = Target::from_leading_zeros(3);
+
+    // Inside a window the target carries over
+    assert_eq!(params.next_target(&blocks_at(&[0, 1, 2], start)), start);
+
+    // On schedule: unchanged. Twice as fast: half the target.
+    assert_eq!(params.next_target(&blocks_at(&[0, 10, 20, 30], start)), start);
+    assert_eq!(params.next_target(&blocks_at(&[0, 5, 10, 15], start)), start.scale(1, 2));
+
+    // Far too slow: at most four times easier, never easier than the limit
+    assert_eq!(params.next_target(&blocks_at(&[0, 1_000, 2_000, 3_000], start)), start.scale(4, 1));
+    assert_eq!(params.next_target(&blocks_at(&[0, 1_000, 2_000, 3_000], params.pow_limit)), params.pow_limit);
+}
+
+#[test]
+fn extreme_timestamps_and_parameters_clamp_instead_of_overflowing() {
+    let params = ChainParams {
+        retarget_interval: 4,
+        target_block_time: 10,
+        ..ChainParams::with_difficulty(1)
+    };
+    let start = Target::from_leading_zeros(3);
+    assert_eq!(params.next_target(&blocks_at(&[i64::MIN, 0, 0, i64::MAX], start)), start.scale(4, 1));
+    assert_eq!(params.next_target(&blocks_at(&[i64::MAX, 0, 0, i64::MIN], start)), start.scale(30 / 4, 30));
+
+    let params = ChainParams {
+        retarget_interval: 4,
+        target_block_time: i64::MAX,
+        ..ChainParams::with_difficulty(1)
+    };
+    let expected = i64::MAX as u64;
+    assert_eq!(params.next_target(&blocks_at(&[0, 10, 20, 30], start)), start.scale(expected / 4, expected));
+}
+
+#[test]
+fn validation_recomputes_the_expected_target() {
+    let miner = Wallet::new();
+    let params = ChainParams { retarget_interval: 2, ..ChainParams::with_difficulty(1) };
+    let mut chain = Blockchain::with_params(params, &miner.get_address());
+    for _ in 0..3 {
+        chain.add_block(vec![], &miner.get_address()).unwrap();
+    }
+
+    // Blocks arrive far faster than ten minutes apart, so block 2 is harder
+    assert!(chain.blocks[2].target < chain.blocks[1].target);
+    assert_eq!(chain.validate(), Ok(()));
+
+    // Mining the tip against the easy genesis target is caught
+    let tip = chain.blocks.last_mut().unwrap();
+    tip.target = chain.params.pow_limit;
+    tip.hash = tip.calculate_hash();
+    tip.mine_block();
+    assert_eq!(chain.validate(), Err(ChainError::UnexpectedTarget(3)));
+}
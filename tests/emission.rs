@@ -0,0 +1,82 @@
+use rust101::chain::{Blockchain, ChainError, ChainParams, Fee, Transaction, Wallet};
+
+fn coinbase_value(chain: &Blockchain, height: usize) -> i32 {
+    chain.blocks[height].transactions[0].vout[0].value
+}
+
+#[test]
+fn subsidy_halves_every_interval() {
+    let params = ChainParams { initial_subsidy: 50, halving_interval: 2, max_supply: 1_000, ..ChainParams::with_difficulty(1) };
+    assert_eq!(
+        (0..7).map(|h| params.block_subsidy(h)).collect::<Vec<_>>(),
+        vec![50, 50, 25, 25, 12, 12, 6]
+    );
+    assert_eq!(params.block_subsidy(2 * 64), 0);
+}
+
+#[test]
+fn chain_follows_the_schedule_and_stops_at_max_supply() {
+    let miner = Wallet::new();
+    let params = ChainParams { initial_subsidy: 50, halving_interval: 2, max_supply: 140, ..ChainParams::with_difficulty(1) };
+    let mut chain = Blockchain::with_params(params, &miner.get_address());
+    for _ in 0..4 {
+        chain.add_block(vec![], &miner.get_address()).unwrap();
+    }
+
+    // 50 + 50 + 25 + 15 (capped) + 0
+    let rewards: Vec<i32> = (0..5).map(|h| coinbase_value(&chain, h)).collect();
+    assert_eq!(rewards, vec![50, 50, 25, 15, 0]);
+    assert_eq!(chain.total_supply(), Ok(140));
+    assert_eq!(chain.validate(), Ok(()));
+}
+
+#[test]
+fn fees_are_still_paid_after_the_subsidy_runs_out() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let miner = Wallet::new();
+    let params = ChainParams { initial_subsidy: 50, halving_interval: 100, max_supply: 50, ..ChainParams::with_difficulty(1) };
+    let mut chain = Blockchain::with_params(params, &alice.get_address());
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, Fee::Fixed(3), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx], &miner.get_address()).unwrap();
+
+    assert_eq!(coinbase_value(&chain, 1), 3);
+    assert_eq!(chain.total_supply(), Ok(50));
+    assert_eq!(chain.validate(), Ok(()));
+}
+
+#[test]
+fn coinbase_over_the_cap_is_rejected() {
+    let miner = Wallet::new();
+    let params = ChainParams { initial_subsidy: 50, halving_interval: 100, max_supply: 60, ..ChainParams::with_difficulty(1) };
+    let mut chain = Blockchain::with_params(params, &miner.get_address());
+    chain.add_block(vec![], &miner.get_address()).unwrap();
+    assert_eq!(coinbase_value(&chain, 1), 10);
+
+    // Claim the full 50 the halving schedule alone would allow
+    let block = &mut chain.blocks[1];
+    let coinbase = &mut block.transactions[0];
+    coinbase.vout[0].value = 50;
+    coinbase.id = coinbase.calculate_hash();
+    block.merkle_root = block.calculate_merkle_root();
+    block.hash = block.calculate_hash();
+    block.mine_block();
+
+    assert_eq!(
+        chain.validate(),
+        Err(ChainError::ExcessiveCoinbase { block: 1, claimed: 50, allowed: 10 })
+    );
+}
+
+#[test]
+fn supply_tracks_disconnected_blocks() {
+    let miner = Wallet::new();
+    let mut chain = Blockchain::new(1, &miner.get_address());
+    chain.add_block(vec![], &miner.get_address()).unwrap();
+    assert_eq!(chain.total_supply(), Ok(100));
+
+    chain.disconnect_tip().unwrap().unwrap();
+    assert_eq!(chain.total_supply(), Ok(50));
+}
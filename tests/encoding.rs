@@ -0,0 +1,70 @@
+use rust101::chain::{
+    write_compact_size, Block, Blockchain, Decode, DecodeError, Encode, Fee, Reader, TXOutput,
+    Transaction, Wallet,
+};
+
+fn chain_with_payment() -> Blockchain {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 20, Fee::Fixed(1), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx], &alice.get_address()).unwrap();
+    chain
+}
+
+#[test]
+fn outputs_have_a_fixed_layout() {
+    // value i32 LE | length | UTF-8 bytes
+    assert_eq!(TXOutput::new(5, "ab").encode(), vec![5, 0, 0, 0, 2, b'a', b'b']);
+}
+
+#[test]
+fn transactions_and_blocks_round_trip() {
+    let chain = chain_with_payment();
+    let block = chain.get_latest_block();
+    let payment = &block.transactions[1];
+
+    let bytes = payment.encode();
+    assert_eq!(bytes.len(), payment.size());
+    assert_eq!(Transaction::decode(&bytes).unwrap(), *payment);
+
+    let decoded = Block::decode(&block.encode()).unwrap();
+    assert_eq!(decoded, *block);
+    assert_eq!(decoded.encode(), block.encode());
+}
+
+#[test]
+fn txid_is_the_hash_of_the_encoding() {
+    let chain = chain_with_payment();
+    let mut tx = chain.get_latest_block().transactions[1].clone();
+    let original = tx.id.clone();
+
+    // Any field change moves the id; the id itself is not part of the bytes
+    tx.id = String::from("anything");
+    assert_eq!(tx.calculate_hash(), original);
+    tx.timestamp += 1;
+    assert_ne!(tx.calculate_hash(), original);
+}
+
+#[test]
+fn malformed_bytes_are_rejected() {
+    let chain = chain_with_payment();
+    let bytes = chain.get_latest_block().transactions[1].encode();
+
+    assert_eq!(Transaction::decode(&bytes[..bytes.len() - 1]), Err(DecodeError::UnexpectedEnd));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Transaction::decode(&trailing), Err(DecodeError::TrailingBytes(1)));
+
+    let mut future = bytes.clone();
+    future[0] = 2;
+    assert_eq!(Transaction::decode(&future), Err(DecodeError::UnsupportedVersion(2)));
+
+    // A count that would fit in one byte must not use the three-byte form
+    let mut padded = Vec::new();
+    write_compact_size(&mut padded, 0xfd);
+    assert_eq!(padded, vec![0xfd, 0xfd, 0x00]);
+    assert_eq!(Reader::new(&[0xfd, 0x05, 0x00]).compact_size(), Err(DecodeError::NonCanonicalSize(5)));
+}
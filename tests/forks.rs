@@ -0,0 +1,150 @@
+use rust101::chain::{
+    Block, BlockStatus, Blockchain, ChainError, ChainEvent, Mempool, OutPoint, TXInput, TXOutput,
+    Transaction, Wallet,
+};
+
+/// Mine a block on top of `parent` paying `reward` to `miner`, with extra transactions
+fn fork_block(chain: &Blockchain, parent: &Block, miner: &str, reward: i32, txs: Vec<Transaction>) -> Block {
+    let height = parent.id + 1;
+    let coinbase = Transaction::new_coinbase(miner, reward, Some(format!("Fork block {} to {}", height, miner)));
+    let mut transactions = vec![coinbase];
+    transactions.extend(txs);
+    let mut block = Block::new(height, parent.hash.clone(), transactions, chain.next_target());
+    block.timestamp = parent.timestamp + 1;
+    block.mine_block();
+    block
+}
+
+/// Alice pays everything she got from genesis to `to`
+fn pay_genesis(chain: &Blockchain, alice: &Wallet, to: &str) -> Transaction {
+    let genesis = OutPoint::new(chain.blocks[0].transactions[0].id.clone(), 0);
+    let mut tx = Transaction {
+        id: String::new(),
+        vin: vec![TXInput::new(genesis.txid, genesis.vout, String::new(), alice.public_key.clone())],
+        vout: vec![TXOutput::new(50, to)],
+        timestamp: 1,
+    };
+    tx.sign(alice);
+    tx.id = tx.calculate_hash();
+    tx
+}
+
+#[test]
+fn heavier_branch_wins_and_replays_the_utxo_set() {
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let carol = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    let genesis = chain.blocks[0].clone();
+
+    // Active chain: alice pays bob
+    chain.add_block(vec![pay_genesis(&chain, &alice, &bob)], "miner").unwrap();
+    assert_eq!(chain.get_balance(&bob), 50);
+
+    // Competing branch: alice pays carol instead. Equal work is not enough to switch.
+    let side_1 = fork_block(&chain, &genesis, "rival", 50, vec![pay_genesis(&chain, &alice, &carol)]);
+    assert_eq!(chain.submit_block(side_1.clone()), Ok(BlockStatus::SideBranch));
+    assert_eq!(chain.submit_block(side_1.clone()), Ok(BlockStatus::AlreadyKnown));
+    assert_eq!(chain.get_balance(&bob), 50);
+
+    let mut events = chain.subscribe();
+    let side_2 = fork_block(&chain, &side_1, "rival", 50, vec![]);
+    assert_eq!(
+        chain.submit_block(side_2.clone()),
+        Ok(BlockStatus::Reorganized { disconnected: 1, connected: 2 })
+    );
+
+    assert_eq!(chain.get_latest_block().hash, side_2.hash);
+    assert_eq!(chain.get_balance(&bob), 0);
+    assert_eq!(chain.get_balance(&carol), 50);
+    assert_eq!(chain.total_supply(), Ok(150));
+    assert_eq!(chain.validate(), Ok(()));
+
+    // The old tip is still known, just not active
+    let old_tip = match events.try_recv().unwrap() {
+        ChainEvent::BlockDisconnected(block) => block,
+        other => panic!("expected a disconnect, got {:?}", other),
+    };
+    assert!(chain.get_block(&old_tip.hash).is_some());
+    assert_eq!(events.try_recv().unwrap(), ChainEvent::BlockConnected(side_1));
+    assert_eq!(events.try_recv().unwrap(), ChainEvent::BlockConnected(side_2.clone()));
+    assert_eq!(
+        events.try_recv().unwrap(),
+        ChainEvent::Reorg {
+            fork_height: 0,
+            old_tip: old_tip.hash,
+            new_tip: side_2.hash,
+            disconnected: 1,
+            connected: 2,
+        }
+    );
+}
+
+#[test]
+fn invalid_branch_is_discarded_and_old_chain_kept() {
+    let miner = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &miner);
+    let genesis = chain.blocks[0].clone();
+    chain.add_block(vec![], &miner).unwrap();
+    let tip = chain.get_latest_block().hash.clone();
+
+    // Claims more than the subsidy, but that only shows once the branch is connected
+    let greedy = fork_block(&chain, &genesis, "rival", 500, vec![]);
+    assert_eq!(chain.submit_block(greedy.clone()), Ok(BlockStatus::SideBranch));
+    let child = fork_block(&chain, &greedy, "rival", 50, vec![]);
+    assert!(matches!(chain.submit_block(child.clone()), Err(ChainError::ExcessiveCoinbase { block: 1, .. })));
+
+    assert_eq!(chain.get_latest_block().hash, tip);
+    assert!(chain.get_block(&greedy.hash).is_none() && chain.get_block(&child.hash).is_none());
+    assert_eq!(chain.total_supply(), Ok(100));
+    assert_eq!(chain.validate(), Ok(()));
+}
+
+#[test]
+fn blocks_must_fit_the_tree() {
+    let miner = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &miner);
+    let genesis = chain.blocks[0].clone();
+
+    let mut orphan = fork_block(&chain, &genesis, "rival", 50, vec![]);
+    orphan.previous_hash = "f".repeat(64);
+    orphan.hash = orphan.calculate_hash();
+    orphan.mine_block();
+    assert_eq!(chain.submit_block(orphan.clone()), Err(ChainError::UnknownParent(orphan.hash)));
+
+    let mut wrong_height = fork_block(&chain, &genesis, "rival", 50, vec![]);
+    wrong_height.id = 5;
+    wrong_height.hash = wrong_height.calculate_hash();
+    wrong_height.mine_block();
+    assert_eq!(chain.submit_block(wrong_height), Err(ChainError::InvalidHeight(5)));
+}
+
+#[test]
+fn subscribers_readmit_transactions_from_disconnected_blocks() {
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    let genesis = chain.blocks[0].clone();
+    let mut mempool = Mempool::default();
+    let payment = pay_genesis(&chain, &alice, &bob);
+    chain.add_block(vec![payment.clone()], "miner").unwrap();
+
+    let mut events = chain.subscribe();
+    let side_1 = fork_block(&chain, &genesis, "rival", 50, vec![]);
+    let side_2 = fork_block(&chain, &side_1, "rival", 50, vec![]);
+    chain.submit_block(side_1).unwrap();
+    chain.submit_block(side_2).unwrap();
+
+    while let Ok(event) = events.try_recv() {
+        match event {
+            ChainEvent::BlockDisconnected(block) => {
+                mempool.readmit_block(&block, &chain.utxo_set);
+            }
+            ChainEvent::BlockConnected(block) => {
+                mempool.remove_for_block(&block);
+            }
+            ChainEvent::Reorg { .. } => {}
+        }
+    }
+    assert!(mempool.contains(&payment.id));
+}
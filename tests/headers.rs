@@ -0,0 +1,72 @@
+use rust101::chain::{
+    validate_header_chain, BlockHeader, Blockchain, ChainError, Decode, Encode, MAX_FUTURE_BLOCK_TIME,
+};
+
+fn chain_of(blocks: usize) -> Blockchain {
+    let miner = "miner";
+    let mut chain = Blockchain::new(2, miner);
+    for _ in 0..blocks {
+        chain.add_block(vec![], miner).unwrap();
+    }
+    chain
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[test]
+fn header_chain_validates_without_bodies() {
+    let chain = chain_of(3);
+    let headers = chain.headers();
+
+    assert_eq!(headers[2].hash(), chain.blocks[2].hash);
+    assert_eq!(BlockHeader::decode(&headers[2].encode()).unwrap(), headers[2]);
+    assert_eq!(validate_header_chain(&chain.params, &headers, now()), Ok(()));
+}
+
+#[test]
+fn broken_links_and_work_are_caught() {
+    let chain = chain_of(3);
+
+    let mut unlinked = chain.headers();
+    unlinked[2].previous_hash = unlinked[0].hash();
+    assert_eq!(
+        validate_header_chain(&chain.params, &unlinked, now()),
+        Err(ChainError::InvalidPreviousHash(2))
+    );
+
+    // Move the tip's nonce until its hash no longer meets the target
+    let mut unworked = chain.headers();
+    let tip = unworked.last_mut().unwrap();
+    while tip.meets_target() {
+        tip.nonce += 1;
+    }
+    assert_eq!(
+        validate_header_chain(&chain.params, &unworked, now()),
+        Err(ChainError::InvalidProofOfWork(3))
+    );
+}
+
+#[test]
+fn timestamps_must_advance_and_not_run_ahead() {
+    let mut chain = chain_of(2);
+
+    // Block 2 stamped at genesis time is not after the median of blocks 0 and 1
+    let genesis_time = chain.blocks[0].timestamp;
+    let block = &mut chain.blocks[2];
+    block.timestamp = genesis_time;
+    block.mine_block();
+    assert_eq!(
+        validate_header_chain(&chain.params, &chain.headers(), now()),
+        Err(ChainError::TimestampTooEarly(2))
+    );
+
+    // A node whose clock is far behind sees every header as from the future
+    let headers = chain_of(1).headers();
+    let behind = headers[0].timestamp - MAX_FUTURE_BLOCK_TIME - 1;
+    assert_eq!(
+        validate_header_chain(&chain.params, &headers, behind),
+        Err(ChainError::TimestampTooFarAhead(0))
+    );
+}
@@ -0,0 +1,205 @@
+use rust101::chain::{
+    Blockchain, Mempool, MempoolError, OutPoint, TXInput, TXOutput, Transaction, Wallet,
+};
+
+/// Build and sign a transaction spending `inputs` (all owned by `owner`)
+fn signed_tx(owner: &Wallet, inputs: Vec<OutPoint>, outputs: Vec<TXOutput>) -> Transaction {
+    let vin = inputs
+        .into_iter()
+        .map(|o| TXInput::new(o.txid, o.vout, String::new(), owner.public_key.clone()))
+        .collect();
+    let mut tx = Transaction { id: String::new(), vin, vout: outputs, timestamp: 1 };
+    tx.sign(owner);
+    tx.id = tx.calculate_hash();
+    tx
+}
+
+/// Chain whose second block splits the genesis reward into five coins of 10 for `owner`
+fn chain_with_coins(owner: &Wallet) -> (Blockchain, Vec<OutPoint>) {
+    let address = owner.get_address();
+    let mut chain = Blockchain::new(1, &address);
+    let genesis = OutPoint::new(chain.blocks[0].transactions[0].id.clone(), 0);
+    let split = signed_tx(owner, vec![genesis], (0..5).map(|_| TXOutput::new(10, &address)).collect());
+    let coins = (0..5).map(|vout| OutPoint::new(split.id.clone(), vout)).collect();
+    chain.add_block(vec![split], "miner").unwrap();
+    (chain, coins)
+}
+
+#[test]
+fn template_orders_by_fee_rate_with_parents_first() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (chain, coins) = chain_with_coins(&alice);
+    let mut mempool = Mempool::default();
+
+    let cheap = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let rich = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(5, &bob.get_address())]);
+    // Child of the cheap transaction, paying a big fee on an unconfirmed output
+    let child = signed_tx(&bob, vec![OutPoint::new(cheap.id.clone(), 0)], vec![TXOutput::new(1, &alice.get_address())]);
+
+    mempool.add(cheap.clone(), &chain.utxo_set).unwrap();
+    mempool.add(rich.clone(), &chain.utxo_set).unwrap();
+    assert_eq!(mempool.add(child.clone(), &chain.utxo_set).unwrap().fee, 8);
+
+    let ids: Vec<_> = mempool.block_template(usize::MAX).into_iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![rich.id, cheap.id, child.id]);
+}
+
+#[test]
+fn conflicts_need_a_higher_fee_to_replace() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (chain, coins) = chain_with_coins(&alice);
+    let mut mempool = Mempool::default();
+
+    let original = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(8, &bob.get_address())]);
+    let child = signed_tx(&bob, vec![OutPoint::new(original.id.clone(), 0)], vec![TXOutput::new(7, &bob.get_address())]);
+    mempool.add(original.clone(), &chain.utxo_set).unwrap();
+    mempool.add(child.clone(), &chain.utxo_set).unwrap();
+
+    // Same fee as the original alone is not enough
+    let cheaper = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(8, &alice.get_address())]);
+    assert!(matches!(
+        mempool.add(cheaper, &chain.utxo_set),
+        Err(MempoolError::ReplacementTooCheap { required_fee: 3, .. })
+    ));
+
+    // Outbidding the original and its child evicts both
+    let bump = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(6, &alice.get_address())]);
+    let outcome = mempool.add(bump.clone(), &chain.utxo_set).unwrap();
+    assert_eq!(outcome.replaced.len(), 2);
+    assert!(!mempool.contains(&original.id) && !mempool.contains(&child.id));
+    assert!(mempool.contains(&bump.id));
+}
+
+#[test]
+fn full_pool_evicts_lowest_fee_rate() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (chain, coins) = chain_with_coins(&alice);
+
+    let low = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let mid = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(7, &bob.get_address())]);
+    let high = signed_tx(&alice, vec![coins[2].clone()], vec![TXOutput::new(5, &bob.get_address())]);
+    let mut mempool = Mempool::new(low.size() + mid.size());
+
+    mempool.add(low.clone(), &chain.utxo_set).unwrap();
+    mempool.add(mid.clone(), &chain.utxo_set).unwrap();
+
+    let outcome = mempool.add(high.clone(), &chain.utxo_set).unwrap();
+    assert_eq!(outcome.evicted, vec![low.clone()]);
+    assert!(mempool.size() <= low.size() + mid.size());
+
+    // Nothing cheaper than what is already there gets in
+    let lowest = signed_tx(&alice, vec![coins[3].clone()], vec![TXOutput::new(10, &bob.get_address())]);
+    assert!(matches!(mempool.add(lowest, &chain.utxo_set), Err(MempoolError::PoolFull(_))));
+    assert_eq!(mempool.len(), 2);
+}
+
+#[test]
+fn full_pool_never_evicts_the_parent_of_the_transaction_it_makes_room_for() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (mut chain, coins) = chain_with_coins(&alice);
+
+    let parent = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let mid = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(7, &bob.get_address())]);
+    let mut mempool = Mempool::new(parent.size() + mid.size());
+    mempool.add(parent.clone(), &chain.utxo_set).unwrap();
+    mempool.add(mid.clone(), &chain.utxo_set).unwrap();
+
+    // The parent is the cheapest entry, but the child needs it
+    let child = signed_tx(&bob, vec![OutPoint::new(parent.id.clone(), 0)], vec![TXOutput::new(1, &bob.get_address())]);
+    let outcome = mempool.add(child.clone(), &chain.utxo_set).unwrap();
+    assert_eq!(outcome.evicted, vec![mid]);
+    assert!(mempool.contains(&parent.id) && mempool.contains(&child.id));
+    chain.add_block(mempool.block_template(usize::MAX), "miner").unwrap();
+
+    // With only its own ancestors cheaper than it, a newcomer is refused
+    let (chain, coins) = chain_with_coins(&alice);
+    let parent = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let child = signed_tx(&bob, vec![OutPoint::new(parent.id.clone(), 0)], vec![TXOutput::new(1, &bob.get_address())]);
+    let mut mempool = Mempool::new(parent.size());
+    mempool.add(parent.clone(), &chain.utxo_set).unwrap();
+    assert!(matches!(mempool.add(child, &chain.utxo_set), Err(MempoolError::PoolFull(_))));
+    assert!(mempool.contains(&parent.id));
+}
+
+#[test]
+fn mined_transactions_leave_and_disconnected_ones_return() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (mut chain, coins) = chain_with_coins(&alice);
+    let mut mempool = Mempool::default();
+
+    let tx = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    mempool.add(tx.clone(), &chain.utxo_set).unwrap();
+    assert!(matches!(mempool.add(tx.clone(), &chain.utxo_set), Err(MempoolError::AlreadyKnown(_))));
+
+    chain.mine_from_mempool(&mut mempool, "miner").unwrap();
+    assert!(mempool.is_empty());
+    assert_eq!(chain.get_balance(&bob.get_address()), 9);
+    assert_eq!(chain.get_balance("miner"), 50 + 50 + 1);
+
+    let block = chain.disconnect_tip().unwrap().unwrap();
+    assert_eq!(mempool.readmit_block(&block, &chain.utxo_set), 1);
+    assert!(mempool.contains(&tx.id));
+    assert!(chain.is_chain_valid());
+
+    // Without the block that created its coin the transaction cannot be mined
+    chain.disconnect_tip().unwrap().unwrap();
+    let dropped = mempool.remove_unspendable(&chain.utxo_set);
+    assert_eq!(dropped.iter().map(|tx| tx.id.as_str()).collect::<Vec<_>>(), [tx.id.as_str()]);
+    assert!(mempool.is_empty());
+}
+
+#[test]
+fn revalidating_drops_whatever_the_chain_moved_past() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (mut chain, coins) = chain_with_coins(&alice);
+    let mut mempool = Mempool::default();
+
+    let mined = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let child = signed_tx(&bob, vec![OutPoint::new(mined.id.clone(), 0)], vec![TXOutput::new(8, &bob.get_address())]);
+    let conflicted = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let pending = signed_tx(&alice, vec![coins[2].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    for tx in [&mined, &child, &conflicted, &pending] {
+        mempool.add(tx.clone(), &chain.utxo_set).unwrap();
+    }
+
+    // A block the pool never heard of confirms one entry and double-spends another
+    let double_spend = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(10, &alice.get_address())]);
+    chain.add_block(vec![mined.clone(), double_spend], "miner").unwrap();
+
+    let dropped: Vec<_> = mempool.revalidate(&chain.utxo_set).into_iter().map(|tx| tx.id).collect();
+    assert_eq!(dropped.len(), 2);
+    assert!(dropped.contains(&mined.id) && dropped.contains(&conflicted.id));
+    assert!(mempool.contains(&child.id) && mempool.contains(&pending.id));
+    chain.add_block(mempool.block_template(usize::MAX), "miner").unwrap();
+}
+
+#[test]
+fn a_rich_child_pulls_its_parent_into_the_template_and_revalidating_keeps_admission_order() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let (chain, coins) = chain_with_coins(&alice);
+    let mut mempool = Mempool::default();
+
+    let older = signed_tx(&alice, vec![coins[0].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let parent = signed_tx(&alice, vec![coins[1].clone()], vec![TXOutput::new(9, &bob.get_address())]);
+    let child = signed_tx(&bob, vec![OutPoint::new(parent.id.clone(), 0)], vec![TXOutput::new(1, &bob.get_address())]);
+    for tx in [&older, &parent, &child] {
+        mempool.add(tx.clone(), &chain.utxo_set).unwrap();
+    }
+
+    // Parent and child together pay more per byte than the older entry alone
+    let ids: Vec<_> = mempool.block_template(usize::MAX).into_iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![parent.id.clone(), child.id.clone(), older.id.clone()]);
+
+    // On their own the two equal-rate entries still go oldest first
+    assert!(mempool.revalidate(&chain.utxo_set).is_empty());
+    mempool.remove(&child.id);
+    let ids: Vec<_> = mempool.block_template(usize::MAX).into_iter().map(|tx| tx.id).collect();
+    assert_eq!(ids, vec![older.id, parent.id]);
+}
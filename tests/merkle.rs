@@ -0,0 +1,93 @@
+use rust101::chain::{
+    Block, BlockStatus, Blockchain, ChainError, Decode, Encode, Fee, MerkleProof, Transaction, Wallet,
+};
+
+/// A chain and an unsubmitted block holding a coinbase and two payments, so the
+/// bottom level of its merkle tree has an odd number of leaves
+fn chain_and_block() -> (Blockchain, Block) {
+    let alice = Wallet::new();
+    let carol = Wallet::new();
+    let bob = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    chain.add_block(vec![], &carol.get_address()).unwrap();
+
+    let payments = vec![
+        Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, Fee::Fixed(1), &chain.utxo_set).unwrap(),
+        Transaction::new_utxo_transaction(&carol, &bob.get_address(), 20, Fee::Fixed(1), &chain.utxo_set).unwrap(),
+    ];
+    let mut block = chain.prepare_block(payments, &alice.get_address()).unwrap();
+    block.mine_block();
+    (chain, block)
+}
+
+#[test]
+fn every_transaction_proves_against_the_header() {
+    let (_, block) = chain_and_block();
+    let header = block.header();
+
+    for tx in &block.transactions {
+        let proof = block.merkle_proof(&tx.id).unwrap();
+        assert!(proof.verify(&header.merkle_root));
+
+        assert_eq!(MerkleProof::decode(&proof.encode()).unwrap(), proof);
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(serde_json::from_str::<MerkleProof>(&json).unwrap(), proof);
+    }
+    assert_eq!(block.merkle_proof("not in the block"), None);
+}
+
+#[test]
+fn tampered_proofs_do_not_verify() {
+    let (_, block) = chain_and_block();
+    let root = &block.merkle_root;
+    let proof = block.merkle_proof(&block.transactions[1].id).unwrap();
+
+    let mut moved = proof.clone();
+    moved.index = 0;
+    assert!(!moved.verify(root));
+
+    let mut resized = proof.clone();
+    resized.leaf_count = 8;
+    assert!(!resized.verify(root));
+
+    let mut extended = proof.clone();
+    extended.siblings.push(Some(block.hash.clone()));
+    assert!(!extended.verify(root));
+
+    let mut swapped = proof.clone();
+    swapped.siblings[0] = Some(block.transactions[2].id.clone());
+    assert!(!swapped.verify(root));
+}
+
+#[test]
+fn duplicated_last_leaf_cannot_pose_as_a_real_one() {
+    let (_, block) = chain_and_block();
+    let last = &block.transactions[2].id;
+    let proof = block.merkle_proof(last).unwrap();
+    assert_eq!(proof.siblings[0], None);
+
+    // In a four-leaf tree whose last two leaves are equal, the same root comes out
+    let forged = MerkleProof {
+        txid: last.clone(),
+        index: 3,
+        leaf_count: 4,
+        siblings: vec![Some(last.clone()), proof.siblings[1].clone()],
+    };
+    assert_eq!(forged.root(), None);
+    assert!(!forged.verify(&block.merkle_root));
+}
+
+#[test]
+fn blocks_repeating_their_last_transaction_are_rejected() {
+    let (mut chain, block) = chain_and_block();
+
+    // Repeating the last transaction keeps the merkle root and so the block hash
+    let mut mutated = block.clone();
+    mutated.transactions.push(block.transactions[2].clone());
+    assert_eq!(mutated.calculate_merkle_root(), block.merkle_root);
+    assert_eq!(mutated.calculate_hash(), block.hash);
+
+    assert_eq!(chain.submit_block(mutated), Err(ChainError::MutatedMerkleTree(2)));
+    // Rejecting the mutated copy must not poison the real block
+    assert_eq!(chain.submit_block(block), Ok(BlockStatus::Connected));
+}
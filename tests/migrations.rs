@@ -0,0 +1,99 @@
+mod common;
+
+use common::test_database_url;
+use deadpool_postgres::Pool;
+use rust101::migrations::{self, MigrationError, MIGRATIONS};
+
+fn pool(url: &str) -> Pool {
+    let mut cfg = deadpool_postgres::Config::new();
+    cfg.url = Some(url.to_string());
+    cfg.create_pool(Some(deadpool_postgres::Runtime::Tokio1), tokio_postgres::NoTls)
+        .unwrap()
+}
+
+async fn tables(pool: &Pool) -> Vec<String> {
+    let client = pool.get().await.unwrap();
+    client
+        .query(
+            "SELECT table_name::text FROM information_schema.tables WHERE table_schema = 'public' ORDER BY 1",
+            &[],
+        )
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect()
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database: TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn fresh_database_migrates_up_and_down() {
+    let url = test_database_url();
+    let pool = pool(&url);
+    pool.get()
+        .await
+        .unwrap()
+        .batch_execute("DROP SCHEMA public CASCADE; CREATE SCHEMA public")
+        .await
+        .unwrap();
+
+    // Stepping up to a version, then the rest; a second run has nothing to do
+    assert_eq!(migrations::migrate_up(&pool, Some(1)).await.unwrap(), vec![1]);
+    assert_eq!(migrations::migrate_up(&pool, None).await.unwrap(), vec![2, 3, 4, 5]);
+    assert_eq!(migrations::migrate_up(&pool, None).await.unwrap(), Vec::<i64>::new());
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.len(), MIGRATIONS.len());
+    assert!(status.iter().all(|status| status.applied_at.is_some()));
+
+    // The users and wallets the database examples write to exist now
+    let client = pool.get().await.unwrap();
+    let row = client
+        .query_one(
+            "INSERT INTO users (email, full_name, cnic, wallet_id, public_key, encrypted_private_key)
+             VALUES ('a@example.com', 'A', '1', 'wallet_a', 'pub', 'priv') RETURNING is_verified",
+            &[],
+        )
+        .await
+        .unwrap();
+    assert!(!row.get::<_, bool>(0));
+    client
+        .execute("INSERT INTO wallets (wallet_id, balance) VALUES ('wallet_a', 12)", &[])
+        .await
+        .unwrap();
+    assert!(client
+        .execute("INSERT INTO wallets (wallet_id) VALUES ('nobody')", &[])
+        .await
+        .is_err());
+    drop(client);
+
+    assert_eq!(migrations::migrate_down(&pool, 1).await.unwrap(), vec![5, 4, 3, 2]);
+    assert_eq!(tables(&pool).await, vec!["schema_migrations", "users"]);
+    assert!(matches!(
+        migrations::migrate_down(&pool, 7).await,
+        Err(MigrationError::NoSuchVersion(7))
+    ));
+    assert_eq!(migrations::migrate_down(&pool, 0).await.unwrap(), vec![1]);
+    assert_eq!(tables(&pool).await, vec!["schema_migrations"]);
+
+    // A database migrated by a newer build is left alone
+    pool.get()
+        .await
+        .unwrap()
+        .execute("INSERT INTO schema_migrations (version, name) VALUES (99, 'from_the_future')", &[])
+        .await
+        .unwrap();
+    assert!(matches!(
+        migrations::migrate_up(&pool, None).await,
+        Err(MigrationError::UnknownVersion(99))
+    ));
+    assert_eq!(migrations::status(&pool).await.unwrap().last().unwrap().name, "from_the_future");
+
+    // Leave the database fully migrated for the other tests
+    pool.get()
+        .await
+        .unwrap()
+        .execute("DELETE FROM schema_migrations WHERE version = 99", &[])
+        .await
+        .unwrap();
+    assert_eq!(migrations::migrate_up(&pool, None).await.unwrap().len(), MIGRATIONS.len());
+}
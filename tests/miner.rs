@@ -0,0 +1,74 @@
+use std::thread;
+use std::time::Duration;
+
+use rust101::chain::{
+    Block, BlockStatus, Blockchain, Miner, MiningHandle, Target, Transaction, Wallet, HEADER_SIZE,
+};
+
+/// A block no nonce can satisfy: only an all-zero hash meets a zero target
+fn impossible_block() -> Block {
+    let coinbase = Transaction::new_coinbase("miner", 50, None);
+    Block::new(1, "0".repeat(64), vec![coinbase], Target::from_be_bytes([0; 32]))
+}
+
+#[test]
+fn threads_find_a_nonce_the_chain_accepts() {
+    let miner = Wallet::new().get_address();
+    let mut chain = Blockchain::new(3, &miner);
+    let mut block = chain.prepare_block(vec![], &miner).unwrap();
+
+    let report = Miner::new(4).mine(&mut block, &MiningHandle::new());
+    assert_eq!(report.nonce, Some(block.nonce));
+    assert!(report.hashes > 0);
+    assert!(block.meets_target());
+    assert_eq!(block.hash, block.calculate_hash());
+    assert_eq!(chain.submit_block(block), Ok(BlockStatus::Connected));
+}
+
+#[test]
+fn header_is_fixed_size_with_the_nonce_last() {
+    let mut block = impossible_block();
+    let before = block.header_bytes();
+    block.nonce = 0x0102_0304_0506_0708;
+    let after = block.header_bytes();
+
+    assert_eq!(after.len(), HEADER_SIZE);
+    assert_eq!(before[..HEADER_SIZE - 8], after[..HEADER_SIZE - 8]);
+    assert_eq!(after[HEADER_SIZE - 8..], block.nonce.to_le_bytes());
+}
+
+#[test]
+fn cancelling_stops_every_worker() {
+    let handle = MiningHandle::new();
+    let worker_handle = handle.clone();
+    let mining = thread::spawn(move || {
+        let mut block = impossible_block();
+        Miner::new(2).mine(&mut block, &worker_handle)
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    handle.cancel();
+    let report = mining.join().unwrap();
+
+    assert!(!report.found());
+    assert!(report.hashes > 0 && report.hashrate() > 0.0);
+    assert_eq!(handle.hashes(), report.hashes);
+}
+
+#[tokio::test]
+async fn a_new_tip_cancels_async_mining() {
+    let miner = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &miner);
+
+    let handle = MiningHandle::new();
+    let watcher = handle.cancel_on_new_tip(chain.subscribe());
+    let mining = tokio::spawn(Miner::new(2).mine_async(impossible_block(), handle.clone()));
+
+    // Someone else extends the chain while we are still mining
+    chain.add_block(vec![], &miner).unwrap();
+
+    let (block, report) = mining.await.unwrap();
+    watcher.await.unwrap();
+    assert!(handle.is_cancelled());
+    assert!(!report.found() && !block.meets_target());
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use rust101::chain::{Block, Blockchain, ChainParams, Fee, Transaction, Wallet};
+use rust101::p2p::{
+    encode_frame, read_message, write_message, Inventory, Message, Node, NodeConfig, NodeEvent, P2pError, Version,
+    MAX_FRAME_SIZE, PROTOCOL_VERSION,
+};
+
+const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+
+/// A chain whose genesis pays `address`; nodes built from the same address share it
+fn shared_chain(address: &str) -> Blockchain {
+    let params = ChainParams::with_difficulty(1);
+    let genesis = Block::genesis(&params, address, GENESIS_TIMESTAMP);
+    Blockchain::with_genesis(params, genesis).unwrap()
+}
+
+/// A node listening on a free localhost port
+async fn listening_node(chain: Blockchain) -> (Node, String) {
+    let node = Node::start(chain, NodeConfig::default()).unwrap();
+    let addr = node.listen("127.0.0.1:0").await.unwrap();
+    (node, addr.to_string())
+}
+
+/// Poll `check` until it holds, failing the test after a few seconds
+async fn eventually(what: &str, mut check: impl AsyncFnMut() -> bool) {
+    let waited = tokio::time::timeout(Duration::from_secs(10), async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "timed out waiting for {}", what);
+}
+
+async fn height(node: &Node) -> u64 {
+    node.chain(|chain| chain.get_latest_block().id).await.unwrap()
+}
+
+#[tokio::test]
+async fn every_message_survives_a_frame_and_corrupt_frames_are_rejected() {
+    let chain = shared_chain("alice");
+    let genesis = chain.blocks[0].clone();
+    let hash = genesis.hash.clone();
+    let messages = vec![
+        Message::Version(Version {
+            version: PROTOCOL_VERSION,
+            genesis_hash: hash.clone(),
+            best_height: 7,
+            nonce: 42,
+            listen_port: 8333,
+            user_agent: "/test/".to_string(),
+            timestamp: GENESIS_TIMESTAMP,
+        }),
+        Message::Verack,
+        Message::Ping(1),
+        Message::Pong(2),
+        Message::Inv(vec![Inventory::block(hash.clone()), Inventory::tx(genesis.transactions[0].id.clone())]),
+        Message::GetData(vec![Inventory::block(hash.clone())]),
+        Message::NotFound(Vec::new()),
+        Message::Block(genesis.clone()),
+        Message::Tx(genesis.transactions[0].clone()),
+        Message::GetHeaders { locator: vec![hash.clone()], stop: None },
+        Message::GetHeaders { locator: Vec::new(), stop: Some(hash.clone()) },
+        Message::Headers(vec![genesis.header()]),
+        Message::GetAddr,
+        Message::Addr(vec!["127.0.0.1:8333".parse().unwrap(), "[2001:db8::1]:18444".parse().unwrap()]),
+    ];
+
+    let (mut client, mut server) = tokio::io::duplex(1 << 16);
+    for message in &messages {
+        write_message(&mut client, message).await.unwrap();
+    }
+    drop(client);
+    for message in &messages {
+        assert_eq!(&read_message(&mut server).await.unwrap(), message);
+    }
+    assert!(matches!(read_message(&mut server).await, Err(P2pError::Closed)));
+
+    let frame = encode_frame(&Message::Ping(3));
+    let mut bad_magic = frame.clone();
+    bad_magic[0] ^= 1;
+    assert!(matches!(read_message(&mut &bad_magic[..]).await, Err(P2pError::BadMagic)));
+    let mut bad_checksum = frame.clone();
+    *bad_checksum.last_mut().unwrap() ^= 1;
+    assert!(matches!(read_message(&mut &bad_checksum[..]).await, Err(P2pError::BadChecksum)));
+    let mut too_large = frame;
+    too_large[4..8].copy_from_slice(&(MAX_FRAME_SIZE as u32 + 1).to_le_bytes());
+    assert!(matches!(read_message(&mut &too_large[..]).await, Err(P2pError::FrameTooLarge(_))));
+}
+
+#[tokio::test]
+async fn nodes_on_another_chain_are_refused() {
+    let (ours, addr) = listening_node(shared_chain("alice")).await;
+    let theirs = Node::start(shared_chain("mallory"), NodeConfig::default()).unwrap();
+    let mut events = theirs.subscribe();
+
+    theirs.connect(addr).await.unwrap();
+    loop {
+        match events.recv().await.unwrap() {
+            NodeEvent::PeerDisconnected { reason, .. } => {
+                assert!(reason.contains("another chain"), "{}", reason);
+                break;
+            }
+            NodeEvent::PeerConnected(peer) => panic!("connected to {:?}", peer),
+            _ => {}
+        }
+    }
+    assert!(ours.peers().await.unwrap().is_empty());
+    assert!(theirs.peers().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn blocks_and_transactions_cross_a_line_of_nodes() {
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let (a, a_addr) = listening_node(shared_chain(&alice.get_address())).await;
+    for _ in 0..3 {
+        a.mine_block("miner-a").await.unwrap().unwrap();
+    }
+
+    // b joins a and catches up through headers, c only knows b
+    let (b, b_addr) = listening_node(shared_chain(&alice.get_address())).await;
+    let c = Node::start(shared_chain(&alice.get_address()), NodeConfig::default()).unwrap();
+    b.connect(a_addr).await.unwrap();
+    c.connect(b_addr).await.unwrap();
+    eventually("c to catch up", async || height(&c).await == 3).await;
+    assert_eq!(b.peers().await.unwrap().len(), 2);
+
+    // A transaction sent at c reaches a's mempool and a's block reaches c
+    let payer = alice.clone();
+    let payee = bob.clone();
+    let tx = c
+        .chain(move |chain| Transaction::new_utxo_transaction(&payer, &payee, 20, Fee::Fixed(1), &chain.utxo_set))
+        .await
+        .unwrap()
+        .unwrap();
+    let txid = tx.id.clone();
+    c.submit_transaction(tx).await.unwrap();
+    eventually("a to receive the transaction", async || {
+        let txid = txid.clone();
+        a.mempool(move |mempool| mempool.contains(&txid)).await.unwrap()
+    })
+    .await;
+
+    let block = a.mine_block("miner-a").await.unwrap().unwrap();
+    assert_eq!(block.transactions.len(), 2);
+    eventually("c to receive the block", async || height(&c).await == 4).await;
+    let bob_at_c = bob.clone();
+    assert_eq!(c.chain(move |chain| chain.get_balance(&bob_at_c)).await.unwrap(), 20);
+    assert!(c.mempool(|mempool| mempool.is_empty()).await.unwrap());
+
+    // c mines on top and everyone follows
+    c.mine_block("miner-c").await.unwrap().unwrap();
+    eventually("a to follow c", async || height(&a).await == 5).await;
+}
+
+#[tokio::test]
+async fn a_reorg_puts_a_chain_of_disconnected_transactions_back_in_the_mempool() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let node = Node::start(shared_chain(&alice.get_address()), NodeConfig::default()).unwrap();
+
+    // Bob spends his coin from block 1 in block 2
+    let mut chain = shared_chain(&alice.get_address());
+    let paid = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 20, Fee::Fixed(1), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![paid.clone()], "miner-a").unwrap();
+    let spent = Transaction::new_utxo_transaction(&bob, "carol", 10, Fee::Fixed(1), &chain.utxo_set).unwrap();
+    chain.add_block(vec![spent.clone()], "miner-a").unwrap();
+    for block in &chain.blocks[1..] {
+        node.submit_block(block.clone()).await.unwrap();
+    }
+
+    // A longer branch without either of them takes over
+    let mut fork = shared_chain(&alice.get_address());
+    for _ in 0..3 {
+        fork.add_block(vec![], "miner-b").unwrap();
+    }
+    for block in &fork.blocks[1..] {
+        node.submit_block(block.clone()).await.unwrap();
+    }
+    assert_eq!(height(&node).await, 3);
+    let ids = node
+        .mempool(|mempool| mempool.block_template(usize::MAX).into_iter().map(|tx| tx.id).collect::<Vec<_>>())
+        .await
+        .unwrap();
+    assert_eq!(ids, vec![paid.id, spent.id]);
+}
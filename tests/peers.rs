@@ -0,0 +1,173 @@
+use chrono::{Duration as Span, Utc};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use rust101::chain::{Block, Blockchain, ChainParams};
+use rust101::p2p::{AddressBook, AddressSource, Node, NodeConfig, NodeEvent, P2pError, MAGIC, MAX_FRAME_SIZE};
+
+fn network_chain() -> Blockchain {
+    let params = ChainParams::with_difficulty(1);
+    let genesis = Block::genesis(&params, "alice", 1_700_000_000);
+    Blockchain::with_genesis(params, genesis).unwrap()
+}
+
+async fn listening_node(config: NodeConfig) -> (Node, SocketAddr) {
+    let node = Node::start(network_chain(), config).unwrap();
+    let addr = node.listen("127.0.0.1:0").await.unwrap();
+    (node, addr)
+}
+
+/// Poll `check` until it holds, failing the test after a few seconds
+async fn eventually(what: &str, mut check: impl AsyncFnMut() -> bool) {
+    let waited = tokio::time::timeout(Duration::from_secs(10), async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "timed out waiting for {}", what);
+}
+
+#[test]
+fn config_reads_seeds_and_limits_from_the_environment() {
+    let vars = HashMap::from([
+        ("P2P_SEEDS", "127.0.0.1:8333, 10.0.0.2:18444"),
+        ("P2P_MAX_OUTBOUND", "2"),
+        ("P2P_BAN_SECONDS", "60"),
+        ("P2P_ADDRESS_BOOK", "peers.json"),
+    ]);
+    let config = NodeConfig::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap();
+    let seeds: Vec<SocketAddr> = vec!["127.0.0.1:8333".parse().unwrap(), "10.0.0.2:18444".parse().unwrap()];
+    assert_eq!(config.seeds, seeds);
+    assert_eq!(config.max_outbound, 2);
+    assert_eq!(config.max_inbound, NodeConfig::default().max_inbound);
+    assert_eq!(config.ban_duration, Duration::from_secs(60));
+    assert_eq!(config.address_book.unwrap().to_str(), Some("peers.json"));
+
+    let error = NodeConfig::from_lookup(|name| (name == "P2P_MAX_INBOUND").then(|| "many".to_string())).unwrap_err();
+    assert!(matches!(&error, P2pError::Config(message) if message.contains("P2P_MAX_INBOUND")), "{}", error);
+}
+
+#[test]
+fn address_book_keeps_addresses_and_bans_across_restarts() {
+    let path = std::env::temp_dir().join(format!("rust101-address-book-{}.json", std::process::id()));
+    let now = Utc::now();
+    let (seed, flaky, banned): (SocketAddr, SocketAddr, SocketAddr) = (
+        "10.0.0.1:8333".parse().unwrap(),
+        "10.0.0.2:8333".parse().unwrap(),
+        "10.0.0.3:8333".parse().unwrap(),
+    );
+
+    let mut book = AddressBook::open(&path).unwrap();
+    assert!(book.add(seed, AddressSource::Seed));
+    assert!(!book.add(seed, AddressSource::Gossip));
+    book.add(flaky, AddressSource::Gossip);
+    book.add(banned, AddressSource::Gossip);
+    book.attempted(&seed, now);
+    book.succeeded(&seed, now);
+    book.attempted(&flaky, now);
+    book.failed(&flaky);
+    book.ban(banned.ip(), now + Span::hours(1), "invalid block");
+    book.save().unwrap();
+
+    let mut reopened = AddressBook::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(reopened.addresses().collect::<Vec<_>>(), book.addresses().collect::<Vec<_>>());
+    assert_eq!(reopened.bans(now), book.bans(now));
+    assert_eq!(reopened.get(&seed).unwrap().last_success, Some(now));
+
+    // The banned address is skipped and the failed one waits out its backoff
+    assert_eq!(reopened.dial_candidates(now), vec![seed]);
+    assert_eq!(reopened.dial_candidates(now + Span::seconds(3)), vec![seed, flaky]);
+    assert!(reopened.is_banned(&banned.ip(), now));
+    assert!(!reopened.is_banned(&banned.ip(), now + Span::hours(2)));
+    assert!(reopened.unban(&banned.ip()));
+    assert_eq!(reopened.dial_candidates(now + Span::seconds(3)), vec![seed, banned, flaky]);
+}
+
+#[tokio::test]
+async fn peer_sending_an_oversized_frame_is_banned_refused_and_listed_over_http() {
+    let (node, addr) = listening_node(NodeConfig::default()).await;
+    let mut events = node.subscribe();
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let mut header = MAGIC.to_vec();
+    header.extend((MAX_FRAME_SIZE as u32 + 1).to_le_bytes());
+    header.extend([0; 4]);
+    stream.write_all(&header).await.unwrap();
+    let ban = loop {
+        if let NodeEvent::PeerBanned(ban) = events.recv().await.unwrap() {
+            break ban;
+        }
+    };
+    let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+    assert_eq!(ban.ip, localhost);
+    assert!(ban.reason.contains("exceeds the size limit"), "{}", ban.reason);
+
+    // Coming back from the same address gets the connection closed straight away
+    let mut again = TcpStream::connect(addr).await.unwrap();
+    let mut buffer = [0u8; 64];
+    assert_eq!(again.read(&mut buffer).await.unwrap(), 0);
+    assert!(node.peers().await.unwrap().is_empty());
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let http = listener.local_addr().unwrap();
+    tokio::spawn(rust101::run(listener, Some(node.clone())).unwrap());
+    let get = async |path: &str| -> serde_json::Value {
+        let body = reqwest::get(format!("http://{}{}", http, path)).await.unwrap().text().await.unwrap();
+        serde_json::from_str(&body).unwrap()
+    };
+    let bans = get("/p2p/bans").await;
+    assert_eq!(bans[0]["ip"], "127.0.0.1");
+    assert_eq!(bans[0]["reason"], ban.reason);
+    assert_eq!(get("/p2p/peers").await, serde_json::json!([]));
+
+    assert!(node.unban(localhost).await.unwrap());
+    assert_eq!(get("/p2p/bans").await, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn node_dials_seeds_up_to_its_outbound_target_and_refuses_extra_inbound() {
+    let mut seeds = Vec::new();
+    let mut seed_nodes = Vec::new();
+    for _ in 0..3 {
+        let (seed, addr) = listening_node(NodeConfig::default()).await;
+        seed_nodes.push(seed);
+        seeds.push(addr);
+    }
+    let config = NodeConfig {
+        max_outbound: 2,
+        seeds: seeds.clone(),
+        ..NodeConfig::default()
+    };
+    let node = Node::start(network_chain(), config).unwrap();
+    let outbound = async || node.peers().await.unwrap().iter().filter(|peer| !peer.inbound).count();
+    eventually("two outbound peers", async || outbound().await == 2).await;
+    // A few maintenance rounds later it has not dialed the third seed
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert_eq!(outbound().await, 2);
+    let book = node.addresses().await.unwrap();
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.iter().filter(|known| known.last_success.is_some()).count(), 2);
+
+    let (guarded, guarded_addr) = listening_node(NodeConfig {
+        max_inbound: 1,
+        ..NodeConfig::default()
+    })
+    .await;
+    seed_nodes[0].connect(guarded_addr).await.unwrap();
+    eventually("the first inbound peer", async || guarded.peers().await.unwrap().len() == 1).await;
+    let mut events = seed_nodes[1].subscribe();
+    seed_nodes[1].connect(guarded_addr).await.unwrap();
+    loop {
+        if let NodeEvent::PeerDisconnected { addr, .. } = events.recv().await.unwrap()
+            && addr == guarded_addr
+        {
+            break;
+        }
+    }
+    assert_eq!(guarded.peers().await.unwrap().len(), 1);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::test_database_url;
+use rust101::chain::{Blockchain, Fee, Transaction, Wallet};
+use rust101::db::{Database, DbConfig, PgChainStore};
+use tokio_postgres::NoTls;
+
+async fn sql_client(url: &str) -> tokio_postgres::Client {
+    let (client, connection) = tokio_postgres::connect(url, NoTls).await.unwrap();
+    tokio::spawn(connection);
+    client
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database: TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn confirmed_blocks_are_queryable_with_sql() {
+    let url = test_database_url();
+    let sql = sql_client(&url).await;
+    let store = PgChainStore::new(Database::new(&DbConfig::new(url)).await.unwrap());
+    sql.batch_execute("TRUNCATE chain_blocks CASCADE").await.unwrap();
+
+    // Follow a chain while alice pays bob, then disconnect the last block
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    assert_eq!(store.catch_up(&chain.blocks).await.unwrap(), 1);
+    let follower = store.clone().follow(chain.subscribe());
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob, 20, Fee::Fixed(1), &chain.utxo_set).unwrap();
+    chain.add_block(vec![tx], "miner").unwrap();
+    chain.add_block(vec![], "miner").unwrap();
+    chain.disconnect_tip().unwrap().unwrap();
+    let blocks = chain.blocks.clone();
+    drop(chain);
+    follower.await.unwrap().unwrap();
+    assert_eq!(store.tip_height().await.unwrap(), Some(1));
+
+    let transfer = sql
+        .query_one("SELECT sender, amount, block_index, transaction_type FROM chain_transfers WHERE receiver = $1", &[&bob])
+        .await
+        .unwrap();
+    assert_eq!(transfer.get::<_, Option<String>>(0), Some(alice.get_address()));
+    assert_eq!(transfer.get::<_, i32>(1), 20);
+    assert_eq!(transfer.get::<_, i64>(2), 1);
+    assert_eq!(transfer.get::<_, String>(3), "transfer");
+
+    let balances = sql
+        .query("SELECT address, balance FROM wallet_balances ORDER BY address", &[])
+        .await
+        .unwrap();
+    let balance_of = |address: &str| {
+        balances
+            .iter()
+            .find(|row| row.get::<_, String>(0) == address)
+            .map(|row| row.get::<_, i64>(1))
+    };
+    assert_eq!(balance_of(&bob), Some(20));
+    assert_eq!(balance_of(&alice.get_address()), Some(50 - 20 - 1));
+    assert_eq!(balance_of("miner"), Some(51));
+
+    // A catch-up that fails halfway leaves the store as it was
+    let other = Blockchain::new(1, "someone");
+    let twice = [other.blocks[0].clone(), other.blocks[0].clone()];
+    assert!(store.catch_up(&twice).await.is_err());
+    assert_eq!(store.tip_height().await.unwrap(), Some(1));
+    let genesis = sql.query_one("SELECT hash FROM chain_blocks WHERE height = 0", &[]).await.unwrap();
+    assert_eq!(genesis.get::<_, String>(0), blocks[0].hash);
+
+    // A different chain replaces everything, starting from its genesis
+    assert_eq!(store.catch_up(&other.blocks).await.unwrap(), 1);
+    assert_eq!(store.catch_up(&other.blocks).await.unwrap(), 0);
+    assert_eq!(store.catch_up(&blocks).await.unwrap(), 2);
+}
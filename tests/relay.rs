@@ -0,0 +1,226 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use rust101::chain::{Block, Blockchain, ChainParams, Fee, Transaction, Wallet};
+use rust101::p2p::{
+    Connection, InvKind, Inventory, Message, Node, NodeConfig, Version, BAN_SCORE, PROTOCOL_VERSION, TX_BURST,
+};
+
+/// A chain whose genesis pays `address`; nodes built from the same address share it
+fn shared_chain(address: &str) -> Blockchain {
+    let params = ChainParams::with_difficulty(1);
+    let genesis = Block::genesis(&params, address, 1_700_000_000);
+    Blockchain::with_genesis(params, genesis).unwrap()
+}
+
+/// Poll `check` until it holds, failing the test after a few seconds
+async fn eventually(what: &str, mut check: impl AsyncFnMut() -> bool) {
+    let waited = tokio::time::timeout(Duration::from_secs(10), async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "timed out waiting for {}", what);
+}
+
+async fn has_tx(node: &Node, txid: &str) -> bool {
+    let txid = txid.to_string();
+    node.mempool(move |mempool| mempool.contains(&txid)).await.unwrap()
+}
+
+/// A payment from `payer` built against `node`'s chain
+async fn payment(node: &Node, payer: &Wallet) -> Transaction {
+    let payer = payer.clone();
+    let payee = Wallet::new().get_address();
+    node.chain(move |chain| Transaction::new_utxo_transaction(&payer, &payee, 10, Fee::Fixed(1), &chain.utxo_set))
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+/// A peer that completes the handshake, answers `getdata` for `serves` and keeps
+/// everything the node sends it
+struct TestPeer {
+    sender: mpsc::Sender<Message>,
+    received: Arc<Mutex<Vec<Message>>>,
+}
+
+impl TestPeer {
+    async fn connect(node: &Node, host: u8, serves: Vec<Transaction>) -> TestPeer {
+        let genesis_hash = node.chain(|chain| chain.blocks[0].hash.clone()).await.unwrap();
+        let addr = SocketAddr::from(([10, 0, 0, host], 8333));
+        let (connection, peer) = Connection::pair(SocketAddr::from(([10, 0, 0, 0], 8333)), addr);
+        node.add_connection(connection, false).await.unwrap();
+        let Connection {
+            sender, mut receiver, ..
+        } = peer;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let replies = sender.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = receiver.recv().await {
+                log.lock().unwrap().push(message.clone());
+                let reply = match message {
+                    Message::Version(_) => {
+                        let version = Version {
+                            version: PROTOCOL_VERSION,
+                            genesis_hash: genesis_hash.clone(),
+                            best_height: 0,
+                            nonce: host as u64,
+                            listen_port: 0,
+                            user_agent: "/test/".to_string(),
+                            timestamp: 0,
+                        };
+                        vec![Message::Version(version), Message::Verack]
+                    }
+                    Message::Ping(nonce) => vec![Message::Pong(nonce)],
+                    Message::GetData(items) => serves
+                        .iter()
+                        .filter(|tx| items.contains(&Inventory::tx(tx.id.clone())))
+                        .map(|tx| Message::Tx(tx.clone()))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                for message in reply {
+                    if replies.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        TestPeer { sender, received }
+    }
+
+    async fn announce(&self, txid: &str) {
+        self.sender.send(Message::Inv(vec![Inventory::tx(txid)])).await.unwrap();
+    }
+
+    /// How many `inv` and `getdata` messages the node sent naming `txid`
+    fn count(&self, txid: &str) -> (usize, usize) {
+        let item = Inventory::tx(txid);
+        let received = self.received.lock().unwrap();
+        let naming = |items: &Vec<Inventory>| items.iter().any(|i| i == &item && i.kind == InvKind::Tx);
+        let invs = received
+            .iter()
+            .filter(|message| matches!(message, Message::Inv(items) if naming(items)))
+            .count();
+        let requests = received
+            .iter()
+            .filter(|message| matches!(message, Message::GetData(items) if naming(items)))
+            .count();
+        (invs, requests)
+    }
+}
+
+#[tokio::test]
+async fn payments_and_mined_blocks_cross_a_line_of_five_nodes() {
+    let alice = Wallet::new();
+    // Only the links made here, so everything has to travel node by node
+    let config = NodeConfig {
+        max_outbound: 0,
+        ..NodeConfig::default()
+    };
+    let mut nodes: Vec<Node> = Vec::new();
+    for _ in 0..5 {
+        let node = Node::start(shared_chain(&alice.get_address()), config.clone()).unwrap();
+        let addr = node.listen("127.0.0.1:0").await.unwrap();
+        if let Some(previous) = nodes.last() {
+            previous.connect(addr).await.unwrap();
+        }
+        nodes.push(node);
+    }
+    for node in &nodes[1..4] {
+        eventually("the line to form", async || node.peers().await.unwrap().len() == 2).await;
+    }
+
+    // A payment made at one end reaches every mempool
+    let tx = payment(&nodes[4], &alice).await;
+    let txid = tx.id.clone();
+    nodes[4].submit_transaction(tx).await.unwrap();
+    for node in &nodes {
+        eventually("the payment to spread", async || has_tx(node, &txid).await).await;
+    }
+
+    // A block mined at the other end confirms it everywhere
+    let block = nodes[0].mine_block("miner").await.unwrap().unwrap();
+    assert!(block.transactions.iter().any(|tx| tx.id == txid));
+    for node in &nodes {
+        eventually("the block to spread", async || {
+            node.chain(|chain| chain.get_latest_block().hash.clone()).await.unwrap() == block.hash
+        })
+        .await;
+        assert!(!has_tx(node, &txid).await);
+    }
+}
+
+#[tokio::test]
+async fn a_transaction_announced_twice_is_fetched_once_and_only_announced_to_others() {
+    let alice = Wallet::new();
+    let node = Node::start(shared_chain(&alice.get_address()), NodeConfig::default()).unwrap();
+    let tx = payment(&node, &alice).await;
+    let first = TestPeer::connect(&node, 1, vec![tx.clone()]).await;
+    let second = TestPeer::connect(&node, 2, vec![tx.clone()]).await;
+    let bystander = TestPeer::connect(&node, 3, Vec::new()).await;
+    eventually("three peers", async || node.peers().await.unwrap().len() == 3).await;
+
+    first.announce(&tx.id).await;
+    second.announce(&tx.id).await;
+    eventually("the transaction", async || has_tx(&node, &tx.id).await).await;
+    eventually("the announcement to the bystander", async || bystander.count(&tx.id).0 == 1).await;
+    // A few more trickles change nothing
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    let (first, second) = (first.count(&tx.id), second.count(&tx.id));
+    assert_eq!(first.1 + second.1, 1, "requested {:?} and {:?}", first, second);
+    assert_eq!((first.0, second.0), (0, 0));
+    assert_eq!(bystander.count(&tx.id), (1, 0));
+}
+
+#[tokio::test]
+async fn a_transaction_request_goes_to_the_next_announcer_when_the_first_sits_on_it() {
+    let alice = Wallet::new();
+    let config = NodeConfig {
+        tx_timeout: Duration::from_millis(300),
+        ..NodeConfig::default()
+    };
+    let node = Node::start(shared_chain(&alice.get_address()), config).unwrap();
+    let tx = payment(&node, &alice).await;
+    let silent = TestPeer::connect(&node, 1, Vec::new()).await;
+    let helpful = TestPeer::connect(&node, 2, vec![tx.clone()]).await;
+    eventually("two peers", async || node.peers().await.unwrap().len() == 2).await;
+
+    silent.announce(&tx.id).await;
+    eventually("the request to the first announcer", async || silent.count(&tx.id).1 == 1).await;
+    helpful.announce(&tx.id).await;
+    eventually("the transaction", async || has_tx(&node, &tx.id).await).await;
+    assert_eq!(silent.count(&tx.id), (0, 1));
+    assert_eq!(helpful.count(&tx.id), (0, 1));
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_peer_sending_transactions_faster_than_its_allowance_is_charged_and_cut_off() {
+    let alice = Wallet::new();
+    let node = Node::start(shared_chain(&alice.get_address()), NodeConfig::default()).unwrap();
+    let tx = payment(&node, &alice).await;
+    let flooder = TestPeer::connect(&node, 1, Vec::new()).await;
+    eventually("the peer", async || node.peers().await.unwrap().len() == 1).await;
+    let score = async || node.peers().await.unwrap()[0].score;
+
+    // The whole burst is taken in, the rest and the transaction right behind it are not
+    let txids: Vec<Inventory> = (0..TX_BURST + 100).map(|i| Inventory::tx(format!("{:064x}", i))).collect();
+    flooder.sender.send(Message::Inv(txids)).await.unwrap();
+    flooder.sender.send(Message::Tx(tx.clone())).await.unwrap();
+    eventually("both to be charged", async || score().await == 40).await;
+    assert!(!has_tx(&node, &tx.id).await);
+    assert!(score().await < BAN_SCORE);
+
+    // A second later there is room again
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    flooder.sender.send(Message::Tx(tx.clone())).await.unwrap();
+    eventually("the transaction", async || has_tx(&node, &tx.id).await).await;
+    assert_eq!(score().await, 40);
+}
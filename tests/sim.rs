@@ -0,0 +1,183 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use rust101::chain::{Block, Blockchain, ChainParams, Miner, MiningHandle};
+use rust101::p2p::{LinkConditions, Node, NodeConfig, SimNetwork, SimStats};
+
+const PORT: u16 = 8333;
+
+fn host(n: u8) -> IpAddr {
+    IpAddr::from([10, 0, 0, n])
+}
+
+fn shared_chain() -> Blockchain {
+    let params = ChainParams::with_difficulty(1);
+    let genesis = Block::genesis(&params, "genesis", 1_700_000_000);
+    Blockchain::with_genesis(params, genesis).unwrap()
+}
+
+/// Start nodes at 10.0.0.1 to 10.0.0.`count` that have each other as seeds
+async fn start_nodes(network: &SimNetwork, count: u8) -> Vec<Node> {
+    let addrs: Vec<SocketAddr> = (1..=count).map(|n| SocketAddr::new(host(n), PORT)).collect();
+    let mut nodes = Vec::new();
+    for addr in &addrs {
+        let config = NodeConfig {
+            seeds: addrs.iter().filter(|seed| *seed != addr).copied().collect(),
+            ping_interval: Duration::from_secs(10),
+            ..NodeConfig::default()
+        };
+        let node = Node::start_on(shared_chain(), config, network.transport(addr.ip())).unwrap();
+        node.listen(addr).await.unwrap();
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Poll `check` until it holds, failing the test after an hour of virtual time
+async fn eventually(what: &str, mut check: impl AsyncFnMut() -> bool) {
+    let waited = tokio::time::timeout(Duration::from_secs(3600), async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "timed out waiting for {}", what);
+}
+
+async fn tip(node: &Node) -> String {
+    node.chain(|chain| chain.get_latest_block().hash.clone()).await.unwrap()
+}
+
+async fn all_at(nodes: &[Node], hash: &str) -> bool {
+    for node in nodes {
+        if tip(node).await != hash {
+            return false;
+        }
+    }
+    true
+}
+
+/// Who mined each block of `node`'s active chain after genesis
+async fn miners(node: &Node) -> Vec<String> {
+    node.chain(|chain| {
+        chain.blocks[1..]
+            .iter()
+            .map(|block| block.transactions[0].vin[0].signature.clone())
+            .collect()
+    })
+    .await
+    .unwrap()
+}
+
+/// Mine a block on `node`'s tip on this thread, so no virtual time passes
+async fn mine(node: &Node, miner: &str) -> Block {
+    let miner = miner.to_string();
+    let mut block = node
+        .chain(move |chain| chain.prepare_block(Vec::new(), &miner))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(Miner::new(1).mine(&mut block, &MiningHandle::new()).found());
+    node.submit_block(block.clone()).await.unwrap();
+    block
+}
+
+async fn mesh(nodes: &[Node]) -> bool {
+    for node in nodes {
+        if node.peers().await.unwrap().len() < nodes.len() - 1 {
+            return false;
+        }
+    }
+    true
+}
+
+#[tokio::test(start_paused = true)]
+async fn halves_of_a_partition_fork_and_reorganize_onto_the_longer_chain_once_healed() {
+    let network = SimNetwork::new(1);
+    network.set_conditions(LinkConditions {
+        latency: Duration::from_millis(50),
+        ..LinkConditions::default()
+    });
+    let nodes = start_nodes(&network, 4).await;
+    eventually("a full mesh", async || mesh(&nodes).await).await;
+
+    network.partition(&[&[host(1), host(2)], &[host(3), host(4)]]);
+    let left = mine(&nodes[0], "left").await;
+    mine(&nodes[2], "right").await;
+    let right = mine(&nodes[2], "right").await;
+    eventually("each half to take its own block", async || {
+        all_at(&nodes[..2], &left.hash).await && all_at(&nodes[2..], &right.hash).await
+    })
+    .await;
+    // Pings across the split go unanswered until the halves drop each other
+    eventually("the halves to lose each other", async || {
+        nodes[0].peers().await.unwrap().len() == 1 && nodes[2].peers().await.unwrap().len() == 1
+    })
+    .await;
+
+    network.heal();
+    eventually("everyone on the longer chain", async || all_at(&nodes, &right.hash).await).await;
+    eventually("the mesh to come back", async || mesh(&nodes).await).await;
+    assert_eq!(miners(&nodes[0]).await, ["Block 1 reward to right", "Block 2 reward to right"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_fork_at_equal_height_resolves_once_one_side_extends_it() {
+    let network = SimNetwork::new(2);
+    network.set_conditions(LinkConditions {
+        latency: Duration::from_millis(20),
+        ..LinkConditions::default()
+    });
+    let nodes = start_nodes(&network, 3).await;
+    eventually("a full mesh", async || mesh(&nodes).await).await;
+
+    // Two blocks at height 1 at once: each node keeps the first it sees
+    network.partition(&[&[host(1)], &[host(2), host(3)]]);
+    let lone = mine(&nodes[0], "lone").await;
+    let pair = mine(&nodes[1], "pair").await;
+    eventually("the fork", async || all_at(&nodes[..1], &lone.hash).await && all_at(&nodes[1..], &pair.hash).await)
+        .await;
+    network.heal();
+
+    let winner = mine(&nodes[0], "lone").await;
+    eventually("everyone on the extended branch", async || all_at(&nodes, &winner.hash).await).await;
+    assert_eq!(miners(&nodes[2]).await, ["Block 1 reward to lone", "Block 2 reward to lone"]);
+}
+
+/// Five nodes on lossy, jittery links mining against each other; returns who mined
+/// the agreed chain and what the network carried
+fn lossy_run(seed: u64) -> (Vec<String>, SimStats) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let network = SimNetwork::new(seed);
+        network.set_conditions(LinkConditions {
+            latency: Duration::from_millis(30),
+            jitter: Duration::from_millis(120),
+            drop_rate: 0.05,
+        });
+        let nodes = start_nodes(&network, 5).await;
+        eventually("a full mesh", async || mesh(&nodes).await).await;
+
+        // Pairs of nodes mine at the same moment, so blocks race each other
+        for round in 0..4 {
+            mine(&nodes[round % 5], &format!("node{}", round % 5)).await;
+            mine(&nodes[(round + 2) % 5], &format!("node{}", (round + 2) % 5)).await;
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        let last = mine(&nodes[4], "node4").await;
+        eventually("everyone on one chain", async || all_at(&nodes, &last.hash).await).await;
+        (miners(&nodes[0]).await, network.stats())
+    })
+}
+
+#[test]
+fn lossy_links_still_converge_and_the_same_seed_repeats_the_run() {
+    let (miners, stats) = lossy_run(7);
+    assert!(miners.len() >= 5, "only {} blocks", miners.len());
+    assert!(stats.dropped > 0);
+    assert_eq!(lossy_run(7), (miners, stats));
+}
@@ -0,0 +1,152 @@
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+
+use rust101::chain::{
+    Block, BlockStatus, Blockchain, ChainError, ChainParams, ChainStore, Fee, OutPoint, StoreError, TXInput,
+    TXOutput, Transaction, Wallet,
+};
+
+/// Fresh directory for a store that must survive being closed and reopened
+fn store_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust101-store-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Mine a block on top of `parent` with a plain coinbase
+fn fork_block(chain: &Blockchain, parent: &Block, miner: &str) -> Block {
+    let height = parent.id + 1;
+    let coinbase = Transaction::new_coinbase(miner, 50, Some(format!("Fork block {} to {}", height, miner)));
+    let mut block = Block::new(height, parent.hash.clone(), vec![coinbase], chain.next_target());
+    block.timestamp = parent.timestamp + 1;
+    block.mine_block();
+    block
+}
+
+#[test]
+fn restart_resumes_from_the_stored_tip() {
+    let dir = store_dir("restart");
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+
+    let store = ChainStore::open(&dir).unwrap();
+    let mut chain = Blockchain::open(store, ChainParams::with_difficulty(1), &alice.get_address()).unwrap();
+    let tx = Transaction::new_utxo_transaction(&alice, &bob, 20, Fee::Fixed(1), &chain.utxo_set).unwrap();
+    chain.add_block(vec![tx], &alice.get_address()).unwrap();
+    chain.add_block(vec![], "miner").unwrap();
+    let tip = chain.get_latest_block().clone();
+
+    // Nothing is mined on resuming; the genesis address is ignored
+    let store = chain.into_store().unwrap();
+    let mut chain = Blockchain::open(store, ChainParams::with_difficulty(1), "someone else").unwrap();
+    assert_eq!(chain.blocks.len(), 3);
+    assert_eq!(*chain.get_latest_block(), tip);
+    assert_eq!(chain.get_balance(&bob), 20);
+    assert_eq!(chain.get_balance(&alice.get_address()), 50 + 50 - 20 - 1 + 1);
+    assert_eq!(chain.total_supply(), Ok(150));
+    assert!(chain.is_chain_valid());
+
+    chain.add_block(vec![], "miner").unwrap();
+    assert_eq!(chain.store().unwrap().tip().unwrap().unwrap().id, 3);
+
+    drop(chain);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn address_index_follows_payments_and_disconnects() {
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let store = ChainStore::temporary().unwrap();
+    let mut chain = Blockchain::open(store, ChainParams::with_difficulty(1), &alice.get_address()).unwrap();
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob, 20, Fee::Fixed(1), &chain.utxo_set).unwrap();
+    let txid = tx.id.clone();
+    chain.add_block(vec![tx], "miner").unwrap();
+
+    let store = chain.store().unwrap();
+    assert_eq!(store.balance(&bob).unwrap(), 20);
+    assert_eq!(store.utxo(&OutPoint::new(txid.clone(), 0)).unwrap(), Some(TXOutput::new(20, &bob)));
+    assert_eq!(store.utxo_set().unwrap(), chain.utxo_set);
+
+    chain.disconnect_tip().unwrap().unwrap();
+    let store = chain.store().unwrap();
+    assert_eq!(store.tip().unwrap().unwrap().hash, chain.blocks[0].hash);
+    assert_eq!(store.hash_at(1).unwrap(), None);
+    assert_eq!(store.balance(&bob).unwrap(), 0);
+    assert_eq!(store.balance(&alice.get_address()).unwrap(), 50);
+    assert_eq!(store.utxo_set().unwrap(), chain.utxo_set);
+}
+
+#[test]
+fn reorganizations_are_written_as_one_change() {
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let store = ChainStore::temporary().unwrap();
+    let mut chain = Blockchain::open(store, ChainParams::with_difficulty(1), &alice.get_address()).unwrap();
+    let genesis = chain.blocks[0].clone();
+
+    // Active chain spends the genesis coins to bob
+    let genesis_coin = OutPoint::new(genesis.transactions[0].id.clone(), 0);
+    let mut tx = Transaction {
+        id: String::new(),
+        vin: vec![TXInput::new(genesis_coin.txid, genesis_coin.vout, String::new(), alice.public_key.clone())],
+        vout: vec![TXOutput::new(50, &bob)],
+        timestamp: 1,
+    };
+    tx.sign(&alice);
+    tx.id = tx.calculate_hash();
+    chain.add_block(vec![tx], "miner").unwrap();
+
+    let side_1 = fork_block(&chain, &genesis, "rival");
+    let side_2 = fork_block(&chain, &side_1, "rival");
+    assert_eq!(chain.submit_block(side_1.clone()), Ok(BlockStatus::SideBranch));
+    assert_eq!(
+        chain.submit_block(side_2.clone()),
+        Ok(BlockStatus::Reorganized { disconnected: 1, connected: 2 })
+    );
+
+    let store = chain.store().unwrap();
+    assert_eq!(store.tip().unwrap(), Some(side_2));
+    assert_eq!(store.block_at(1).unwrap(), Some(side_1));
+    assert_eq!(store.balance(&bob).unwrap(), 0);
+    assert_eq!(store.balance(&alice.get_address()).unwrap(), 50);
+    assert_eq!(store.utxo_set().unwrap(), chain.utxo_set);
+}
+
+#[test]
+fn interrupted_reindex_is_caught_on_open_and_repaired() {
+    let dir = store_dir("reindex");
+    let alice = Wallet::new();
+    let bob = Wallet::new().get_address();
+    let store = ChainStore::open(&dir).unwrap();
+    let mut chain = Blockchain::open(store, ChainParams::with_difficulty(1), &alice.get_address()).unwrap();
+    let tx = Transaction::new_utxo_transaction(&alice, &bob, 20, Fee::Fixed(1), &chain.utxo_set).unwrap();
+    chain.add_block(vec![tx], "miner").unwrap();
+    chain.add_block(vec![], "miner").unwrap();
+
+    // Crash after the UTXO set has caught up with block 1 of 2
+    let store = chain.into_store().unwrap();
+    let crash = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        store.reindex(|height, _| assert!(height < 1, "crash")).unwrap();
+    }));
+    assert!(crash.is_err());
+    assert!(matches!(store.check_consistency(), Err(StoreError::Inconsistent(_))));
+    assert!(matches!(
+        Blockchain::open(store.clone(), ChainParams::with_difficulty(1), "").err(),
+        Some(ChainError::Storage(_))
+    ));
+
+    let mut reported = Vec::new();
+    store.reindex(|height, tip_height| reported.push((height, tip_height))).unwrap();
+    assert_eq!(reported, vec![(0, 2), (1, 2), (2, 2)]);
+
+    let chain = Blockchain::open(store, ChainParams::with_difficulty(1), "").unwrap();
+    assert_eq!(chain.get_balance(&bob), 20);
+    let mut verified = 0;
+    assert_eq!(chain.validate_with_progress(|_, _| verified += 1), Ok(()));
+    assert_eq!(verified, 3);
+
+    drop(chain);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
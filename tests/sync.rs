@@ -0,0 +1,191 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rust101::chain::{Block, BlockHeader, Blockchain, ChainParams};
+use rust101::p2p::{
+    Connection, Message, Node, NodeConfig, NodeEvent, Version, MAX_BLOCKS_IN_FLIGHT, MAX_HEADERS, PROTOCOL_VERSION,
+};
+
+/// A chain of `height` empty blocks on the genesis every test node shares
+fn mined_chain(height: u64) -> Blockchain {
+    let params = ChainParams::with_difficulty(1);
+    let genesis = Block::genesis(&params, "alice", 1_700_000_000);
+    let mut chain = Blockchain::with_genesis(params, genesis).unwrap();
+    for _ in 0..height {
+        chain.add_block(Vec::new(), "miner").unwrap();
+    }
+    chain
+}
+
+/// Poll `check` until it holds, failing the test after a few seconds
+async fn eventually(what: &str, mut check: impl AsyncFnMut() -> bool) {
+    let waited = tokio::time::timeout(Duration::from_secs(10), async {
+        while !check().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(waited.is_ok(), "timed out waiting for {}", what);
+}
+
+/// How a scripted peer behaves
+#[derive(Default)]
+struct Script {
+    serve_blocks: bool,
+    headers: Option<Vec<BlockHeader>>, // Sent instead of the chain's own headers
+    headers_delay: Duration,
+}
+
+/// Connect a peer that serves `chain` to `node` as `script` says; returns how many
+/// blocks the node asked it for
+async fn scripted_peer(node: &Node, chain: Arc<Blockchain>, script: Script) -> Arc<AtomicUsize> {
+    // Every scripted peer gets an address of its own, so a ban only hits the one
+    static NEXT_HOST: AtomicU8 = AtomicU8::new(1);
+    let addr = SocketAddr::from(([10, 0, 0, NEXT_HOST.fetch_add(1, Ordering::SeqCst)], 8333));
+    let (connection, peer) = Connection::pair(SocketAddr::from(([10, 0, 0, 0], 8333)), addr);
+    node.add_connection(connection, false).await.unwrap();
+    let Connection {
+        sender, mut receiver, ..
+    } = peer;
+
+    let requested = Arc::new(AtomicUsize::new(0));
+    let counter = requested.clone();
+    tokio::spawn(async move {
+        while let Some(Ok(message)) = receiver.recv().await {
+            let reply = match message {
+                Message::Version(_) => {
+                    let version = Version {
+                        version: PROTOCOL_VERSION,
+                        genesis_hash: chain.blocks[0].hash.clone(),
+                        best_height: chain.get_latest_block().id,
+                        nonce: rand::random(),
+                        listen_port: 0,
+                        user_agent: "/scripted/".to_string(),
+                        timestamp: 0,
+                    };
+                    let _ = sender.send(Message::Version(version)).await;
+                    vec![Message::Verack]
+                }
+                Message::Ping(nonce) => vec![Message::Pong(nonce)],
+                Message::GetHeaders { locator, stop } => {
+                    tokio::time::sleep(script.headers_delay).await;
+                    match &script.headers {
+                        Some(headers) => vec![Message::Headers(headers.clone())],
+                        None => vec![Message::Headers(chain.headers_after(&locator, stop.as_deref(), MAX_HEADERS))],
+                    }
+                }
+                Message::GetData(items) => {
+                    counter.fetch_add(items.len(), Ordering::SeqCst);
+                    if !script.serve_blocks {
+                        continue;
+                    }
+                    items
+                        .iter()
+                        .map(|item| Message::Block(chain.get_block(&item.hash).unwrap().clone()))
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            for message in reply {
+                if sender.send(message).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    requested
+}
+
+async fn tip(node: &Node) -> String {
+    node.chain(|chain| chain.get_latest_block().hash.clone()).await.unwrap()
+}
+
+#[tokio::test]
+async fn fresh_node_downloads_blocks_from_several_peers_after_their_headers() {
+    let source = Arc::new(mined_chain(60));
+    let node = Node::start(mined_chain(0), NodeConfig::default()).unwrap();
+    let mut events = node.subscribe();
+    // Both handshakes finish before either peer's headers arrive
+    let script = || Script {
+        serve_blocks: true,
+        headers_delay: Duration::from_millis(100),
+        ..Script::default()
+    };
+    let first = scripted_peer(&node, source.clone(), script()).await;
+    let second = scripted_peer(&node, source.clone(), script()).await;
+
+    let expected = source.get_latest_block().hash.clone();
+    eventually("the node to sync", async || tip(&node).await == expected).await;
+    let progress = node.sync_progress().await.unwrap();
+    assert_eq!((progress.header_height, progress.block_height), (60, 60));
+    assert!(progress.is_synced());
+    assert!(node.chain(|chain| chain.is_chain_valid()).await.unwrap());
+
+    // Both peers served their share, more than one peer could have in flight alone
+    let (first, second) = (first.load(Ordering::SeqCst), second.load(Ordering::SeqCst));
+    assert!(first > 0 && second > 0, "{} and {}", first, second);
+    assert_eq!(first + second, 60);
+    assert!(first.max(second) <= 60 - MAX_BLOCKS_IN_FLIGHT);
+
+    loop {
+        if let NodeEvent::SyncProgress(progress) = events.recv().await.unwrap()
+            && progress.is_synced()
+        {
+            assert_eq!(progress.percent(), 100.0);
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn blocks_a_peer_sits_on_are_retried_from_another() {
+    let source = Arc::new(mined_chain(40));
+    let config = NodeConfig {
+        block_timeout: Duration::from_millis(300),
+        ..NodeConfig::default()
+    };
+    let node = Node::start(mined_chain(0), config).unwrap();
+    let silent = scripted_peer(&node, source.clone(), Script::default()).await;
+    let script = Script {
+        serve_blocks: true,
+        headers_delay: Duration::from_millis(100),
+        ..Script::default()
+    };
+    let helpful = scripted_peer(&node, source.clone(), script).await;
+
+    let expected = source.get_latest_block().hash.clone();
+    eventually("the node to sync", async || tip(&node).await == expected).await;
+    assert!(silent.load(Ordering::SeqCst) > 0);
+    // Everything the silent peer was asked for came from the other one in the end
+    assert_eq!(helpful.load(Ordering::SeqCst), 40);
+    assert_eq!(node.peers().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn peers_sending_invalid_headers_are_dropped_before_any_download() {
+    let source = Arc::new(mined_chain(5));
+    let mut headers = source.headers()[1..].to_vec();
+    while headers[2].meets_target() {
+        headers[2].nonce += 1;
+    }
+    let node = Node::start(mined_chain(0), NodeConfig::default()).unwrap();
+    let mut events = node.subscribe();
+    let script = Script {
+        serve_blocks: true,
+        headers: Some(headers),
+        ..Script::default()
+    };
+    let requested = scripted_peer(&node, source, script).await;
+
+    loop {
+        if let NodeEvent::PeerDisconnected { reason, .. } = events.recv().await.unwrap() {
+            assert!(reason.contains("invalid header"), "{}", reason);
+            break;
+        }
+    }
+    assert_eq!(requested.load(Ordering::SeqCst), 0);
+    let progress = node.sync_progress().await.unwrap();
+    assert_eq!((progress.header_height, progress.block_height), (0, 0));
+}
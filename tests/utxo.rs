@@ -0,0 +1,123 @@
+use rust101::chain::{Block, Blockchain, ChainError, Fee, OutPoint, TXOutput, Transaction, UtxoSet, Wallet};
+
+#[test]
+fn spending_one_output_keeps_sibling_indices() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let charlie = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+
+    // tx1 has two outputs: 30 to Bob (vout 0) and 20 change to Alice (vout 1)
+    let tx1 = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let tx1_id = tx1.id.clone();
+    chain.add_block(vec![tx1], &charlie.get_address()).unwrap();
+
+    // Bob spends vout 0 first...
+    let tx2 = Transaction::new_utxo_transaction(&bob, &charlie.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx2], &charlie.get_address()).unwrap();
+
+    // ...and Alice's change at vout 1 must still resolve to her coin
+    let change = chain.utxo_set.get(&OutPoint::new(tx1_id.clone(), 1)).unwrap();
+    assert_eq!(change.value, 20);
+    assert_eq!(change.pub_key_hash, alice.get_address());
+    assert!(chain.utxo_set.get(&OutPoint::new(tx1_id, 0)).is_none());
+
+    let tx3 = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 20, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx3], &charlie.get_address()).unwrap();
+    assert_eq!(chain.get_balance(&alice.get_address()), 0);
+    assert_eq!(chain.get_balance(&bob.get_address()), 20);
+    assert_eq!(chain.get_balance(&charlie.get_address()), 180);
+}
+
+#[test]
+fn apply_then_undo_restores_previous_set() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+    let before = chain.utxo_set.clone();
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let coinbase = Transaction::new_coinbase(&bob.get_address(), 50, None);
+    let block = Block::new(1, chain.get_latest_block().hash.clone(), vec![coinbase, tx], chain.next_target());
+
+    let mut utxo_set = chain.utxo_set.clone();
+    let undo = utxo_set.apply_block(&block).unwrap();
+    assert_eq!(utxo_set.balance(&bob.get_address()), 60);
+    assert_eq!(undo.spent.len(), 1);
+
+    utxo_set.undo_block(&block, &undo);
+    assert_eq!(utxo_set, before);
+}
+
+#[test]
+fn failed_apply_leaves_set_untouched() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    // Two transactions spending the same genesis output
+    let tx_a = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let tx_b = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 20, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let block = Block::new(1, chain.get_latest_block().hash.clone(), vec![tx_a, tx_b], chain.next_target());
+
+    let mut utxo_set: UtxoSet = chain.utxo_set.clone();
+    assert!(matches!(utxo_set.apply_block(&block), Err(ChainError::UnknownOutput { .. })));
+    assert_eq!(utxo_set, chain.utxo_set);
+}
+
+#[test]
+fn disconnect_tip_restores_balances() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 30, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    chain.add_block(vec![tx], &bob.get_address()).unwrap();
+    assert_eq!(chain.get_balance(&bob.get_address()), 80);
+
+    let block = chain.disconnect_tip().unwrap().unwrap();
+    assert_eq!(block.id, 1);
+    assert_eq!(chain.get_balance(&alice.get_address()), 50);
+    assert_eq!(chain.get_balance(&bob.get_address()), 0);
+    assert_eq!(chain.disconnect_tip(), Ok(None));
+}
+
+#[test]
+fn outputs_never_overwrite_unspent_ones() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    // A block that pays out, spends, and then copies its own coinbase byte for byte
+    let coinbase = Transaction::new_coinbase(&bob.get_address(), 50, None);
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), 10, Fee::Fixed(0), &chain.utxo_set)
+        .unwrap();
+    let transactions = vec![coinbase.clone(), tx, coinbase.clone()];
+    let block = Block::new(1, chain.get_latest_block().hash.clone(), transactions, chain.next_target());
+
+    let mut utxo_set = chain.utxo_set.clone();
+    let duplicate = OutPoint::new(coinbase.id, 0);
+    assert_eq!(utxo_set.apply_block(&block), Err(ChainError::DuplicateOutput(duplicate)));
+    assert_eq!(utxo_set, chain.utxo_set);
+}
+
+#[test]
+fn balances_and_coin_selection_add_up_past_the_largest_output() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut utxo_set = UtxoSet::new();
+    utxo_set.insert(OutPoint::new("a".repeat(64), 0), TXOutput::new(i32::MAX - 10, &alice.get_address()));
+    utxo_set.insert(OutPoint::new("b".repeat(64), 0), TXOutput::new(i32::MAX, &alice.get_address()));
+    assert_eq!(utxo_set.balance(&alice.get_address()), 2 * i64::from(i32::MAX) - 10);
+
+    let tx = Transaction::new_utxo_transaction(&alice, &bob.get_address(), i32::MAX, Fee::Fixed(0), &utxo_set).unwrap();
+    assert_eq!(tx.vin.len(), 2);
+    assert_eq!(tx.vout[1].value, i32::MAX - 10);
+}
@@ -0,0 +1,120 @@
+use rust101::chain::{
+    verify_transactions, Blockchain, ChainError, OutPoint, TXInput, TXOutput, Transaction,
+    TxValidationError, Wallet,
+};
+
+/// Build and sign a transaction spending `inputs` (all owned by `owner`)
+fn signed_tx(owner: &Wallet, inputs: Vec<OutPoint>, outputs: Vec<TXOutput>) -> Transaction {
+    let vin = inputs
+        .into_iter()
+        .map(|o| TXInput::new(o.txid, o.vout, String::new(), owner.public_key.clone()))
+        .collect();
+    let mut tx = Transaction { id: String::new(), vin, vout: outputs, timestamp: 1 };
+    tx.sign(owner);
+    tx.id = tx.calculate_hash();
+    tx
+}
+
+fn genesis_outpoint(chain: &Blockchain) -> OutPoint {
+    OutPoint::new(chain.blocks[0].transactions[0].id.clone(), 0)
+}
+
+#[test]
+fn fee_is_inputs_minus_outputs() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = signed_tx(
+        &alice,
+        vec![genesis_outpoint(&chain)],
+        vec![TXOutput::new(30, &bob.get_address()), TXOutput::new(15, &alice.get_address())],
+    );
+    assert_eq!(tx.verify(&chain.utxo_set), Ok(5));
+}
+
+#[test]
+fn overspend_is_rejected() {
+    let alice = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = signed_tx(&alice, vec![genesis_outpoint(&chain)], vec![TXOutput::new(51, &alice.get_address())]);
+    assert_eq!(
+        tx.verify(&chain.utxo_set),
+        Err(TxValidationError::Overspend { inputs: 50, outputs: 51 })
+    );
+}
+
+#[test]
+fn same_outpoint_twice_in_one_transaction_is_rejected() {
+    let alice = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+    let outpoint = genesis_outpoint(&chain);
+
+    let tx = signed_tx(
+        &alice,
+        vec![outpoint.clone(), outpoint.clone()],
+        vec![TXOutput::new(100, &alice.get_address())],
+    );
+    assert_eq!(tx.verify(&chain.utxo_set), Err(TxValidationError::DuplicateInput(outpoint)));
+}
+
+#[test]
+fn same_outpoint_across_transactions_in_one_block_is_rejected() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let mut chain = Blockchain::new(1, &alice.get_address());
+    let outpoint = genesis_outpoint(&chain);
+
+    let to_bob = signed_tx(&alice, vec![outpoint.clone()], vec![TXOutput::new(50, &bob.get_address())]);
+    let to_alice = signed_tx(&alice, vec![outpoint.clone()], vec![TXOutput::new(50, &alice.get_address())]);
+    let expected = ChainError::InvalidTransaction {
+        txid: to_alice.id.clone(),
+        error: TxValidationError::DuplicateInput(outpoint),
+    };
+
+    assert_eq!(chain.add_block(vec![to_bob, to_alice], &bob.get_address()).unwrap_err(), expected);
+    assert_eq!(chain.blocks.len(), 1);
+    assert_eq!(chain.get_balance(&alice.get_address()), 50);
+}
+
+#[test]
+fn chained_spends_within_a_block_are_allowed() {
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let first = signed_tx(&alice, vec![genesis_outpoint(&chain)], vec![TXOutput::new(48, &bob.get_address())]);
+    let second = signed_tx(&bob, vec![OutPoint::new(first.id.clone(), 0)], vec![TXOutput::new(45, &alice.get_address())]);
+    assert_eq!(verify_transactions(&chain.utxo_set, &[first, second]), Ok(vec![2, 3]));
+}
+
+#[test]
+fn non_positive_outputs_are_rejected() {
+    let alice = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let tx = signed_tx(
+        &alice,
+        vec![genesis_outpoint(&chain)],
+        vec![TXOutput::new(60, &alice.get_address()), TXOutput::new(-10, &alice.get_address())],
+    );
+    assert_eq!(
+        tx.verify(&chain.utxo_set),
+        Err(TxValidationError::NonPositiveOutput { index: 1, value: -10 })
+    );
+}
+
+#[test]
+fn unknown_outpoint_and_foreign_coins_are_rejected() {
+    let alice = Wallet::new();
+    let mallory = Wallet::new();
+    let chain = Blockchain::new(1, &alice.get_address());
+
+    let missing = OutPoint::new("00".repeat(32), 0);
+    let tx = signed_tx(&alice, vec![missing.clone()], vec![TXOutput::new(1, &alice.get_address())]);
+    assert_eq!(tx.verify(&chain.utxo_set), Err(TxValidationError::UnknownOutpoint(missing)));
+
+    let theft = signed_tx(&mallory, vec![genesis_outpoint(&chain)], vec![TXOutput::new(50, &mallory.get_address())]);
+    assert_eq!(theft.verify(&chain.utxo_set), Err(TxValidationError::PubKeyMismatch { index: 0 }));
+}